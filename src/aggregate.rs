@@ -0,0 +1,162 @@
+/// Folds the rows of a run into running aggregates as they are logged,
+/// for runs too long to keep every row in memory.
+use colored::*;
+use std::collections::BTreeMap;
+
+use super::raw_data::{RawData, RawDataError};
+
+/// Getter of an aggregated series.
+type MetricGetter = fn(&RawData, u64) -> Result<Vec<f64>, RawDataError>;
+
+/// Series that are aggregated, by their spreadsheet column name.
+const METRICS: [(&str, MetricGetter); 8] = [
+    ("reserves_x", RawData::get_pool_x_per_lq_float),
+    ("reserves_y", RawData::get_pool_y_per_lq_float),
+    ("reported_price", RawData::get_reported_price_float),
+    ("ref_price", RawData::get_exchange_price_float),
+    ("pvf", RawData::get_portfolio_value_float),
+    ("invariant", RawData::get_invariant_float),
+    ("arb_pvf", RawData::get_arber_portfolio_value_float),
+    ("arb_gas_cost", RawData::get_arbitrageur_gas_cost),
+];
+
+/// # RunningStats
+/// Aggregates of a series over the values pushed so far.
+///
+/// # Fields
+/// * `count` - Number of values pushed. (usize)
+/// * `mean` - Mean of the values. (f64)
+/// * `min` - Smallest value. (f64)
+/// * `max` - Largest value. (f64)
+/// * `last` - Last value pushed, the value of the final row. (f64)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RunningStats {
+    pub count: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub last: f64,
+}
+
+impl Default for RunningStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            mean: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            last: 0.0,
+        }
+    }
+}
+
+impl RunningStats {
+    /// Updates the aggregates with the next value.
+    pub fn push(&mut self, value: f64) {
+        self.count += 1;
+        self.mean += (value - self.mean) / self.count as f64;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.last = value;
+    }
+}
+
+/// # StreamingAggregates
+/// Running aggregates of each metric of a run, updated a row at a time.
+///
+/// # Fields
+/// * `metrics` - Aggregates of each metric, by its spreadsheet column name. (BTreeMap<String, RunningStats>)
+/// * `last_step` - Step index of the last folded row, so a row is never folded twice. (Option<u64>)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StreamingAggregates {
+    pub metrics: BTreeMap<String, RunningStats>,
+    pub last_step: Option<u64>,
+}
+
+#[allow(unused)]
+impl StreamingAggregates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Folds the last recorded row of the pool into the aggregates.
+    /// Does nothing if the row was already folded, e.g. a step that was not recorded in on change only mode.
+    pub fn record(&mut self, raw_data: &RawData, pool_id: u64) -> Result<(), RawDataError> {
+        let step = match raw_data.step_indices.get(&pool_id).and_then(|s| s.last()) {
+            Some(step) => *step,
+            None => return Ok(()),
+        };
+        if self.last_step == Some(step) {
+            return Ok(());
+        }
+        self.last_step = Some(step);
+
+        for (name, series) in METRICS.iter() {
+            if let Some(value) = series(raw_data, pool_id)?.last() {
+                self.metrics
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(*value);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Computes the aggregates of every row recorded for the pool.
+    pub fn from_raw_data(raw_data: &RawData, pool_id: u64) -> Result<Self, RawDataError> {
+        let mut aggregates = Self::new();
+        for (name, series) in METRICS.iter() {
+            let stats = aggregates.metrics.entry(name.to_string()).or_default();
+            for value in series(raw_data, pool_id)? {
+                stats.push(value);
+            }
+        }
+        aggregates.last_step = raw_data
+            .step_indices
+            .get(&pool_id)
+            .and_then(|s| s.last().copied());
+        Ok(aggregates)
+    }
+
+    /// Prints the aggregates to the console.
+    pub fn print(&self) {
+        println!(
+            "{}\n{}",
+            "Aggregates:".bright_yellow(),
+            "------------------".bright_yellow()
+        );
+        for (name, stats) in self.metrics.iter() {
+            println!(
+                "{}: mean {} min {} max {} last {}",
+                name, stats.mean, stats.min, stats.max, stats.last
+            );
+        }
+        println!("{}", "------------------".bright_yellow());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SimConfig;
+    use crate::sim;
+
+    #[tokio::test]
+    async fn streaming_aggregates_match_full_run() {
+        let config = SimConfig::default();
+        let (raw_data, pool_id) = sim::run(&config).await.unwrap();
+        let (streamed, _) = sim::run_streaming(&config).await.unwrap();
+
+        let full = StreamingAggregates::from_raw_data(&raw_data, pool_id).unwrap();
+        assert_eq!(streamed.metrics.len(), full.metrics.len());
+        for (name, stats) in full.metrics.iter() {
+            let streamed = streamed.metrics.get(name).unwrap();
+            assert_eq!(streamed.count, stats.count, "{}", name);
+            assert!((streamed.mean - stats.mean).abs() < 1e-9, "{}", name);
+            assert_eq!(streamed.min, stats.min, "{}", name);
+            assert_eq!(streamed.max, stats.max, "{}", name);
+            assert_eq!(streamed.last, stats.last, "{}", name);
+        }
+    }
+}
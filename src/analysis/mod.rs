@@ -1,9 +1,11 @@
+pub mod price_impact;
 pub mod trading_function;
 
 /// Available analyses.
 #[allow(unused)]
 pub enum Analysis {
     TradingFunction(TradingFunctionSubtype),
+    PriceImpact,
 }
 
 /// Specific analysis to conduct on Trading Function analysis class.
@@ -11,6 +13,7 @@ pub enum Analysis {
 pub enum TradingFunctionSubtype {
     Error,
     Curve,
+    InverseError,
 }
 
 impl Default for TradingFunctionSubtype {
@@ -0,0 +1,200 @@
+/// Analyzes the price impact of swaps on the pool, the solidity `getAmountOut` against the rust approximation.
+use crate::calls::Caller;
+use crate::math::NormalCurve as RustInput;
+use crate::plots::get_coordinate_bounds;
+use itertools_num::linspace;
+use visualize::{
+    design::{Color, CurveDesign, DisplayMode},
+    plot::{transparent_plot, Axes, Curve, Display},
+};
+
+use crate::common::BASIS_POINT_DIVISOR;
+use crate::config;
+use crate::setup;
+use crate::task;
+use anyhow::anyhow;
+use arbiter::{
+    manager::SimulationManager,
+    utils::{float_to_wad, recast_address, wad_to_float},
+};
+use bindings::{
+    external_normal_strategy_lib::NormalCurve as SolidityInput, i_portfolio::PoolsReturn,
+};
+use ethers::abi::Tokenize;
+
+/// Number of swap sizes swept.
+static POINTS: usize = 100;
+/// Largest swap size swept, as a fraction of the pool's x reserve.
+static MAX_INPUT_FRACTION: f64 = 0.5;
+static DIR: &str = "./out_data";
+static FILE: &str = "price_impact_analysis";
+
+/// Plots the output of selling x to the pool across swap sizes, in solidity and rust, and their error.
+pub fn main() -> anyhow::Result<(), anyhow::Error> {
+    // Simulation config defines the key parameters that are being used to generate data.
+    let sim_config = config::main();
+    // Create the evm god.
+    let mut manager = SimulationManager::new();
+    // Deploys initial contracts and agents, and a pool to swap against.
+    setup::run(&mut manager, &sim_config)
+        .map_err(|e| anyhow!("Error in analyze price impact, setup.rs step: {}", e))?;
+    let pool_id = setup::init_pool(&manager, &sim_config)
+        .map_err(|e| anyhow!("Error in analyze price impact, init pool step: {}", e))?;
+    setup::allocate_liquidity(&manager, pool_id)?;
+
+    let (inputs, sol, rs) = price_impact_data(&manager, pool_id)?;
+
+    let error = sol
+        .iter()
+        .zip(rs.iter())
+        .map(|(x, y)| x - y)
+        .collect::<Vec<f64>>();
+
+    let curves: Vec<Curve> = vec![
+        Curve {
+            x_coordinates: inputs.clone(),
+            y_coordinates: sol,
+            design: CurveDesign {
+                color: Color::Blue,
+                color_slot: 1,
+                style: visualize::design::Style::Lines(visualize::design::LineEmphasis::Light),
+            },
+            name: Some("solidity".to_string()),
+        },
+        Curve {
+            x_coordinates: inputs.clone(),
+            y_coordinates: rs,
+            design: CurveDesign {
+                color: Color::Green,
+                color_slot: 1,
+                style: visualize::design::Style::Lines(visualize::design::LineEmphasis::Light),
+            },
+            name: Some("rust".to_string()),
+        },
+        Curve {
+            x_coordinates: inputs.clone(),
+            y_coordinates: error,
+            design: CurveDesign {
+                color: Color::Purple,
+                color_slot: 1,
+                style: visualize::design::Style::Lines(visualize::design::LineEmphasis::Light),
+            },
+            name: Some("error".to_string()),
+        },
+    ];
+
+    let (min_y, max_y) = get_coordinate_bounds(
+        curves
+            .iter()
+            .map(|x| x.y_coordinates.clone())
+            .collect::<Vec<Vec<f64>>>(),
+    );
+    let max_x = inputs.last().copied().unwrap_or(0.0);
+
+    let axes = Axes {
+        x_label: String::from("X In"),
+        y_label: String::from("Y Out"),
+        bounds: (vec![0.0, max_x], vec![min_y, max_y]),
+    };
+
+    let display = Display {
+        transparent: false,
+        mode: DisplayMode::Light,
+        show: false,
+    };
+
+    transparent_plot(
+        Some(curves),
+        None,
+        axes,
+        "Swap Price Impact".to_string(),
+        display,
+        Some(format!("{}/{}.html", DIR, FILE)),
+    );
+
+    Ok(())
+}
+
+/// Sweeps the size of a swap of x into the pool, up to `MAX_INPUT_FRACTION` of its x reserve.
+///
+/// The rust output is approximated per unit of liquidity, from the pool's curve, with the input less the
+/// swap fee the arbitrageur pays, then scaled back by the pool's liquidity.
+/// Returns the inputs, the solidity outputs, and the rust outputs, in tokens.
+fn price_impact_data(
+    manager: &SimulationManager,
+    pool_id: u64,
+) -> Result<(Vec<f64>, Vec<f64>, Vec<f64>), anyhow::Error> {
+    let arbitrageur = manager.agents.get("arbitrageur").unwrap();
+    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+    let library = manager.deployed_contracts.get("library").unwrap();
+    let caller = Caller::new(arbitrageur);
+
+    let pool: PoolsReturn = caller.view(portfolio, "pools", pool_id.into_tokens())?;
+    let curve: SolidityInput = caller.view(
+        library,
+        "getCurveConfiguration",
+        (recast_address(portfolio.address), pool_id).into_tokens(),
+    )?;
+    let curve = RustInput::from_solidity_input(&curve);
+
+    let liquidity = wad_to_float(pool.liquidity.into());
+    let fee_basis_points = task::effective_fee_basis_points(
+        pool.controller,
+        pool.fee_basis_points,
+        pool.priority_fee_basis_points,
+        recast_address(arbitrageur.address()),
+    );
+    let fee = fee_basis_points as f64 / BASIS_POINT_DIVISOR as f64;
+    let max_input = wad_to_float(pool.virtual_x.into()) * MAX_INPUT_FRACTION;
+
+    let mut inputs = Vec::<f64>::new();
+    let mut sol = Vec::<f64>::new();
+    let mut rs = Vec::<f64>::new();
+
+    // First size cannot be zero, there is no output to compare.
+    for input in linspace(0.0, max_input, POINTS + 1).skip(1) {
+        let output_sol = task::get_amount_out(manager, pool_id, true, float_to_wad(input))
+            .map_err(|e| anyhow!("Error getting the solidity amount out: {}", e))?;
+        let output_rs = curve
+            .approximate_amount_out(true, input * (1.0 - fee) / liquidity)
+            .map_err(|e| anyhow!("Error approximating the rust amount out: {:?}", e))?
+            * liquidity;
+
+        inputs.push(input);
+        sol.push(wad_to_float(output_sol));
+        rs.push(output_rs);
+    }
+
+    Ok((inputs, sol, rs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_output_tracks_solidity_output() {
+        let sim_config = config::SimConfig::default();
+        let mut manager = SimulationManager::new();
+        setup::run(&mut manager, &sim_config).unwrap();
+        let pool_id = setup::init_pool(&manager, &sim_config).unwrap();
+        setup::allocate_liquidity(&manager, pool_id).unwrap();
+
+        let (inputs, sol, rs) = price_impact_data(&manager, pool_id).unwrap();
+        assert_eq!(inputs.len(), POINTS);
+
+        // Larger swaps get more out, at a worse price.
+        assert!(sol.windows(2).all(|w| w[1] > w[0]));
+        assert!(sol.last().unwrap() / inputs.last().unwrap() < sol[0] / inputs[0]);
+
+        for ((input, out_sol), out_rs) in inputs.iter().zip(sol.iter()).zip(rs.iter()) {
+            assert!(
+                (out_sol - out_rs).abs() / out_sol < 1e-3,
+                "input: {}, sol: {}, rs: {}",
+                input,
+                out_sol,
+                out_rs
+            );
+        }
+    }
+}
@@ -1,6 +1,7 @@
 /// Analyzes the trading function solidity against the rust implementation.
 use crate::calls::{Caller, DecodedReturns};
-use crate::math::NormalCurve as RustInput;
+use crate::common::SECONDS_PER_YEAR;
+use crate::math::{CurveVariant, Graphable, NormalCurve as RustInput};
 use crate::plots::get_coordinate_bounds;
 use itertools_num::linspace;
 use visualize::{
@@ -13,6 +14,8 @@ use crate::config;
 use crate::setup;
 use anyhow::anyhow;
 use arbiter::{
+    agent::Agent,
+    environment::contract::{IsDeployed, SimulationContract},
     manager::SimulationManager,
     utils::{float_to_wad, wad_to_float},
 };
@@ -24,35 +27,21 @@ use ethers::abi::Tokenizable;
 #[derive(Clone, Debug)]
 struct Input(SolidityInput);
 
-/// Output format of the data.
-#[derive(Clone, Debug)]
-#[allow(unused)]
-struct Output {
-    pub output_sol: f64,
-    pub output_rs: f64,
-}
-
-/// Each data point.
-#[derive(Clone, Debug)]
-#[allow(unused)]
-struct DataPoint {
-    pub input: Input,
-    pub output: Output,
-}
-
-/// Collection of data.
-#[derive(Clone, Debug)]
-#[allow(unused)]
-struct Results {
-    pub data: Vec<DataPoint>,
-}
-
-static STEP: f64 = 0.001;
+/// Default distance between the swept x coordinates.
+pub static DEFAULT_STEP: f64 = 0.001;
 static DIR: &str = "./out_data";
 static FILE: &str = "trading_function_analysis";
+static INVERSE_FILE: &str = "trading_function_inverse_analysis";
+/// Max absolute error between the solidity and rust x given y.
+#[allow(unused)]
+static INVERSE_TOLERANCE: f64 = 1e-6;
+
+/// Plots the trading function error of the config's pool curve, sweeping x by `step`.
+pub fn main(subtype: TradingFunctionSubtype, step: f64) -> anyhow::Result<(), anyhow::Error> {
+    if !(step > 0.0 && step < 1.0) {
+        return Err(anyhow!("Step must be within (0, 1), got {}", step));
+    }
 
-/// Plots the trading function error.
-pub fn main(subtype: TradingFunctionSubtype) -> anyhow::Result<(), anyhow::Error> {
     // Simulation config defines the key parameters that are being used to generate data.
     let sim_config = config::main();
     // Create the evm god.
@@ -73,97 +62,8 @@ pub fn main(subtype: TradingFunctionSubtype) -> anyhow::Result<(), anyhow::Error
 
     let library = manager.deployed_contracts.get("library").unwrap();
     let admin = manager.agents.get("admin").unwrap();
-    let mut caller = Caller::new(admin);
-
-    let mut input_rs = RustInput {
-        reserve_x_per_wad: 0.308537538726,
-        reserve_y_per_wad: 0.308537538726,
-        strike_price_f: 1.0,
-        std_dev_f: 1.0,
-        time_remaining_sec: 31556953.0,
-        invariant_f: 0.0,
-    };
-
-    let mut input_sol = Input(SolidityInput {
-        reserve_x_per_wad: float_to_wad(input_rs.reserve_x_per_wad),
-        reserve_y_per_wad: float_to_wad(input_rs.reserve_y_per_wad),
-        strike_price_wad: float_to_wad(input_rs.strike_price_f),
-        standard_deviation_wad: float_to_wad(input_rs.std_dev_f),
-        time_remaining_seconds: 31556953.into(),
-        invariant: 0.into(),
-    });
-
-    let mut inputs = Vec::<Input>::new();
-    let mut sol = Vec::<f64>::new();
-    let mut rs = Vec::<f64>::new();
-
-    let mut x = 0.0;
-    let mut y = 0.0;
-
-    // Collect y coordinates from sol & rust at x coordinates with a distance of STEP.
-    // Important that x != 1.0, as that is outside the domain of the functions.
-    while x <= 1.0 {
-        let _ = y; // does nothing. Just to silence the compiler warning.
-
-        // First step cannot be zero! Undefined input for the math functions.
-        x += STEP;
-
-        // Edit the rust input.
-        input_rs.reserve_x_per_wad = x;
-
-        // Compute the rust output.
-        y = input_rs.approximate_y_given_x_floating();
-
-        // Edit the rust output.
-        rs.push(y);
-
-        // Edit the rust input with the new y value.
-        input_rs.reserve_y_per_wad = y;
-
-        // Edit the solidity input.
-        input_sol.0.reserve_x_per_wad = float_to_wad(x);
-
-        // Compute the solidity output and edit the input.
-        input_sol.0.reserve_y_per_wad = caller
-            .call(
-                library,
-                "approximateYGivenX",
-                vec![input_sol.0.clone().into_token()],
-            )?
-            .decoded(library)?;
-
-        // Edit the solidity output.
-        sol.push(wad_to_float(input_sol.0.reserve_y_per_wad));
-
-        // Add the input to the inputs vector.
-        inputs.push(input_sol.clone());
-    }
-
-    // Assert both y coordinates are the same length
-    if sol.len() != rs.len() {
-        return Err(anyhow!("sol.len() != rs.len()"));
-    }
-
-    // Compute the error solidity - rust.
-    let error = sol
-        .clone()
-        .into_iter()
-        .zip(rs.clone().into_iter())
-        .map(|(x, y)| x - y)
-        .collect::<Vec<f64>>();
-
-    // Format the data into the Results struct.
-    let mut data = Vec::<DataPoint>::new();
-
-    for i in 0..sol.len() {
-        data.push(DataPoint {
-            input: inputs[i].clone(),
-            output: Output {
-                output_sol: sol[i],
-                output_rs: rs[i],
-            },
-        });
-    }
+    let input_rs = curve_from_config(&sim_config.economic);
+    let (_, sol, rs, error) = forward_data(admin, library, &input_rs, step)?;
 
     // Plot the data.
     let len = rs.len();
@@ -242,6 +142,48 @@ pub fn main(subtype: TradingFunctionSubtype) -> anyhow::Result<(), anyhow::Error
                 Some(format!("{}/{}.html", DIR.to_string(), FILE.to_string())),
             );
         }
+        TradingFunctionSubtype::InverseError => {
+            let (y_coordinates, sol, rs) = inverse_data(admin, library, &input_rs, step)?;
+
+            let inverse_error = sol
+                .iter()
+                .zip(rs.iter())
+                .map(|(x, y)| x - y)
+                .collect::<Vec<f64>>();
+
+            let curves: Vec<Curve> = vec![Curve {
+                x_coordinates: y_coordinates.clone(),
+                y_coordinates: inverse_error,
+                design: CurveDesign {
+                    color: Color::Purple,
+                    color_slot: 1,
+                    style: visualize::design::Style::Lines(visualize::design::LineEmphasis::Light),
+                },
+                name: Some("error".to_string()),
+            }];
+
+            let (min_y, max_y) = get_coordinate_bounds(
+                curves
+                    .iter()
+                    .map(|x| x.y_coordinates.clone())
+                    .collect::<Vec<Vec<f64>>>(),
+            );
+
+            let axes = Axes {
+                x_label: String::from("Y"),
+                y_label: String::from("X Error"),
+                bounds: (vec![0.0, input_rs.strike_price_f], vec![min_y, max_y]),
+            };
+
+            transparent_plot(
+                Some(curves),
+                None,
+                axes,
+                "Trading Function Inverse Error".to_string(),
+                display,
+                Some(format!("{}/{}.html", DIR, INVERSE_FILE)),
+            );
+        }
         TradingFunctionSubtype::Curve => {
             let curves: Vec<Curve> = vec![curve_sol, curve_rs];
 
@@ -276,3 +218,229 @@ pub fn main(subtype: TradingFunctionSubtype) -> anyhow::Result<(), anyhow::Error
 
     Ok(())
 }
+
+/// Curve swept by default, used for any parameter of the config that is not a positive number.
+fn default_curve() -> RustInput {
+    RustInput {
+        reserve_x_per_wad: 0.308537538726,
+        reserve_y_per_wad: 0.308537538726,
+        strike_price_f: 1.0,
+        std_dev_f: 1.0,
+        time_remaining_sec: 31556953.0,
+        invariant_f: 0.0,
+        variant: CurveVariant::Call,
+    }
+}
+
+/// Curve with the strike, volatility, and time remaining of the config's pool.
+/// Falls back to the default curve's value for any parameter that is not a positive number.
+fn curve_from_config(economic: &config::Economic) -> RustInput {
+    let mut curve = default_curve();
+    let valid = |value: f64| value.is_finite() && value > 0.0;
+
+    if valid(economic.pool_strike_price_f) {
+        curve.strike_price_f = economic.pool_strike_price_f;
+    }
+    if valid(economic.pool_volatility_f) {
+        curve.std_dev_f = economic.pool_volatility_f;
+    }
+    if valid(economic.pool_time_remaining_years_f) {
+        curve.time_remaining_sec = economic.pool_time_remaining_years_f * SECONDS_PER_YEAR as f64;
+    }
+
+    curve
+}
+
+/// Collects the y coordinates from sol & rust at x coordinates sweeping the domain (0, 1) by `step`.
+///
+/// Shared by every subtype that plots y given x, so the error and curve plots can't drift.
+/// Returns the solidity inputs, the solidity y coordinates, the rust y coordinates, and their error, sol - rust.
+fn forward_data(
+    admin: &dyn Agent,
+    library: &SimulationContract<IsDeployed>,
+    curve: &RustInput,
+    step: f64,
+) -> Result<(Vec<Input>, Vec<f64>, Vec<f64>, Vec<f64>), anyhow::Error> {
+    let mut caller = Caller::new(admin);
+    let mut input_rs = curve.clone();
+    let mut input_sol = Input(input_rs.to_solidity_input());
+
+    let mut inputs = Vec::<Input>::new();
+    let mut sol = Vec::<f64>::new();
+    let mut rs = Vec::<f64>::new();
+
+    let mut x = 0.0;
+
+    // Collect y coordinates from sol & rust at x coordinates with a distance of step.
+    // Important that x != 1.0, as that is outside the domain of the functions.
+    while x <= 1.0 {
+        // First step cannot be zero! Undefined input for the math functions.
+        x += step;
+
+        // Edit the rust input.
+        input_rs.reserve_x_per_wad = x;
+
+        // Compute the rust output.
+        let y = input_rs.approximate_y_given_x_floating();
+
+        // Edit the rust output.
+        rs.push(y);
+
+        // Edit the rust input with the new y value.
+        input_rs.reserve_y_per_wad = y;
+
+        // Edit the solidity input.
+        input_sol.0.reserve_x_per_wad = float_to_wad(x);
+
+        // Compute the solidity output and edit the input.
+        input_sol.0.reserve_y_per_wad = caller
+            .call(
+                library,
+                "approximateYGivenX",
+                vec![input_sol.0.clone().into_token()],
+            )?
+            .decoded(library)?;
+
+        // Edit the solidity output.
+        sol.push(wad_to_float(input_sol.0.reserve_y_per_wad));
+
+        // Add the input to the inputs vector.
+        inputs.push(input_sol.clone());
+    }
+
+    // Assert both y coordinates are the same length
+    if sol.len() != rs.len() {
+        return Err(anyhow!("sol.len() != rs.len()"));
+    }
+
+    // Compute the error solidity - rust.
+    let error = sol
+        .iter()
+        .zip(rs.iter())
+        .map(|(x, y)| x - y)
+        .collect::<Vec<f64>>();
+
+    Ok((inputs, sol, rs, error))
+}
+
+/// Collects the x coordinates from sol & rust at y coordinates sweeping the domain (0, strike).
+///
+/// The rust x given y solves for x using the invariant of the curve's current reserves,
+/// so each y is taken from the curve at an x grid point to keep the invariant at zero.
+/// Returns the y coordinates, the solidity x coordinates, and the rust x coordinates.
+fn inverse_data(
+    admin: &dyn Agent,
+    library: &SimulationContract<IsDeployed>,
+    curve: &RustInput,
+    step: f64,
+) -> Result<(Vec<f64>, Vec<f64>, Vec<f64>), anyhow::Error> {
+    let mut caller = Caller::new(admin);
+    let mut input_rs = curve.clone();
+    input_rs.invariant_f = 0.0;
+    let mut input_sol = input_rs.to_solidity_input();
+
+    let mut y_coordinates = Vec::<f64>::new();
+    let mut sol = Vec::<f64>::new();
+    let mut rs = Vec::<f64>::new();
+
+    // Endpoints are excluded, they are outside the domain of the functions.
+    let mut x = 0.0;
+    while x + step < 1.0 {
+        x += step;
+        let y = input_rs.y_equals(x);
+
+        // The domain is open at both ends.
+        if y <= 0.0 || y >= input_rs.strike_price_f {
+            continue;
+        }
+
+        // Rust x given y, from the point (x, y) on the curve.
+        input_rs.reserve_x_per_wad = x;
+        rs.push(input_rs.x_equals(y));
+
+        // Solidity x given y.
+        input_sol.reserve_y_per_wad = float_to_wad(y);
+        let x_sol: ethers::types::U256 = caller
+            .call(
+                library,
+                "approximateXGivenY",
+                vec![input_sol.clone().into_token()],
+            )?
+            .decoded(library)?;
+        sol.push(wad_to_float(x_sol));
+
+        y_coordinates.push(y);
+    }
+
+    Ok((y_coordinates, sol, rs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_error_within_tolerance() {
+        let sim_config = config::SimConfig::default();
+        let mut manager = SimulationManager::new();
+        setup::run(&mut manager, &sim_config).unwrap();
+
+        let library = manager.deployed_contracts.get("library").unwrap();
+        let admin = manager.agents.get("admin").unwrap();
+        let curve = default_curve();
+
+        let (y_coordinates, sol, rs) = inverse_data(admin, library, &curve, DEFAULT_STEP).unwrap();
+        assert!(!y_coordinates.is_empty());
+
+        for ((y, x_sol), x_rs) in y_coordinates.iter().zip(sol.iter()).zip(rs.iter()) {
+            assert!(
+                (x_sol - x_rs).abs() < INVERSE_TOLERANCE,
+                "y: {}, sol: {}, rs: {}",
+                y,
+                x_sol,
+                x_rs
+            );
+        }
+    }
+
+    #[test]
+    fn forward_error_is_solidity_minus_rust() {
+        let sim_config = config::SimConfig::default();
+        let mut manager = SimulationManager::new();
+        setup::run(&mut manager, &sim_config).unwrap();
+
+        let library = manager.deployed_contracts.get("library").unwrap();
+        let admin = manager.agents.get("admin").unwrap();
+
+        let (inputs, sol, rs, error) =
+            forward_data(admin, library, &default_curve(), 0.01).unwrap();
+        assert!(!sol.is_empty());
+        assert_eq!(inputs.len(), sol.len());
+        assert_eq!(rs.len(), sol.len());
+
+        for ((e, y_sol), y_rs) in error.iter().zip(sol.iter()).zip(rs.iter()) {
+            assert_eq!(*e, y_sol - y_rs);
+        }
+    }
+
+    #[test]
+    fn curve_is_seeded_from_config_with_default_fallback() {
+        let mut economic = config::SimConfig::default().economic;
+        economic.pool_strike_price_f = 1.5;
+        economic.pool_volatility_f = 0.2;
+        economic.pool_time_remaining_years_f = 0.5;
+
+        let curve = curve_from_config(&economic);
+        assert_eq!(curve.strike_price_f, 1.5);
+        assert_eq!(curve.std_dev_f, 0.2);
+        assert_eq!(curve.time_remaining_sec, 0.5 * SECONDS_PER_YEAR as f64);
+
+        economic.pool_volatility_f = 0.0;
+        economic.pool_time_remaining_years_f = f64::NAN;
+
+        let curve = curve_from_config(&economic);
+        assert_eq!(curve.strike_price_f, 1.5);
+        assert_eq!(curve.std_dev_f, default_curve().std_dev_f);
+        assert_eq!(curve.time_remaining_sec, default_curve().time_remaining_sec);
+    }
+}
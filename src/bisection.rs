@@ -1,3 +1,6 @@
+use super::counters;
+use super::math;
+
 /// Bisection method for finding roots of a function.
 /// lower - lower bound of the search space
 /// upper - upper bound of the search space
@@ -10,6 +13,42 @@ pub struct Bisection {
     pub max_iter: f64,
 }
 
+/// Reasons the bisection method fails to find a root.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BisectionError {
+    /// `fx(lower)` and `fx(upper)` have the same sign, so the interval does not bracket a root.
+    NoSignChange { lower: f64, upper: f64 },
+    /// The interval was still wider than epsilon after `iterations`, the maximum.
+    DidNotConverge {
+        root: f64,
+        distance: f64,
+        iterations: f64,
+    },
+}
+
+impl std::fmt::Display for BisectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BisectionError::NoSignChange { lower, upper } => write!(
+                f,
+                "bisection.rs: no sign change on the interval [{}, {}]",
+                lower, upper
+            ),
+            BisectionError::DidNotConverge {
+                root,
+                distance,
+                iterations,
+            } => write!(
+                f,
+                "bisection.rs: did not converge within {} iterations, last root {} at distance {}",
+                iterations, root, distance
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BisectionError {}
+
 /// Bisection, or binary search, is a method for finding roots of a function.
 /// It works by taking a lower and upper bound, and then finding the midpoint between them.
 /// If the midpoint is the root, then we are done. Otherwise, we check if the midpoint is
@@ -31,10 +70,20 @@ impl Bisection {
 
     /// Finds the root of the function `fx` between `lower` and `upper` with a maximum error of `epsilon`.
     /// fx - function to find the root of.
-    pub fn bisection<F>(&self, fx: F) -> f64
+    /// Errors if the interval does not bracket a root, or if it does not converge within `max_iter`.
+    /// Set the `VERBOSE` env variable to print the convergence diagnostics.
+    pub fn bisection<F>(&self, fx: F) -> Result<f64, BisectionError>
     where
         F: Fn(f64) -> f64,
     {
+        counters::record_solver_call();
+        if fx(self.lower) * fx(self.upper) > 0.0 {
+            return Err(BisectionError::NoSignChange {
+                lower: self.lower,
+                upper: self.upper,
+            });
+        }
+
         let mut root = 0.0;
         let mut distance = self.upper - self.lower;
         let mut iterations = 0.0;
@@ -55,11 +104,278 @@ impl Bisection {
             iterations += 1.0;
         }
 
-        println!(
-            "found root at distance {} less than epsilon {} in {} iterations",
-            distance, self.epsilon, iterations
-        );
-        root
+        if distance > self.epsilon {
+            return Err(BisectionError::DidNotConverge {
+                root,
+                distance,
+                iterations,
+            });
+        }
+
+        if math::verbose() {
+            println!(
+                "found root at distance {} less than epsilon {} in {} iterations",
+                distance, self.epsilon, iterations
+            );
+        }
+        Ok(root)
+    }
+
+    /// Finds the root of the function `fx` with `NewtonRaphson`, starting at the midpoint of the interval.
+    /// The derivative is approximated with a central difference of `derivative_step`.
+    /// Returns `None` if the derivative is near zero, the root is outside the interval,
+    /// or it does not converge within `max_iter`.
+    pub fn newton<F>(&self, fx: F, derivative_step: f64) -> Option<f64>
+    where
+        F: Fn(f64) -> f64,
+    {
+        let dfx = |x: f64| {
+            let slope =
+                (fx(x + derivative_step) - fx(x - derivative_step)) / (2.0 * derivative_step);
+            // A flat derivative steps far off, so it is treated as zero.
+            if slope.abs() < MIN_SLOPE {
+                0.0
+            } else {
+                slope
+            }
+        };
+
+        NewtonRaphson::new((self.lower + self.upper) / 2.0, self.epsilon, self.max_iter)
+            .newton_raphson_with_derivative(&fx, dfx)
+            .filter(|root| (self.lower..=self.upper).contains(root))
+    }
+
+    /// Finds the root of the function `fx` with newton's method,
+    /// falling back to bisection if newton's method does not find a root within the interval.
+    /// fx - function to find the root of.
+    /// derivative_step - step size of the central difference approximating the derivative.
+    pub fn solve<F>(&self, fx: F, derivative_step: f64) -> Result<f64, BisectionError>
+    where
+        F: Fn(f64) -> f64,
+    {
+        match self.newton(&fx, derivative_step) {
+            Some(root) => Ok(root),
+            None => self.bisection(fx),
+        }
+    }
+}
+
+/// Brent's method for finding roots of a function, a drop-in for `Bisection` that converges faster.
+/// lower - lower bound of the search space
+/// upper - upper bound of the search space
+/// epsilon - maximum error between root and discovered value
+/// max_iter - maximum number of iterations to perform
+pub struct Brent {
+    pub lower: f64,
+    pub upper: f64,
+    pub epsilon: f64,
+    pub max_iter: f64,
+}
+
+/// Brent's method keeps a bracket around the root like bisection, but steps with inverse quadratic
+/// interpolation through the last three points, or the secant through the last two, when that step
+/// stays well inside the bracket. Otherwise it bisects, so it never converges slower than bisection,
+/// and converges superlinearly near a smooth root.
+#[allow(unused)]
+impl Brent {
+    /// Creates a new brent object.
+    pub fn new(lower: f64, upper: f64, epsilon: f64, max_iter: f64) -> Self {
+        Self {
+            lower,
+            upper,
+            epsilon,
+            max_iter,
+        }
+    }
+
+    /// Finds the root of the function `fx` between `lower` and `upper` with a maximum error of `epsilon`.
+    /// fx - function to find the root of.
+    /// Errors if the interval does not bracket a root, or if it does not converge within `max_iter`.
+    /// Set the `VERBOSE` env variable to print the convergence diagnostics.
+    pub fn solve<F>(&self, fx: F) -> Result<f64, BisectionError>
+    where
+        F: Fn(f64) -> f64,
+    {
+        counters::record_solver_call();
+        // `b` is the best estimate of the root, `c` the other end of the bracket, `a` the previous estimate.
+        let (mut a, mut b) = (self.lower, self.upper);
+        let (mut fa, mut fb) = (fx(a), fx(b));
+        if fa * fb > 0.0 {
+            return Err(BisectionError::NoSignChange {
+                lower: self.lower,
+                upper: self.upper,
+            });
+        }
+
+        let (mut c, mut fc) = (b, fb);
+        // Last step, and the step before it, which interpolated steps must shrink faster than.
+        let mut step = b - a;
+        let mut last_step = step;
+        let mut iterations = 0.0;
+
+        while iterations < self.max_iter {
+            // Keeps the root bracketed between b and c.
+            if (fb > 0.0 && fc > 0.0) || (fb < 0.0 && fc < 0.0) {
+                c = a;
+                fc = fa;
+                step = b - a;
+                last_step = step;
+            }
+            if fc.abs() < fb.abs() {
+                a = b;
+                b = c;
+                c = a;
+                fa = fb;
+                fb = fc;
+                fc = fa;
+            }
+
+            let tolerance = 2.0 * f64::EPSILON * b.abs() + 0.5 * self.epsilon;
+            let midpoint = 0.5 * (c - b);
+            if midpoint.abs() <= tolerance || fb == 0.0 {
+                if math::verbose() {
+                    println!(
+                        "found root at distance {} less than epsilon {} in {} iterations",
+                        (c - b).abs(),
+                        self.epsilon,
+                        iterations
+                    );
+                }
+                return Ok(b);
+            }
+
+            if last_step.abs() >= tolerance && fa.abs() > fb.abs() {
+                let s = fb / fa;
+                let (mut p, mut q) = if a == c {
+                    // Secant through a and b.
+                    (2.0 * midpoint * s, 1.0 - s)
+                } else {
+                    // Inverse quadratic interpolation through a, b, and c.
+                    let q = fa / fc;
+                    let r = fb / fc;
+                    (
+                        s * (2.0 * midpoint * q * (q - r) - (b - a) * (r - 1.0)),
+                        (q - 1.0) * (r - 1.0) * (s - 1.0),
+                    )
+                };
+                if p > 0.0 {
+                    q = -q;
+                } else {
+                    p = -p;
+                }
+
+                // Takes the interpolated step only if it lands inside the bracket and shrinks fast enough.
+                if 2.0 * p < (3.0 * midpoint * q - (tolerance * q).abs()).min((last_step * q).abs())
+                {
+                    last_step = step;
+                    step = p / q;
+                } else {
+                    step = midpoint;
+                    last_step = midpoint;
+                }
+            } else {
+                step = midpoint;
+                last_step = midpoint;
+            }
+
+            a = b;
+            fa = fb;
+            b += if step.abs() > tolerance {
+                step
+            } else {
+                tolerance.copysign(midpoint)
+            };
+            fb = fx(b);
+            iterations += 1.0;
+        }
+
+        Err(BisectionError::DidNotConverge {
+            root: b,
+            distance: (c - b).abs(),
+            iterations,
+        })
+    }
+}
+
+/// Newton-Raphson method for finding roots of a function.
+/// initial_guess - starting point of the search
+/// epsilon - maximum step size, or function output, at which the root is considered found
+/// max_iter - maximum number of iterations to perform
+pub struct NewtonRaphson {
+    pub initial_guess: f64,
+    pub epsilon: f64,
+    pub max_iter: f64,
+}
+
+/// Step size used to approximate the derivative with a central difference.
+const DERIVATIVE_STEP: f64 = 1e-7;
+
+/// Slope below which `Bisection::newton` treats the derivative as flat and falls back to bisection.
+const MIN_SLOPE: f64 = 1e-12;
+
+/// Newton-Raphson starts from an initial guess and repeatedly steps to the root of the
+/// function's tangent line at the current guess, x = x - f(x) / f'(x). It converges much faster
+/// than bisection near a root, but can diverge if the derivative is flat or the guess is poor,
+/// so it returns `None` instead of a root in that case.
+#[allow(unused)]
+impl NewtonRaphson {
+    /// Creates a new newton-raphson object.
+    pub fn new(initial_guess: f64, epsilon: f64, max_iter: f64) -> Self {
+        Self {
+            initial_guess,
+            epsilon,
+            max_iter,
+        }
+    }
+
+    /// Finds the root of the function `fx`, approximating its derivative numerically.
+    /// fx - function to find the root of.
+    pub fn newton_raphson<F>(&self, fx: F) -> Option<f64>
+    where
+        F: Fn(f64) -> f64,
+    {
+        let dfx =
+            |x: f64| (fx(x + DERIVATIVE_STEP) - fx(x - DERIVATIVE_STEP)) / (2.0 * DERIVATIVE_STEP);
+        self.newton_raphson_with_derivative(&fx, dfx)
+    }
+
+    /// Finds the root of the function `fx` using its derivative `dfx`.
+    /// fx - function to find the root of.
+    /// dfx - derivative of `fx`.
+    pub fn newton_raphson_with_derivative<F, D>(&self, fx: F, dfx: D) -> Option<f64>
+    where
+        F: Fn(f64) -> f64,
+        D: Fn(f64) -> f64,
+    {
+        counters::record_solver_call();
+        let mut root = self.initial_guess;
+        let mut iterations = 0.0;
+
+        while iterations < self.max_iter {
+            let output = fx(root);
+            if output.abs() < self.epsilon {
+                return Some(root);
+            }
+
+            let slope = dfx(root);
+            if slope == 0.0 || !slope.is_finite() {
+                return None;
+            }
+
+            let step = output / slope;
+            root -= step;
+            iterations += 1.0;
+
+            if !root.is_finite() {
+                return None;
+            }
+
+            if step.abs() < self.epsilon {
+                return Some(root);
+            }
+        }
+
+        None
     }
 }
 
@@ -71,7 +387,120 @@ mod tests {
         // basic polynomial function
         let fx = |x: f64| x.powi(3) - x.powi(2) + 2.0;
         let bisection = super::Bisection::new(-200.0, 300.0, 0.0001, 1000.0);
-        let root = bisection.bisection(fx);
+        let root = bisection.bisection(fx).unwrap();
         assert!((root - -1.0).abs() < 0.0001); // about 1, but floating point error!
     }
+
+    #[test]
+    fn newton_raphson_finds_same_root_as_bisection() {
+        let fx = |x: f64| x.powi(3) - x.powi(2) + 2.0;
+        let dfx = |x: f64| 3.0 * x.powi(2) - 2.0 * x;
+
+        let bisection = super::Bisection::new(-200.0, 300.0, 0.0001, 1000.0)
+            .bisection(fx)
+            .unwrap();
+        let newton = super::NewtonRaphson::new(-2.0, 1e-12, 100.0);
+        let analytic = newton.newton_raphson_with_derivative(fx, dfx).unwrap();
+        let numeric = newton.newton_raphson(fx).unwrap();
+
+        assert!((analytic - -1.0).abs() < 1e-9);
+        assert!((numeric - -1.0).abs() < 1e-9);
+        assert!((analytic - bisection).abs() < 0.0001);
+    }
+
+    #[test]
+    fn brent_takes_fewer_evaluations_than_bisection() {
+        use std::cell::Cell;
+
+        let evaluations = Cell::new(0);
+        let fx = |x: f64| {
+            evaluations.set(evaluations.get() + 1);
+            x.powi(3) - x.powi(2) + 2.0
+        };
+
+        let root = super::Bisection::new(-200.0, 300.0, 0.0001, 1000.0)
+            .bisection(fx)
+            .unwrap();
+        let bisection_evaluations = evaluations.replace(0);
+
+        let brent_root = super::Brent::new(-200.0, 300.0, 0.0001, 1000.0)
+            .solve(fx)
+            .unwrap();
+        let brent_evaluations = evaluations.get();
+
+        assert!((brent_root - -1.0).abs() < 0.0001);
+        assert!((brent_root - root).abs() < 0.0001);
+        assert!(
+            brent_evaluations < bisection_evaluations,
+            "brent: {}, bisection: {}",
+            brent_evaluations,
+            bisection_evaluations
+        );
+    }
+
+    #[test]
+    fn brent_errors_without_sign_change() {
+        let fx = |x: f64| x.powi(3) - x.powi(2) + 2.0;
+        assert_eq!(
+            super::Brent::new(0.0, 10.0, 0.0001, 1000.0).solve(fx),
+            Err(super::BisectionError::NoSignChange {
+                lower: 0.0,
+                upper: 10.0
+            })
+        );
+    }
+
+    #[test]
+    fn bisection_errors_without_sign_change() {
+        // the cubic is positive on the whole interval.
+        let fx = |x: f64| x.powi(3) - x.powi(2) + 2.0;
+        let bisection = super::Bisection::new(0.0, 10.0, 0.0001, 1000.0);
+        assert_eq!(
+            bisection.bisection(fx),
+            Err(super::BisectionError::NoSignChange {
+                lower: 0.0,
+                upper: 10.0
+            })
+        );
+    }
+
+    #[test]
+    fn bisection_errors_if_not_converged() {
+        let fx = |x: f64| x.powi(3) - x.powi(2) + 2.0;
+        let bisection = super::Bisection::new(-200.0, 300.0, 0.0001, 5.0);
+        assert!(matches!(
+            bisection.bisection(fx),
+            Err(super::BisectionError::DidNotConverge { .. })
+        ));
+    }
+
+    #[test]
+    fn newton_raphson_flat_derivative_diverges() {
+        // x = 0 is a stationary point of the cubic, so the first step is undefined.
+        let fx = |x: f64| x.powi(3) - x.powi(2) + 2.0;
+        let dfx = |x: f64| 3.0 * x.powi(2) - 2.0 * x;
+        let newton = super::NewtonRaphson::new(0.0, 1e-12, 100.0);
+
+        assert!(newton.newton_raphson_with_derivative(fx, dfx).is_none());
+    }
+
+    #[test]
+    fn newton_converges_on_root() {
+        let fx = |x: f64| x.powi(3) - x.powi(2) + 2.0;
+        let bisection = super::Bisection::new(-3.0, 2.0, 0.0001, 1000.0);
+        let root = bisection.newton(fx, 1e-7).unwrap();
+        assert!((root - -1.0).abs() < 0.0001);
+        assert!((bisection.solve(fx, 1e-7).unwrap() - root).abs() < 0.0001);
+    }
+
+    #[test]
+    fn solve_falls_back_to_bisection_on_flat_derivative() {
+        // the midpoint x = 0 is a stationary point of the cubic.
+        let fx = |x: f64| x.powi(3) - x.powi(2) + 2.0;
+        let bisection = super::Bisection::new(-2.0, 2.0, 0.0001, 1000.0);
+
+        assert!(bisection.newton(fx, 1e-7).is_none());
+        let root = bisection.solve(fx, 1e-7).unwrap();
+        assert!((root - -1.0).abs() < 0.0001);
+    }
 }
@@ -6,18 +6,23 @@ use arbiter::{
 };
 
 use bindings::i_portfolio_actions::{AllocateCall, Order, SwapCall};
+use bindings::portfolio::PORTFOLIO_ABI;
 use ethers::{
-    abi::{Tokenizable, Tokenize},
+    abi::{ParamType, Token, Tokenizable, Tokenize},
     types::{Address, U256},
 };
 
 use anyhow::{anyhow, Error, Result};
-use revm::primitives::ExecutionResult;
+use revm::primitives::{Bytes, ExecutionResult};
+
+use super::counters;
 
 /// Wraps an agent that can calls the contracts.
+/// `total_gas` accumulates the gas used by every call made, failed calls included.
 pub struct Caller<'a> {
     pub caller: &'a dyn Agent,
     pub last_call: Call,
+    pub total_gas: u64,
 }
 
 /// Represents a call to a contract.
@@ -30,6 +35,7 @@ pub struct Call {
     target: Address,
     args: Vec<ethers::abi::Token>,
     result: Option<ExecutionResult>,
+    gas_used: Option<u64>,
 }
 
 /// Uses zero addresses and empty strings as defaults.
@@ -41,6 +47,7 @@ impl Default for Call {
             target: Address::zero(),
             args: vec![],
             result: None,
+            gas_used: None,
         }
     }
 }
@@ -53,11 +60,14 @@ impl<'a> Caller<'a> {
         Caller {
             caller,
             last_call: Call::default(),
+            total_gas: 0,
         }
     }
 
-    /// Updates the last_call field, based on the last call made
+    /// Updates the last_call field, based on the last call made.
+    /// Every call sets the last call before executing, so this also counts the call.
     fn set_last_call(&mut self, last_call: Call) {
+        counters::record_evm_call();
         self.last_call = last_call;
     }
 
@@ -66,6 +76,18 @@ impl<'a> Caller<'a> {
         self.last_call.result = Some(result);
     }
 
+    /// Records the gas used by the last call, and adds it to the total.
+    fn set_last_call_gas_used(&mut self, gas_used: u64) {
+        self.last_call.gas_used = Some(gas_used);
+        self.total_gas += gas_used;
+    }
+
+    /// Gas used by the last call, including failed calls that reverted or halted.
+    /// None if the call could not be executed.
+    pub fn last_gas_used(&self) -> Option<u64> {
+        self.last_call.gas_used
+    }
+
     /// Call `res()` to get the result and error.
     /// Call `decoded()` to get the decoded result.
     /// These are terminal methods for the caller.
@@ -89,6 +111,7 @@ impl<'a> Caller<'a> {
             target: recast_address(contract.address),
             args: args.clone(),
             result: None,
+            gas_used: None,
         });
 
         let result = self.caller.call(contract, function_name, args.clone());
@@ -99,6 +122,69 @@ impl<'a> Caller<'a> {
         Ok(self)
     }
 
+    /// Performs a read only call and returns its raw output bytes.
+    /// Never touches the last call or the total gas, so `res()` and `decoded()`
+    /// keep referring to the last transaction, and its error context is preserved.
+    pub fn call_static(
+        &self,
+        contract: &SimulationContract<IsDeployed>,
+        function_name: &str,
+        args: Vec<ethers::abi::Token>,
+    ) -> Result<Bytes, Error> {
+        counters::record_evm_call();
+        let call = Call {
+            from: recast_address(self.caller.address()),
+            function_name: function_name.to_string(),
+            target: recast_address(contract.address),
+            args: args.clone(),
+            result: None,
+            gas_used: None,
+        };
+
+        let result = match self.caller.call(contract, function_name, args) {
+            Ok(result) => result,
+            Err(e) => {
+                return Err(anyhow!(
+                    "calls.rs: failed to static call {:?}: msg: {:?}",
+                    call,
+                    e.to_string()
+                ))
+            }
+        };
+        if !result.is_success() {
+            return Err(anyhow!(
+                "calls.rs: {:?} static call failed: {}: {:?}",
+                call,
+                failure_reason(&result),
+                result
+            ));
+        }
+
+        Ok(unpack_execution(result)?)
+    }
+
+    /// Calls a view function and returns its decoded output, for reads interleaved with transactions.
+    /// Leaves the last call and the total gas as they were, so they keep tracking the last transaction.
+    pub fn view<T: Tokenizable>(
+        &self,
+        contract: &SimulationContract<IsDeployed>,
+        function_name: &str,
+        args: Vec<ethers::abi::Token>,
+    ) -> Result<T, Error> {
+        let return_bytes = self.call_static(contract, function_name, args)?;
+        if return_bytes.len() == 0 {
+            return Err(anyhow!(
+                "calls.rs: static call of {} returned empty bytes",
+                function_name
+            ));
+        }
+
+        counters::record_decode();
+        contract
+            .decode_output(function_name, return_bytes)
+            .map_err(|e| anyhow!("calls.rs: failed to decode output: {:?}", e.to_string()))
+    }
+
     pub fn balance_of(&mut self, token: &SimulationContract<IsDeployed>) -> &mut Self {
         let owner = recast_address(self.caller.address().clone()).clone();
         self.set_last_call(Call {
@@ -107,6 +193,7 @@ impl<'a> Caller<'a> {
             target: recast_address(token.address),
             args: (owner).into_tokens(),
             result: None,
+            gas_used: None,
         });
 
         let result = self.caller.call(token, "balanceOf", (owner).into_tokens());
@@ -134,6 +221,7 @@ impl<'a> Caller<'a> {
             target: recast_address(token.address),
             args: (spender, amount.clone()).into_tokens(),
             result: None,
+            gas_used: None,
         });
 
         let result = self
@@ -163,6 +251,7 @@ impl<'a> Caller<'a> {
             target: recast_address(token.address),
             args: (to, amount.clone()).into_tokens(),
             result: None,
+            gas_used: None,
         });
 
         let result = self
@@ -204,6 +293,7 @@ impl<'a> Caller<'a> {
             target: recast_address(portfolio.address),
             args: args.clone().into_tokens(),
             result: None,
+            gas_used: None,
         });
 
         let result = self
@@ -215,6 +305,17 @@ impl<'a> Caller<'a> {
         self
     }
 
+    /// Creates a pair of the `asset` and `quote` tokens on portfolio and returns the new pair's id.
+    pub fn create_pair(
+        &mut self,
+        portfolio: &SimulationContract<IsDeployed>,
+        asset: Address,
+        quote: Address,
+    ) -> Result<u32, Error> {
+        self.call(portfolio, "createPair", (asset, quote).into_tokens())?
+            .decoded(portfolio)
+    }
+
     /// For swapping on portfolio
     pub fn swap(
         &mut self,
@@ -231,6 +332,7 @@ impl<'a> Caller<'a> {
             target: recast_address(portfolio.address),
             args: args.clone().into_tokens(),
             result: None,
+            gas_used: None,
         });
 
         let result = self
@@ -249,6 +351,9 @@ impl<'a> Caller<'a> {
     ) -> Result<ExecutionResult, Error> {
         match tx_result {
             Ok(res) => {
+                // Failed calls still consume gas before they revert.
+                self.set_last_call_gas_used(res.gas_used());
+
                 if res.is_success() {
                     /*let return_bytes = unpack_execution(res.clone()).unwrap();
 
@@ -267,8 +372,9 @@ impl<'a> Caller<'a> {
                     return Ok(res);
                 } else {
                     return Err(anyhow!(
-                        "calls.rs: {:?} call failed: {:?}",
+                        "calls.rs: {:?} call failed: {}: {:?}",
                         self.last_call,
+                        failure_reason(&res),
                         res
                     ));
                 }
@@ -285,6 +391,64 @@ impl<'a> Caller<'a> {
     }
 }
 
+/// Selector of the `Error(string)` revert of `require` and `revert` with a message.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Selector of the `Panic(uint256)` revert of failed assertions and arithmetic errors.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Human readable reason a call failed: the decoded revert of a reverted call, or the halt reason.
+pub fn failure_reason(result: &ExecutionResult) -> String {
+    match result {
+        ExecutionResult::Revert { output, .. } => decode_revert(output),
+        ExecutionResult::Halt { reason, .. } => format!("halted: {:?}", reason),
+        ExecutionResult::Success { .. } => "succeeded".to_string(),
+    }
+}
+
+/// Decodes revert output into the error's name and params, e.g. `Portfolio_InvalidInvariant(1, 0)`.
+/// Knows the `Error(string)` and `Panic(uint256)` reverts and the custom errors of the portfolio ABI.
+/// Falls back to the hex of the output if the error is unknown or its params don't decode.
+pub fn decode_revert(output: &[u8]) -> String {
+    let fallback = || format!("0x{}", hex::encode(output));
+    if output.len() < 4 {
+        return fallback();
+    }
+    let (selector, data) = output.split_at(4);
+
+    let decoded = if selector == ERROR_SELECTOR {
+        ethers::abi::decode(&[ParamType::String], data)
+            .ok()
+            .map(|tokens| ("Error".to_string(), tokens))
+    } else if selector == PANIC_SELECTOR {
+        ethers::abi::decode(&[ParamType::Uint(256)], data)
+            .ok()
+            .map(|tokens| ("Panic".to_string(), tokens))
+    } else {
+        PORTFOLIO_ABI
+            .errors()
+            .find(|error| error.signature()[..4] == *selector)
+            .and_then(|error| {
+                error
+                    .decode(data)
+                    .ok()
+                    .map(|tokens| (error.name.clone(), tokens))
+            })
+    };
+
+    match decoded {
+        Some((name, tokens)) => format!(
+            "{}({})",
+            name,
+            tokens
+                .iter()
+                .map(Token::to_string)
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        None => fallback(),
+    }
+}
+
 /// Decodes the last call's result into a tokenizable type.
 pub trait DecodedReturns {
     fn decoded<T: Tokenizable>(
@@ -299,6 +463,7 @@ impl DecodedReturns for Caller<'_> {
         &self,
         contract: &SimulationContract<IsDeployed>,
     ) -> Result<T, Error> {
+        counters::record_decode();
         let result = self.last_call.result.clone();
         let result = match result {
             Some(result) => result,
@@ -360,6 +525,91 @@ mod tests {
         }
     }
 
+    #[test]
+    fn view_keeps_last_call() {
+        let mut manager = manager::SimulationManager::new();
+
+        let admin = manager.agents.get("admin").unwrap();
+
+        let contract = SimulationContract::new(weth::WETH_ABI.clone(), weth::WETH_BYTECODE.clone());
+        let (contract, _) = admin.deploy(contract, vec![]).unwrap();
+
+        let mut caller = Caller::new(admin);
+        caller
+            .approve(&contract, Address::zero(), 0.0)
+            .res()
+            .unwrap();
+        let total_gas = caller.total_gas;
+
+        let balance: U256 = caller
+            .view(
+                &contract,
+                "balanceOf",
+                recast_address(admin.address()).into_tokens(),
+            )
+            .unwrap();
+
+        assert_eq!(balance, U256::zero());
+        assert_eq!(caller.last_call.function_name, "approve");
+        assert_eq!(caller.total_gas, total_gas);
+    }
+
+    #[test]
+    fn static_call_keeps_write_error_context() {
+        let mut manager = manager::SimulationManager::new();
+
+        let admin = manager.agents.get("admin").unwrap();
+
+        let contract = SimulationContract::new(weth::WETH_ABI.clone(), weth::WETH_BYTECODE.clone());
+        let (contract, _) = admin.deploy(contract, vec![]).unwrap();
+
+        let mut caller = Caller::new(admin);
+        // Reverts, nothing was approved.
+        let failed = caller.transfer_from(&contract, Address::zero(), 1.0).res();
+        assert!(failed.is_err());
+        let total_gas = caller.total_gas;
+
+        let balance = caller
+            .call_static(
+                &contract,
+                "balanceOf",
+                recast_address(admin.address()).into_tokens(),
+            )
+            .unwrap();
+        assert_eq!(balance.len(), 32);
+
+        // The read did not clobber the failed write.
+        assert_eq!(caller.last_call.function_name, "transferFrom");
+        assert!(caller.res().is_err());
+        assert_eq!(caller.total_gas, total_gas);
+
+        // The next write replaces it as usual.
+        caller
+            .approve(&contract, Address::zero(), 0.0)
+            .res()
+            .unwrap();
+        assert_eq!(caller.last_call.function_name, "approve");
+    }
+
+    #[test]
+    fn revert_output_decodes_to_error_name() {
+        // Require messages.
+        let mut output = ERROR_SELECTOR.to_vec();
+        output.extend(ethers::abi::encode(&[Token::String("nope".to_string())]));
+        assert_eq!(decode_revert(&output), "Error(nope)");
+
+        // Custom errors of the portfolio.
+        let error = PORTFOLIO_ABI
+            .errors()
+            .find(|error| error.inputs.is_empty())
+            .unwrap();
+        let output = error.encode(&[]).unwrap();
+        assert_eq!(decode_revert(&output), format!("{}()", error.name));
+
+        // Unknown selectors fall back to hex.
+        assert_eq!(decode_revert(&[0xde, 0xad, 0xbe, 0xef]), "0xdeadbeef");
+    }
+
     #[test]
     fn approve_good_contract_succeeds() {
         let mut manager = manager::SimulationManager::new();
@@ -398,4 +648,26 @@ mod tests {
             Err(e) => assert!(true),
         }
     }
+
+    #[test]
+    fn gas_is_tracked_across_calls() {
+        let mut manager = manager::SimulationManager::new();
+
+        let admin = manager.agents.get("admin").unwrap();
+
+        let contract = SimulationContract::new(weth::WETH_ABI.clone(), weth::WETH_BYTECODE.clone());
+        let (contract, _) = admin.deploy(contract, vec![]).unwrap();
+
+        let mut caller = Caller::new(admin);
+        caller.approve(&contract, Address::zero(), 0.0);
+        let approve_gas = caller.last_gas_used().unwrap();
+        assert!(approve_gas > 0);
+
+        // Failed calls still record the gas used before reverting.
+        let _ = caller.transfer_from(&contract, Address::zero(), 0.0).res();
+        let transfer_gas = caller.last_gas_used().unwrap();
+        assert!(transfer_gas > 0);
+
+        assert_eq!(caller.total_gas, approve_gas + transfer_gas);
+    }
 }
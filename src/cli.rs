@@ -4,7 +4,11 @@ use anyhow::anyhow;
 use clap::{Parser, Subcommand};
 use colored::*;
 
+use super::estimate;
+use super::event_log;
+use super::seed_sweep;
 use super::sim;
+use super::validate;
 
 /// CLI arguments.
 #[derive(Parser)]
@@ -12,11 +16,18 @@ use super::sim;
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// OPTIONAL: Path to stream the sim's events to, as newline delimited json.
+    #[arg(long, global = true)]
+    event_log: Option<String>,
 }
 
 /// CLI commands that can be run.
-/// `analyze` - Runs an analysis specified with --name and --subtype.
+/// `analyze` - Runs an analysis specified with --name, --subtype, and --step.
 /// `sim` - Runs the simulation.
+/// `seed-sweep` - Runs the simulation across many seeds, specified with --seeds.
+/// `estimate` - Estimates the runtime and output size of a simulation of --steps.
+/// `validate` - Compares a sim csv, --sim, against a theoretical model csv, --theory.
 #[derive(Subcommand)]
 enum Commands {
     /// Runs an analysis.
@@ -28,8 +39,38 @@ enum Commands {
         /// OPTIONAL: The subtype analysis to run
         #[arg(short, long)]
         subtype: Option<String>,
+
+        /// OPTIONAL: Distance between the swept x coordinates of the trading function analysis.
+        #[arg(long, default_value_t = analysis::trading_function::DEFAULT_STEP)]
+        step: f64,
     },
     Sim {},
+    /// Runs the simulation across many seeds and aggregates the summaries.
+    SeedSweep {
+        /// OPTIONAL: The number of seeds to run, starting at the config's seed.
+        #[arg(short, long, default_value_t = 10)]
+        seeds: usize,
+    },
+    /// Estimates the runtime and output size of a simulation from a short calibration run.
+    Estimate {
+        /// REQUIRED: The number of steps of the simulation to estimate.
+        #[arg(short, long)]
+        steps: usize,
+    },
+    /// Compares a sim csv against a theoretical model csv, failing if any column diverges beyond the tolerance.
+    Validate {
+        /// REQUIRED: Path to the csv output of the sim.
+        #[arg(long)]
+        sim: String,
+
+        /// REQUIRED: Path to the csv of the theoretical model, its columns are compared with the sim's.
+        #[arg(long)]
+        theory: String,
+
+        /// OPTIONAL: Largest absolute divergence of a column that passes.
+        #[arg(short, long, default_value_t = 1e-6)]
+        tolerance: f64,
+    },
 }
 
 /// Handles the cli commands argument parsing to run the sim or a specific analysis.
@@ -38,8 +79,30 @@ pub async fn main() -> anyhow::Result<(), anyhow::Error> {
 
     let start_time = std::time::Instant::now();
 
-    match &cli.command {
-        Some(Commands::Analyze { name, subtype }) => {
+    if let Some(path) = &cli.event_log {
+        event_log::open(path)?;
+    }
+
+    // Flushes the event log even if the command failed, so it keeps the events up to the failure.
+    let result = run(&cli.command, start_time).await;
+    let closed = event_log::close();
+    result?;
+    closed?;
+
+    Ok(())
+}
+
+/// Runs the command, defaulting to the simulation.
+async fn run(
+    command: &Option<Commands>,
+    start_time: std::time::Instant,
+) -> anyhow::Result<(), anyhow::Error> {
+    match command {
+        Some(Commands::Analyze {
+            name,
+            subtype,
+            step,
+        }) => {
             println!("\n{}", "Running analysis!".blue());
 
             match name.as_str() {
@@ -54,13 +117,19 @@ pub async fn main() -> anyhow::Result<(), anyhow::Error> {
                             "curve" => {
                                 subtype_to_run = analysis::TradingFunctionSubtype::Curve;
                             }
+                            "inverse_error" => {
+                                subtype_to_run = analysis::TradingFunctionSubtype::InverseError;
+                            }
                             _ => {
                                 return Err(anyhow!("Analysis subtype not found: {}", subtype));
                             }
                         }
                     }
 
-                    analysis::trading_function::main(subtype_to_run)?;
+                    analysis::trading_function::main(subtype_to_run, *step)?;
+                }
+                "price_impact" => {
+                    analysis::price_impact::main()?;
                 }
                 _ => {
                     return Err(anyhow!("Analysis not found: {}", name));
@@ -97,6 +166,46 @@ pub async fn main() -> anyhow::Result<(), anyhow::Error> {
                 "seconds to run.".bright_black(),
             );
         }
+        Some(Commands::SeedSweep { seeds }) => {
+            println!("\n{}", "Starting seed sweep!".blue());
+
+            match seed_sweep::main(*seeds).await {
+                Ok(_) => {
+                    println!("{}", "Seed sweep complete!".green());
+                }
+                Err(e) => {
+                    return Err(anyhow!("Error running seed sweep: {}", e));
+                }
+            }
+
+            let elapsed = start_time.elapsed();
+            println!(
+                "{} {} {}",
+                "Seed sweep took".bright_black(),
+                elapsed.as_secs_f64().to_string().purple().bold(),
+                "seconds to run.".bright_black(),
+            );
+        }
+        Some(Commands::Estimate { steps }) => {
+            println!("\n{}", "Starting estimate!".blue());
+
+            if let Err(e) = estimate::main(*steps).await {
+                return Err(anyhow!("Error running estimate: {}", e));
+            }
+        }
+        Some(Commands::Validate {
+            sim,
+            theory,
+            tolerance,
+        }) => {
+            println!("\n{}", "Validating against the theory!".blue());
+
+            if let Err(e) = validate::main(sim, theory, *tolerance) {
+                return Err(anyhow!("Validation failed: {}", e));
+            }
+
+            println!("{}", "Validation passed!".green());
+        }
         None => {
             println!("\n{}", "Running simulation!".blue());
 
@@ -2,10 +2,15 @@ use ethers;
 
 pub static WAD: f64 = 1_000_000_000_000_000_000.0;
 pub static ARBITRAGEUR_ADDRESS_BASE: u64 = 2_u64;
+pub static ARBITRAGEUR_INITIAL_BALANCE_F: f64 = 50.0;
+pub static EXCHANGE_INITIAL_BALANCE_F: f64 = 88888888888888.0;
 pub static FEE_BPS: u16 = 10;
 pub static VOLATILITY_F: f64 = 0.1;
 pub static BASIS_POINT_DIVISOR: u16 = 10_000;
 pub static SECONDS_PER_YEAR: u64 = 31556953;
+pub static ENTRYPOINT_TOKEN_DECIMALS: u8 = 18;
+pub static ENTRYPOINT_PAIR_ID: u32 = 1;
+pub static ADMIN_INITIAL_BALANCE_F: f64 = 4_809.0;
 
 pub trait Endian {
     fn down_endian(&self) -> ethers::types::U256;
@@ -1,11 +1,12 @@
 use super::common;
+use super::math::{InvariantOffset, InvariantSign};
 /// Configuration for the simulation.
 /// Includes all the key parameters used to generate
 /// the price process, the agent parameters,
 /// and the pool parameters.
 use arbiter::stochastic::price_process::{PriceProcess, PriceProcessType, OU};
 use colored::*;
-use config::{Config, ConfigError};
+use config::{builder::DefaultState, Config, ConfigBuilder, ConfigError};
 use serde_derive::Deserialize;
 
 /// # Economic
@@ -16,8 +17,14 @@ use serde_derive::Deserialize;
 /// * `initial_price` - Initial price process and pool price. (f64)
 /// * `pool_volatility_f` - Normal strategy pool's volatility parameter. (f64)
 /// * `pool_strike_price_f` - Normal strategy pool's strike price parameter. (f64)
-/// * `pool_time_remaining_years_f` - Normal strategy pool's time remaining seconds parameter. Note: not supported yet. (f64)
+/// * `pool_time_remaining_years_f` - Normal strategy pool's time remaining parameter, in years. Decreases by the process timestep each step unless the pool is perpetual. (f64)
 /// * `pool_is_perpetual` - Normal strategy pool's is perpetual parameter. Sets tau to be constant. (bool)
+/// * `pool_pair_id` - Id of the token pair the pool is created on, see `Caller::create_pair`. 1 is the token0/token1 pair. (u32)
+/// * `arb_fee_tolerance_bps` - Fee tolerance of the arbitrageur agent's price change detection, in basis points. Defaults to the pool fee. (Option<u16>)
+/// * `controller_fee_share` - Fraction of the fees of a controller-managed pool that the controller captures. (f64)
+/// * `price_convention` - Units of the price process and initial price. (PriceConvention)
+/// * `arbitrageur_initial_balance_f` - Amount of each token minted to the arbitrageur at setup, its trading capital. (f64)
+/// * `exchange_initial_balance_f` - Amount of each token minted to each exchange at setup, its liquidity. (f64)
 #[derive(Clone, Debug, Deserialize)]
 #[allow(unused)] // todo: use
 pub struct Economic {
@@ -27,6 +34,537 @@ pub struct Economic {
     pub pool_is_perpetual: bool,
     pub pool_fee_basis_points: u16,
     pub pool_priority_fee_basis_points: u16,
+    #[serde(default = "default_pool_pair_id")]
+    pub pool_pair_id: u32,
+    #[serde(default)]
+    pub arb_fee_tolerance_bps: Option<u16>,
+    #[serde(default)]
+    pub controller_fee_share: f64,
+    pub price_convention: PriceConvention,
+    #[serde(default = "default_arbitrageur_initial_balance_f")]
+    pub arbitrageur_initial_balance_f: f64,
+    #[serde(default = "default_exchange_initial_balance_f")]
+    pub exchange_initial_balance_f: f64,
+}
+
+/// Pair of the pool when `pool_pair_id` is omitted, the entrypoint's token0/token1 pair.
+fn default_pool_pair_id() -> u32 {
+    common::ENTRYPOINT_PAIR_ID
+}
+
+/// Arbitrageur's initial balance of each token when `arbitrageur_initial_balance_f` is omitted.
+fn default_arbitrageur_initial_balance_f() -> f64 {
+    common::ARBITRAGEUR_INITIAL_BALANCE_F
+}
+
+/// Each exchange's initial balance of each token when `exchange_initial_balance_f` is omitted.
+fn default_exchange_initial_balance_f() -> f64 {
+    common::EXCHANGE_INITIAL_BALANCE_F
+}
+
+/// # PriceConvention
+/// Units the configured prices are quoted in. The pool and exchanges always quote token1 per token0.
+///
+/// # Variants
+/// * `QuotePerBase` - Prices are token1 per token0, the same as the contracts.
+/// * `BasePerQuote` - Prices are token0 per token1, the inverse of the contracts.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceConvention {
+    QuotePerBase,
+    BasePerQuote,
+}
+
+impl PriceConvention {
+    /// Converts a price in this convention to the contracts' quote per base price.
+    /// Inverting is its own inverse, so this also converts contract prices back to this convention.
+    pub fn to_quote_per_base(&self, price: f64) -> f64 {
+        match self {
+            PriceConvention::QuotePerBase => price,
+            PriceConvention::BasePerQuote => 1.0 / price,
+        }
+    }
+}
+
+impl Economic {
+    /// Fee tolerance of the arbitrageur agent, in basis points.
+    /// The configured tolerance if there is one, else the pool fee the arbitrageur actually pays.
+    pub fn arb_threshold_bps(&self) -> u16 {
+        self.arb_fee_tolerance_bps
+            .unwrap_or(self.pool_fee_basis_points)
+    }
+
+    /// Warning if the configured fee tolerance is tighter than the pool fee,
+    /// which makes the arbitrageur react to deviations that don't cover the fee and trade at a loss.
+    pub fn arb_threshold_warning(&self) -> Option<String> {
+        let tolerance = self.arb_threshold_bps();
+        if tolerance < self.pool_fee_basis_points {
+            Some(format!(
+                "arbitrageur fee tolerance of {} bps is tighter than the pool fee of {} bps, it will make money-losing trades",
+                tolerance, self.pool_fee_basis_points
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// # Arbitrageur
+/// Defines the behavior of the arbitrageur agent.
+///
+/// # Fields
+/// * `swap_retry_shrink` - How the swap output is shrunk after a failed swap attempt. (ShrinkStrategy)
+/// * `swap_retry_shrink_factor` - Factor the swap output is multiplied by after a failed swap attempt. (f64)
+/// * `search_swap_output` - Binary searches for the largest output the pool accepts before swapping, so failed attempts are rare. (bool)
+/// * `venues` - Names of the exchanges the arbitrageur reads prices from and trades on. The first is the primary exchange. (Vec<String>)
+/// * `min_swap_output_f` - Swap orders with a smaller output, in tokens, are skipped. (f64)
+/// * `min_pool_liquidity_f` - Arbitrageur skips pools with less liquidity, where swaps slip too much. Zero disables the guard. (f64)
+/// * `direction_filter` - Reference price moves the arbitrageur acts on. (DirectionFilter)
+/// * `max_price_jump_pct` - Largest reference price change in a step, in percent, the arbitrageur arbs as is. Zero disables the breaker. (f64)
+/// * `price_jump_policy` - What the arbitrageur does on a larger change. (PriceJumpPolicy)
+/// * `no_arb_tolerance_wad` - Distance from the no-arb bounds, in wad, within which prices are treated as inside the bounds. (u64)
+/// * `prefer_direction` - Swap direction taken when the direction is ambiguous: the target is within the no-arb tolerance of both bounds, or the actor's arb input disagrees with the bounds. (PreferDirection)
+/// * `arb_interval_steps` - Arbitrageur only acts every this many steps, starting with the first, letting the pool drift in between. 0 and 1 act on every step. (usize)
+/// * `topup_amount_f` - Amount of each token minted to the arbitrageur every `topup_every` steps, simulating external capital so long runs don't deplete its inventory. 0.0 disables it. (f64)
+/// * `topup_every` - Number of steps between top ups, the first after this many steps. 0 disables it. (usize)
+/// * `trade_failure_policy` - What happens when the exchange trade fails after the portfolio swap went through. (TradeFailurePolicy)
+/// * `hedge_lag_steps` - Steps the exchange trade hedging a portfolio swap is deferred by, modeling settlement lag. 0 trades in the same step. (usize)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Arbitrageur {
+    pub swap_retry_shrink: ShrinkStrategy,
+    pub swap_retry_shrink_factor: f64,
+    pub search_swap_output: bool,
+    pub venues: Vec<String>,
+    pub min_swap_output_f: f64,
+    pub min_pool_liquidity_f: f64,
+    pub direction_filter: DirectionFilter,
+    pub max_price_jump_pct: f64,
+    pub price_jump_policy: PriceJumpPolicy,
+    pub no_arb_tolerance_wad: u64,
+    pub prefer_direction: PreferDirection,
+    pub arb_interval_steps: usize,
+    pub topup_amount_f: f64,
+    pub topup_every: usize,
+    pub trade_failure_policy: TradeFailurePolicy,
+    pub hedge_lag_steps: usize,
+}
+
+impl Default for Arbitrageur {
+    fn default() -> Self {
+        Self {
+            swap_retry_shrink: ShrinkStrategy::Factor,
+            swap_retry_shrink_factor: 0.999,
+            search_swap_output: true,
+            venues: vec!["exchange".to_string()],
+            min_swap_output_f: 0.0,
+            min_pool_liquidity_f: 0.0,
+            direction_filter: DirectionFilter::Both,
+            max_price_jump_pct: 0.0,
+            price_jump_policy: PriceJumpPolicy::Skip,
+            no_arb_tolerance_wad: 1000,
+            prefer_direction: PreferDirection::Sign,
+            arb_interval_steps: 1,
+            topup_amount_f: 0.0,
+            topup_every: 0,
+            trade_failure_policy: TradeFailurePolicy::Abort,
+            hedge_lag_steps: 0,
+        }
+    }
+}
+
+impl Arbitrageur {
+    /// True if the arbitrageur acts on the step with index `step`.
+    pub fn is_scheduled(&self, step: usize) -> bool {
+        self.arb_interval_steps <= 1 || step % self.arb_interval_steps == 0
+    }
+
+    /// True if the arbitrageur's tokens are topped up before the step with index `step`.
+    pub fn is_topup_step(&self, step: usize) -> bool {
+        self.topup_every > 0
+            && self.topup_amount_f > 0.0
+            && step > 0
+            && step % self.topup_every == 0
+    }
+}
+
+/// # DirectionFilter
+/// Direction of the reference price moves the arbitrageur corrects the pool on.
+///
+/// # Variants
+/// * `Both` - Acts on every move.
+/// * `UpOnly` - Only acts when the reference price increases.
+/// * `DownOnly` - Only acts when the reference price decreases.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DirectionFilter {
+    Both,
+    UpOnly,
+    DownOnly,
+}
+
+/// # ShrinkStrategy
+/// Strategy for shrinking the swap output when a swap fails.
+///
+/// # Variants
+/// * `Factor` - Multiplies the output by the shrink factor.
+/// * `Quoted` - Jumps to the pool's quoted `getAmountOut` for the input if it is smaller than the shrunk output.
+/// * `Bisect` - Halves the gap between the failed output and the quoted output, narrowing in on the largest output that succeeds.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShrinkStrategy {
+    Factor,
+    Quoted,
+    Bisect,
+}
+
+/// # PriceJumpPolicy
+/// What the arbitrageur does when the reference price changes by more than the max price jump in a step.
+///
+/// # Variants
+/// * `Clamp` - Arbs towards the price at the max jump instead.
+/// * `Skip` - Does not arb the step.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceJumpPolicy {
+    Clamp,
+    Skip,
+}
+
+/// # TradeFailurePolicy
+/// What the arbitrageur does when its exchange trade fails after its portfolio swap went through,
+/// which leaves it with an unhedged position.
+///
+/// # Variants
+/// * `Abort` - Errors, ending the sim.
+/// * `Rollback` - Restores a snapshot of the EVM state taken before the step, undoing the portfolio swap.
+/// * `Continue` - Keeps the unhedged position, records it, and carries on.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeFailurePolicy {
+    Abort,
+    Rollback,
+    Continue,
+}
+
+/// # PreferDirection
+/// Swap direction the arbitrageur takes when the direction is ambiguous.
+///
+/// # Variants
+/// * `Sign` - Follows the no-arb bounds, so an ambiguous arb is skipped.
+/// * `SwapXToY` - Swaps x to y.
+/// * `SwapYToX` - Swaps y to x.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PreferDirection {
+    Sign,
+    SwapXToY,
+    SwapYToX,
+}
+
+/// # Solver
+/// Defines the parameters of the rust root finders used to approximate swaps.
+///
+/// # Fields
+/// * `invariant_epsilon` - Distance from the current invariant the root finders target. (f64)
+/// * `invariant_sign` - Side of the current invariant the root finders target, "above" or "below". (InvariantSign)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Solver {
+    pub invariant_epsilon: f64,
+    pub invariant_sign: InvariantSign,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self {
+            invariant_epsilon: 1e-5,
+            invariant_sign: InvariantSign::Above,
+        }
+    }
+}
+
+impl Solver {
+    /// Invariant offset targeted by `NormalCurve` root finders.
+    #[allow(unused)]
+    pub fn invariant_offset(&self) -> InvariantOffset {
+        InvariantOffset {
+            epsilon: self.invariant_epsilon,
+            sign: self.invariant_sign,
+        }
+    }
+}
+
+/// # Gas
+/// Defines how the gas used by the arbitrageur is priced.
+///
+/// # Fields
+/// * `gas_price_gwei` - Price of a unit of gas, in gwei. (f64)
+/// * `eth_price_f` - Price of ETH, in units of the quote token. (f64)
+/// * `zero_evm_gas_price` - Prices the EVM's gas at zero, so an agent's native balance never blocks a call. (bool)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Gas {
+    pub gas_price_gwei: f64,
+    pub eth_price_f: f64,
+    pub zero_evm_gas_price: bool,
+}
+
+impl Default for Gas {
+    fn default() -> Self {
+        Self {
+            gas_price_gwei: 0.0,
+            eth_price_f: 2000.0,
+            zero_evm_gas_price: false,
+        }
+    }
+}
+
+impl Gas {
+    /// Cost of `gas_used`, in units of the quote token.
+    pub fn cost(&self, gas_used: u64) -> f64 {
+        gas_used as f64 * self.gas_price_gwei * 1e-9 * self.eth_price_f
+    }
+}
+
+/// # Sandwich
+/// Defines the swaps made before and after the arbitrageur's swap each step, a crude model of sandwich attacks.
+///
+/// # Fields
+/// * `size_f` - Input of the swap made before the arbitrageur's swap, in the same direction. The output is swapped back after. Zero disables the sandwich. (f64)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Sandwich {
+    pub size_f: f64,
+}
+
+impl Default for Sandwich {
+    fn default() -> Self {
+        Self { size_f: 0.0 }
+    }
+}
+
+/// # FeeSchedule
+/// Defines how the pool's fee changes over the run, like a dynamic fee AMM.
+/// A dynamic schedule creates the pool with the admin as its controller, which sets the fee before each step.
+///
+/// # Fields
+/// * `schedule_type` - How the fee is set each step. (FeeScheduleType)
+/// * `window_steps` - Number of trailing reference price returns the realized volatility is measured over. (usize)
+/// * `volatility_multiplier` - Basis points of fee added per basis point of realized volatility per step. (f64)
+/// * `max_fee_basis_points` - Largest fee the schedule sets, in basis points. Portfolio allows at most 1000. (u16)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct FeeSchedule {
+    pub schedule_type: FeeScheduleType,
+    pub window_steps: usize,
+    pub volatility_multiplier: f64,
+    pub max_fee_basis_points: u16,
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        Self {
+            schedule_type: FeeScheduleType::Constant,
+            window_steps: 10,
+            volatility_multiplier: 1.0,
+            max_fee_basis_points: 100,
+        }
+    }
+}
+
+/// # FeeScheduleType
+/// How the pool's fee is set each step.
+///
+/// # Variants
+/// * `Constant` - The pool keeps the fee it was created with.
+/// * `Volatility` - The pool fee plus the multiplier times the reference price's realized volatility over the window.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FeeScheduleType {
+    Constant,
+    Volatility,
+}
+
+impl FeeSchedule {
+    /// True if the schedule changes the pool's fee over the run.
+    pub fn is_dynamic(&self) -> bool {
+        self.schedule_type != FeeScheduleType::Constant
+    }
+
+    /// Fee of the step whose reference price is the last of `prices`, in basis points.
+    /// Never below `base_fee_basis_points`, the fee the pool was created with, or above the max fee.
+    pub fn fee_basis_points(&self, base_fee_basis_points: u16, prices: &[f64]) -> u16 {
+        match self.schedule_type {
+            FeeScheduleType::Constant => base_fee_basis_points,
+            FeeScheduleType::Volatility => {
+                let start = prices.len().saturating_sub(self.window_steps + 1);
+                let volatility_basis_points =
+                    realized_volatility(&prices[start..]) * common::BASIS_POINT_DIVISOR as f64;
+                let fee = base_fee_basis_points as f64
+                    + self.volatility_multiplier * volatility_basis_points;
+                fee.round().clamp(
+                    base_fee_basis_points as f64,
+                    self.max_fee_basis_points as f64,
+                ) as u16
+            }
+        }
+    }
+}
+
+/// Standard deviation of the log returns between consecutive prices. Zero for fewer than two returns.
+fn realized_volatility(prices: &[f64]) -> f64 {
+    let returns = prices
+        .windows(2)
+        .map(|pair| (pair[1] / pair[0]).ln())
+        .collect::<Vec<f64>>();
+    if returns.len() < 2 {
+        return 0.0;
+    }
+
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let variance =
+        returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+    variance.sqrt()
+}
+
+/// # Log
+/// Defines what is recorded into the raw data while the sim runs.
+///
+/// # Fields
+/// * `include_warmup` - Records the initial state of the pool, before the arbitrageur's first step. (bool)
+/// * `on_change_only` - Only records a step if the pool's reserves or reported price changed since the last recorded step. (bool)
+/// * `snapshot_every` - Writes a snapshot of the pool's trading curve every this many steps. Zero disables snapshots. (usize)
+/// * `snapshot_directory` - Directory the trading curve snapshots are written to. (String)
+/// * `emit_wad` - Adds the raw wad series to the csv as decimal strings, for exact reconciliation with the contracts. (bool)
+/// * `emit_tracking` - Also writes the reference price path, the reported price, and their difference to a separate csv. (bool)
+/// * `emit_parquet` - Also writes the spreadsheet as a parquet file, which is smaller and faster to reload than the csv. (bool)
+/// * `strict_reserves` - Errors on a step whose reserves are invalid, instead of warning. (bool)
+/// * `streaming` - Only keeps running aggregates of each metric instead of every row, for very long runs. No csv or plots are written. (bool)
+/// * `valuation_price` - Price the pool's reserves are valued at in its portfolio value. (ValuationPrice)
+/// * `write_incrementally` - Appends each row to the csv as it is logged, only keeping the last rows in memory for the plots. No summary is written. (bool)
+/// * `flush_every` - Number of rows appended to the csv between flushes to disk, when writing incrementally. (usize)
+/// * `float_precision` - Decimal places the float columns of the csv are rounded to. Full precision if not set, and not applied to the wad csv. (Option<usize>)
+/// * `output_format` - Format the spreadsheet of a full run is written in. Incremental runs always append to a csv. (OutputFormat)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Log {
+    pub include_warmup: bool,
+    pub on_change_only: bool,
+    pub snapshot_every: usize,
+    pub snapshot_directory: String,
+    pub emit_wad: bool,
+    pub emit_tracking: bool,
+    pub emit_parquet: bool,
+    pub strict_reserves: bool,
+    pub streaming: bool,
+    pub valuation_price: ValuationPrice,
+    pub write_incrementally: bool,
+    pub flush_every: usize,
+    pub float_precision: Option<usize>,
+    pub output_format: OutputFormat,
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Self {
+            include_warmup: true,
+            on_change_only: false,
+            snapshot_every: 0,
+            snapshot_directory: "out_data/snapshots".to_string(),
+            emit_wad: false,
+            emit_tracking: false,
+            emit_parquet: false,
+            strict_reserves: false,
+            streaming: false,
+            valuation_price: ValuationPrice::Reference,
+            write_incrementally: false,
+            flush_every: 100,
+            float_precision: None,
+            output_format: OutputFormat::Csv,
+        }
+    }
+}
+
+/// # ValuationPrice
+/// Price the pool's reserves are marked at when computing its portfolio value.
+///
+/// # Variants
+/// * `Reference` - The exchange's reference price, the external mark.
+/// * `Reported` - The pool's own reported spot price, the internal mark.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ValuationPrice {
+    Reference,
+    Reported,
+}
+
+/// # OutputFormat
+/// File format the sim's spreadsheet is written in, with the same column names in each.
+///
+/// # Variants
+/// * `Csv` - Comma separated values, with the wad columns if `emit_wad`.
+/// * `Json` - A JSON array of the rows.
+/// * `Jsonl` - Newline delimited JSON, one row per line.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    Json,
+    Jsonl,
+}
+
+impl OutputFormat {
+    /// File extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+/// # Artifacts
+/// Paths of compiled contract artifacts loaded at runtime in place of the bindings, e.g. to test a contract change without regenerating them.
+/// Each path is a forge artifact json, with the contract's `abi` and `bytecode`. `None` deploys the bindings' contract.
+///
+/// # Fields
+/// * `weth` - Artifact of the WETH contract. (Option<String>)
+/// * `portfolio` - Artifact of the portfolio contract. (Option<String>)
+/// * `entrypoint` - Artifact of the entrypoint contract. (Option<String>)
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Artifacts {
+    #[serde(default)]
+    pub weth: Option<String>,
+    #[serde(default)]
+    pub portfolio: Option<String>,
+    #[serde(default)]
+    pub entrypoint: Option<String>,
+}
+
+/// # Tokens
+/// Decimals of the pool's tokens, set on the mock ERC20s at setup, e.g. a 6 decimal quote token like USDC.
+/// Portfolio keeps reserves and swap amounts in wad, so only token balances and transfers are in these decimals.
+///
+/// # Fields
+/// * `decimals_x` - Decimals of token0, the asset. (u8)
+/// * `decimals_y` - Decimals of token1, the quote. (u8)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Tokens {
+    pub decimals_x: u8,
+    pub decimals_y: u8,
+}
+
+impl Default for Tokens {
+    fn default() -> Self {
+        Self {
+            decimals_x: 18,
+            decimals_y: 18,
+        }
+    }
 }
 
 /// # SimConfig
@@ -35,20 +573,131 @@ pub struct Economic {
 pub struct SimConfig {
     pub process: PriceProcess,
     pub economic: Economic,
+    #[serde(default)]
+    pub arbitrageur: Arbitrageur,
+    #[serde(default)]
+    pub solver: Solver,
+    #[serde(default)]
+    pub gas: Gas,
+    #[serde(default)]
+    pub sandwich: Sandwich,
+    #[serde(default)]
+    pub fee_schedule: FeeSchedule,
+    #[serde(default)]
+    pub log: Log,
+    #[serde(default)]
+    pub tokens: Tokens,
+    #[serde(default)]
+    pub artifacts: Artifacts,
 }
 
 impl SimConfig {
+    /// Initial price of the pool and exchange, in the contracts' quote per base units.
+    pub fn initial_pool_price(&self) -> f64 {
+        self.economic
+            .price_convention
+            .to_quote_per_base(self.process.initial_price)
+    }
+
     /// Loads the `arbiter.toml` configuration file and attempts to deserialize it into a `SimConfig`.
     pub fn new() -> Result<Self, ConfigError> {
-        let settings = Config::builder()
+        let builder = Config::builder()
             .add_source(config::File::with_name("arbiter"))
-            .add_source(config::Environment::with_prefix("ARBITER"))
-            .build()?;
+            .add_source(config::Environment::with_prefix("ARBITER"));
+
+        Self::from_builder(builder)
+    }
+
+    /// Deserializes and validates the config built from the sources of `builder`.
+    /// The process type is case insensitive, e.g. `"gbm"` selects the `GBM` process.
+    fn from_builder(builder: ConfigBuilder<DefaultState>) -> Result<Self, ConfigError> {
+        let settings = builder.clone().build()?;
+        let settings = match settings.get_string(PROCESS_TYPE_KEY) {
+            Ok(process_type) => builder
+                .set_override(PROCESS_TYPE_KEY, process_type.to_uppercase())?
+                .build()?,
+            Err(_) => settings,
+        };
+
+        let config: Self = settings.try_deserialize()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the parameters that deserialize but can't produce a meaningful sim.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let PriceProcessType::GBM(gbm) = &self.process.process_type {
+            if gbm.volatility <= 0.0 {
+                return Err(ConfigError::Message(format!(
+                    "GBM volatility must be positive, got {}: the price path would be flat and the arbitrageur would never trade",
+                    gbm.volatility
+                )));
+            }
+        }
+
+        let duration = self.process.timestep * self.process.num_steps as f64;
+        if !self.economic.pool_is_perpetual && duration >= self.economic.pool_time_remaining_years_f
+        {
+            return Err(ConfigError::Message(format!(
+                "non-perpetual pool with {} years remaining expires before the sim's {} years end",
+                self.economic.pool_time_remaining_years_f, duration
+            )));
+        }
+
+        if self.fee_schedule.is_dynamic()
+            && self.fee_schedule.max_fee_basis_points < self.economic.pool_fee_basis_points
+        {
+            return Err(ConfigError::Message(format!(
+                "fee schedule max fee of {} bps is below the pool fee of {} bps",
+                self.fee_schedule.max_fee_basis_points, self.economic.pool_fee_basis_points
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Generates the config's price path twice and returns it only if both paths are identical,
+/// to check that the seed reproduces its path.
+///
+/// # Notes
+/// - The OU and GBM processes draw from an rng seeded with `process.seed`, so they always pass.
+///   A process that isn't seedable, e.g. one drawing from the thread's rng, fails with the first step the paths differ at.
+pub fn generate_deterministic_path(config: &SimConfig) -> Result<Vec<f64>, ConfigError> {
+    let first = config.process.generate_price_path().1;
+    let second = config.process.generate_price_path().1;
+
+    match_paths(config.process.seed, first, second)
+}
+
+/// Returns the path if the two are identical. Compares bits, so a NaN price matches itself.
+fn match_paths(seed: u64, first: Vec<f64>, second: Vec<f64>) -> Result<Vec<f64>, ConfigError> {
+    if first.len() != second.len() {
+        return Err(ConfigError::Message(format!(
+            "price process is nondeterministic for seed {}: paths have {} and {} steps",
+            seed,
+            first.len(),
+            second.len()
+        )));
+    }
 
-        settings.try_deserialize()
+    if let Some(step) = first
+        .iter()
+        .zip(second.iter())
+        .position(|(a, b)| a.to_bits() != b.to_bits())
+    {
+        return Err(ConfigError::Message(format!(
+            "price process is nondeterministic for seed {}: paths differ at step {}, {} vs. {}",
+            seed, step, first[step], second[step]
+        )));
     }
+
+    Ok(first)
 }
 
+/// Key of the price process type in the config sources.
+static PROCESS_TYPE_KEY: &str = "process.process_type.price_process_type";
+
 pub fn main() -> SimConfig {
     let settings = SimConfig::new().unwrap();
     println!(
@@ -74,6 +723,45 @@ impl Default for SimConfig {
     /// pool strike price: 1.0
     /// pool time remaining years: 1.0
     /// pool is perpetual: true
+    /// pool pair id: 1, the token0/token1 pair
+    /// arbitrageur fee tolerance: the pool fee, 10 bps
+    /// controller fee share: none
+    /// price convention: quote per base
+    /// arbitrageur initial balance: 50 of each token
+    /// exchange initial balance: 88888888888888 of each token
+    /// arbitrageur swap retry shrink: factor of 0.999
+    /// arbitrageur search swap output: true
+    /// arbitrageur venues: the primary exchange
+    /// arbitrageur min swap output: 0.0
+    /// arbitrageur min pool liquidity: 0.0, no minimum
+    /// arbitrageur direction filter: both
+    /// arbitrageur max price jump: disabled, skips the step if enabled
+    /// arbitrageur no-arb tolerance: 1000 wei
+    /// arbitrageur prefer direction: sign
+    /// arbitrageur interval: every step
+    /// arbitrageur top up: disabled
+    /// arbitrageur trade failure policy: abort
+    /// arbitrageur hedge lag: none, hedges in the same step
+    /// solver invariant offset: 1e-5 above the current invariant
+    /// gas price: 0 gwei, so gas is free
+    /// gas eth price: 2000
+    /// zero evm gas price: false
+    /// sandwich: none
+    /// fee schedule: constant, the pool fee
+    /// log include warmup: true
+    /// log on change only: false
+    /// log curve snapshots: disabled, written to out_data/snapshots if enabled
+    /// log emit wad: false
+    /// log emit tracking: false
+    /// log emit parquet: false
+    /// log strict reserves: false, invalid reserves only warn
+    /// log streaming: false
+    /// log valuation price: reference
+    /// log write incrementally: false, every row is kept in memory, flushed every 100 rows if enabled
+    /// log float precision: full precision
+    /// log output format: csv
+    /// tokens: 18 decimals each
+    /// artifacts: none, every contract is deployed from the bindings
     fn default() -> Self {
         SimConfig {
             process: PriceProcess {
@@ -92,7 +780,263 @@ impl Default for SimConfig {
                 pool_is_perpetual: true,
                 pool_fee_basis_points: common::FEE_BPS,
                 pool_priority_fee_basis_points: 0,
+                pool_pair_id: common::ENTRYPOINT_PAIR_ID,
+                arb_fee_tolerance_bps: None,
+                controller_fee_share: 0.0,
+                price_convention: PriceConvention::QuotePerBase,
+                arbitrageur_initial_balance_f: common::ARBITRAGEUR_INITIAL_BALANCE_F,
+                exchange_initial_balance_f: common::EXCHANGE_INITIAL_BALANCE_F,
             },
+
+            arbitrageur: Arbitrageur::default(),
+
+            solver: Solver::default(),
+
+            gas: Gas::default(),
+
+            sandwich: Sandwich::default(),
+
+            fee_schedule: FeeSchedule::default(),
+
+            log: Log::default(),
+
+            tokens: Tokens::default(),
+            artifacts: Artifacts::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arbiter::stochastic::price_process::GBM;
+
+    /// Removes the `sections` and the lines setting `keys` from `arbiter.toml`.
+    fn builder_omitting(sections: &[&str], keys: &[&str]) -> ConfigBuilder<DefaultState> {
+        let contents = std::fs::read_to_string("arbiter.toml").unwrap();
+        let mut omitting = false;
+        let contents = contents
+            .lines()
+            .filter(|line| {
+                if line.starts_with('[') {
+                    omitting = sections
+                        .iter()
+                        .any(|section| *line == format!("[{}]", section));
+                }
+                !omitting
+                    && !keys
+                        .iter()
+                        .any(|key| line.starts_with(&format!("{} =", key)))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Config::builder().add_source(config::File::from_str(&contents, config::FileFormat::Toml))
+    }
+
+    /// Replaces the process type section of `arbiter.toml` with `process_type`.
+    fn builder_with_process_type(process_type: &str) -> ConfigBuilder<DefaultState> {
+        let contents = std::fs::read_to_string("arbiter.toml").unwrap();
+        let start = contents.find("[process.process_type]").unwrap();
+        let contents = format!("{}{}", &contents[..start], process_type);
+
+        Config::builder().add_source(config::File::from_str(&contents, config::FileFormat::Toml))
+    }
+
+    #[test]
+    fn gbm_process_is_selected_from_config() {
+        let config = SimConfig::from_builder(builder_with_process_type(
+            r#"
+[process.process_type]
+price_process_type = "gbm"
+
+[process.process_type.price_process]
+drift = 0.1
+volatility = 0.07
+"#,
+        ))
+        .unwrap();
+
+        match config.process.process_type {
+            PriceProcessType::GBM(gbm) => {
+                assert_eq!(gbm.drift, 0.1);
+                assert_eq!(gbm.volatility, 0.07);
+            }
+            _ => panic!("expected a GBM process"),
+        }
+    }
+
+    #[test]
+    fn omitted_sections_take_the_defaults() {
+        let config = SimConfig::from_builder(builder_omitting(
+            &[
+                "arbitrageur",
+                "solver",
+                "log",
+                "gas",
+                "sandwich",
+                "fee_schedule",
+            ],
+            &[],
+        ))
+        .unwrap();
+        let default = SimConfig::default();
+
+        assert_eq!(
+            format!("{:?}", config.arbitrageur),
+            format!("{:?}", default.arbitrageur)
+        );
+        assert_eq!(
+            format!("{:?}", config.solver),
+            format!("{:?}", default.solver)
+        );
+        assert_eq!(format!("{:?}", config.log), format!("{:?}", default.log));
+        assert_eq!(format!("{:?}", config.gas), format!("{:?}", default.gas));
+        assert_eq!(
+            format!("{:?}", config.sandwich),
+            format!("{:?}", default.sandwich)
+        );
+        assert_eq!(
+            format!("{:?}", config.fee_schedule),
+            format!("{:?}", default.fee_schedule)
+        );
+    }
+
+    #[test]
+    fn omitted_economic_fields_take_the_defaults() {
+        let config = SimConfig::from_builder(builder_omitting(
+            &[],
+            &[
+                "controller_fee_share",
+                "pool_pair_id",
+                "arbitrageur_initial_balance_f",
+                "exchange_initial_balance_f",
+            ],
+        ))
+        .unwrap();
+        let default = SimConfig::default();
+
+        assert_eq!(
+            config.economic.controller_fee_share,
+            default.economic.controller_fee_share
+        );
+        assert_eq!(config.economic.pool_pair_id, default.economic.pool_pair_id);
+        assert_eq!(
+            config.economic.arbitrageur_initial_balance_f,
+            default.economic.arbitrageur_initial_balance_f
+        );
+        assert_eq!(
+            config.economic.exchange_initial_balance_f,
+            default.economic.exchange_initial_balance_f
+        );
+    }
+
+    #[test]
+    fn seeded_ou_path_is_deterministic() {
+        let config = SimConfig::default();
+        let path = generate_deterministic_path(&config).unwrap();
+
+        assert!(!path.is_empty());
+        assert_eq!(path, config.process.generate_price_path().1);
+    }
+
+    #[test]
+    fn differing_paths_are_rejected() {
+        let err = match_paths(1, vec![1.0, 1.1, 1.2], vec![1.0, 1.1, 1.3])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("differ at step 2"),
+            "unexpected error: {}",
+            err
+        );
+
+        assert!(match_paths(1, vec![1.0], vec![1.0, 1.1]).is_err());
+        assert!(match_paths(1, vec![f64::NAN], vec![f64::NAN]).is_ok());
+    }
+
+    #[test]
+    fn tolerance_tighter_than_fee_warns() {
+        let mut economic = SimConfig::default().economic;
+        economic.pool_fee_basis_points = 30;
+
+        // Derived from the pool fee by default.
+        assert_eq!(economic.arb_threshold_bps(), 30);
+        assert_eq!(economic.arb_threshold_warning(), None);
+
+        economic.arb_fee_tolerance_bps = Some(10);
+        assert_eq!(economic.arb_threshold_bps(), 10);
+        assert!(economic.arb_threshold_warning().is_some());
+
+        economic.arb_fee_tolerance_bps = Some(50);
+        assert_eq!(economic.arb_threshold_warning(), None);
+    }
+
+    #[test]
+    fn volatility_fee_rises_in_volatile_segment() {
+        let schedule = FeeSchedule {
+            schedule_type: FeeScheduleType::Volatility,
+            window_steps: 5,
+            volatility_multiplier: 1.0,
+            max_fee_basis_points: 1000,
+        };
+
+        // Flat, then swinging 5% up and down.
+        let mut prices = vec![1.0; 10];
+        for i in 0..6 {
+            prices.push(if i % 2 == 0 { 1.05 } else { 1.0 });
         }
+
+        let calm = schedule.fee_basis_points(10, &prices[..10]);
+        let volatile = schedule.fee_basis_points(10, &prices);
+        assert_eq!(calm, 10);
+        assert!(volatile > calm, "volatile fee: {}", volatile);
+
+        // Capped by the max fee, and constant schedules keep the pool fee.
+        let capped = FeeSchedule {
+            max_fee_basis_points: 20,
+            ..schedule.clone()
+        };
+        assert_eq!(capped.fee_basis_points(10, &prices), 20);
+        let constant = FeeSchedule {
+            schedule_type: FeeScheduleType::Constant,
+            ..schedule
+        };
+        assert_eq!(constant.fee_basis_points(10, &prices), 10);
+    }
+
+    #[test]
+    fn zero_gbm_volatility_fails_validation() {
+        let result = SimConfig::from_builder(builder_with_process_type(
+            r#"
+[process.process_type]
+price_process_type = "gbm"
+
+[process.process_type.price_process]
+drift = 0.1
+volatility = 0.0
+"#,
+        ));
+        assert!(result.is_err());
+
+        let mut config = SimConfig::default();
+        config.process.process_type = PriceProcessType::GBM(GBM::new(0.1, 0.0));
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("GBM volatility"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn non_perpetual_pool_expiring_during_sim_is_rejected() {
+        let mut config = SimConfig::default();
+        config.economic.pool_is_perpetual = false;
+        config.economic.pool_time_remaining_years_f = 1.0;
+        assert!(config.validate().is_ok());
+
+        config.process.num_steps = 1000;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("expires"), "unexpected error: {}", err);
+
+        config.economic.pool_is_perpetual = true;
+        assert!(config.validate().is_ok());
     }
 }
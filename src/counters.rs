@@ -0,0 +1,121 @@
+/// Tallies of the computations made while the sim runs, to see where the time goes.
+/// Counters are thread local, so concurrent sims on other threads don't interfere.
+use colored::*;
+use std::cell::{Cell, RefCell};
+
+/// # Counts
+/// Number of each computation made.
+///
+/// # Fields
+/// * `evm_calls` - Calls made to the EVM through a `Caller`. (u64)
+/// * `decodes` - Decoded call results. (u64)
+/// * `solver_calls` - Root finder invocations. (u64)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Counts {
+    pub evm_calls: u64,
+    pub decodes: u64,
+    pub solver_calls: u64,
+}
+
+thread_local! {
+    static EVM_CALLS: Cell<u64> = Cell::new(0);
+    static DECODES: Cell<u64> = Cell::new(0);
+    static SOLVER_CALLS: Cell<u64> = Cell::new(0);
+    /// Totals at the end of the last recorded step.
+    static STEP_START: Cell<Counts> = Cell::new(Counts::default());
+    /// Counts of each recorded step.
+    static STEPS: RefCell<Vec<Counts>> = RefCell::new(Vec::new());
+}
+
+fn increment(counter: &'static std::thread::LocalKey<Cell<u64>>) {
+    counter.with(|c| c.set(c.get() + 1));
+}
+
+pub fn record_evm_call() {
+    increment(&EVM_CALLS);
+}
+
+pub fn record_decode() {
+    increment(&DECODES);
+}
+
+pub fn record_solver_call() {
+    increment(&SOLVER_CALLS);
+}
+
+/// Counts made since the counters were last reset.
+pub fn totals() -> Counts {
+    Counts {
+        evm_calls: EVM_CALLS.with(|c| c.get()),
+        decodes: DECODES.with(|c| c.get()),
+        solver_calls: SOLVER_CALLS.with(|c| c.get()),
+    }
+}
+
+/// Records the counts made since the last recorded step as a step.
+pub fn end_step() {
+    let totals = totals();
+    let start = STEP_START.with(|s| s.replace(totals));
+    STEPS.with(|steps| steps.borrow_mut().push(totals.since(&start)));
+}
+
+/// Counts of each recorded step.
+pub fn steps() -> Vec<Counts> {
+    STEPS.with(|steps| steps.borrow().clone())
+}
+
+/// Zeroes the counters and clears the recorded steps.
+pub fn reset() {
+    EVM_CALLS.with(|c| c.set(0));
+    DECODES.with(|c| c.set(0));
+    SOLVER_CALLS.with(|c| c.set(0));
+    STEP_START.with(|s| s.set(Counts::default()));
+    STEPS.with(|steps| steps.borrow_mut().clear());
+}
+
+impl Counts {
+    /// Counts made between `earlier` and these counts.
+    pub fn since(&self, earlier: &Counts) -> Counts {
+        Counts {
+            evm_calls: self.evm_calls - earlier.evm_calls,
+            decodes: self.decodes - earlier.decodes,
+            solver_calls: self.solver_calls - earlier.solver_calls,
+        }
+    }
+}
+
+/// Prints the totals and the mean and max per step counts to the console.
+pub fn print() {
+    let totals = totals();
+    let steps = steps();
+    let per_step = |count: fn(&Counts) -> u64| {
+        let mean = steps.iter().map(count).sum::<u64>() as f64 / steps.len().max(1) as f64;
+        let max = steps.iter().map(count).max().unwrap_or(0);
+        (mean, max)
+    };
+
+    println!(
+        "{}\n{}\n{:<14}{:>12}{:>14}{:>12}",
+        "Computation counts:".bright_yellow(),
+        "------------------".bright_yellow(),
+        "",
+        "total",
+        "mean / step",
+        "max / step"
+    );
+    for (name, total, count) in [
+        (
+            "evm calls",
+            totals.evm_calls,
+            (|c: &Counts| c.evm_calls) as fn(&Counts) -> u64,
+        ),
+        ("decodes", totals.decodes, |c: &Counts| c.decodes),
+        ("solver calls", totals.solver_calls, |c: &Counts| {
+            c.solver_calls
+        }),
+    ] {
+        let (mean, max) = per_step(count);
+        println!("{:<14}{:>12}{:>14.2}{:>12}", name, total, mean, max);
+    }
+    println!("{}", "------------------".bright_yellow());
+}
@@ -0,0 +1,119 @@
+/// Estimates the runtime and output size of a simulation from a short calibration run.
+use colored::*;
+use std::{error::Error, fs, time::Instant};
+
+use super::config::SimConfig;
+use super::sim;
+use super::spreadsheetorizer::DiskWritable;
+
+/// Number of steps run to calibrate the estimate.
+pub static CALIBRATION_STEPS: usize = 100;
+
+/// # Calibration
+/// Per step costs measured over a calibration run.
+///
+/// # Fields
+/// * `steps` - Number of steps in the calibration run. (usize)
+/// * `seconds_per_step` - Wall clock time of the simulation loop per step, excluding the deployment. (f64)
+/// * `bytes_per_step` - Size of the csv output per step. (f64)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calibration {
+    pub steps: usize,
+    pub seconds_per_step: f64,
+    pub bytes_per_step: f64,
+}
+
+/// # Estimate
+/// Projected totals of a simulation run.
+///
+/// # Fields
+/// * `steps` - Number of steps the estimate is for. (usize)
+/// * `seconds` - Projected wall clock time of the simulation loop. (f64)
+/// * `bytes` - Projected size of the csv output. (f64)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Estimate {
+    pub steps: usize,
+    pub seconds: f64,
+    pub bytes: f64,
+}
+
+/// Calibrates with the config's sim and prints the projected totals of a run of `steps`.
+pub async fn main(steps: usize) -> Result<(), Box<dyn Error>> {
+    let sim_config = SimConfig::new()?;
+    let calibration = calibrate(&sim_config, CALIBRATION_STEPS).await?;
+    let estimate = calibration.extrapolate(steps);
+
+    println!(
+        "{}\n{:#?}\n{}\n{:#?}",
+        "Calibration:".bright_yellow(),
+        calibration,
+        "Estimate:".bright_yellow(),
+        estimate
+    );
+    println!(
+        "{} {} {} {} {}",
+        "Projected".bright_cyan(),
+        format!("{:.2}", estimate.seconds).purple().bold(),
+        "seconds and".bright_cyan(),
+        format!("{:.2}", estimate.bytes / 1e6).purple().bold(),
+        "MB of output.".bright_cyan(),
+    );
+
+    Ok(())
+}
+
+/// Runs the config's sim for `steps` steps, timing the simulation loop and measuring its csv output.
+/// The output is written to the system's temp directory, so nothing is left in `out_data`.
+pub async fn calibrate(config: &SimConfig, steps: usize) -> Result<Calibration, Box<dyn Error>> {
+    let mut config = config.clone();
+    config.process.num_steps = steps;
+
+    let mut manager = sim::deploy(&config)?;
+    let start_time = Instant::now();
+    let (raw_data, pool_id) = sim::run_with_manager(&mut manager, &config).await?;
+    let seconds = start_time.elapsed().as_secs_f64();
+    manager.shutdown();
+
+    let path = std::env::temp_dir().join(format!("proto_sim_estimate_{}.csv", steps));
+    let path = path.to_str().unwrap();
+    raw_data.write_to_disk(path, pool_id, config.log.float_precision)?;
+    let bytes = fs::metadata(path)?.len() as f64;
+    fs::remove_file(path)?;
+
+    Ok(Calibration {
+        steps,
+        seconds_per_step: seconds / steps as f64,
+        bytes_per_step: bytes / steps as f64,
+    })
+}
+
+impl Calibration {
+    /// Projects the per step costs linearly to a run of `steps`.
+    pub fn extrapolate(&self, steps: usize) -> Estimate {
+        Estimate {
+            steps,
+            seconds: self.seconds_per_step * steps as f64,
+            bytes: self.bytes_per_step * steps as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn estimate_scales_linearly_with_steps() {
+        let calibration = calibrate(&SimConfig::default(), 20).await.unwrap();
+
+        let estimate = calibration.extrapolate(1_000);
+        let doubled = calibration.extrapolate(2_000);
+        assert_eq!(doubled.seconds, estimate.seconds * 2.0);
+        assert_eq!(doubled.bytes, estimate.bytes * 2.0);
+
+        // A calibration twice as long measures roughly twice the output.
+        let longer = calibrate(&SimConfig::default(), 40).await.unwrap();
+        let ratio = longer.extrapolate(40).bytes / calibration.extrapolate(20).bytes;
+        assert!(ratio > 1.5 && ratio < 2.5, "ratio: {}", ratio);
+    }
+}
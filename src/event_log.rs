@@ -0,0 +1,95 @@
+/// Streams semantic sim events, one JSON object per line (NDJSON), as the sim runs.
+/// Unlike a call trace, events describe what happened in the sim rather than the raw EVM calls.
+/// The log is thread local and disabled until it is opened, emitting is a no-op while closed.
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// # Event
+/// A sim event, serialized with its kind in the `event` field.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The reference price of a venue was set.
+    PriceStep { venue: String, price: f64 },
+    /// The arbitrageur swapped on the pool.
+    Swap {
+        pool_id: u64,
+        sell_asset: bool,
+        input: u128,
+        output: u128,
+        retries: usize,
+    },
+    /// Liquidity was allocated to the pool.
+    Allocation { pool_id: u64, delta_liquidity: u128 },
+}
+
+thread_local! {
+    static SINK: RefCell<Option<BufWriter<File>>> = RefCell::new(None);
+}
+
+/// Opens the event log at `path`, truncating it, so the following events are written to it.
+pub fn open(path: &str) -> std::io::Result<()> {
+    let file = File::create(path)?;
+    SINK.with(|sink| *sink.borrow_mut() = Some(BufWriter::new(file)));
+    Ok(())
+}
+
+/// Writes the event as a line of the event log, if it is open.
+pub fn emit(event: Event) -> std::io::Result<()> {
+    SINK.with(|sink| match sink.borrow_mut().as_mut() {
+        Some(writer) => {
+            serde_json::to_writer(&mut *writer, &event)?;
+            writer.write_all(b"\n")
+        }
+        None => Ok(()),
+    })
+}
+
+/// Flushes and closes the event log. Does nothing if it is not open.
+pub fn close() -> std::io::Result<()> {
+    match SINK.with(|sink| sink.borrow_mut().take()) {
+        Some(mut writer) => writer.flush(),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SimConfig;
+    use crate::sim;
+
+    /// Counts the events of kind `event` in the lines of an event log.
+    fn count(lines: &[serde_json::Value], event: &str) -> usize {
+        lines.iter().filter(|line| line["event"] == event).count()
+    }
+
+    #[tokio::test]
+    async fn short_run_logs_price_steps_and_swaps() {
+        let path = std::env::temp_dir().join("proto_sim_event_log.ndjson");
+        let path = path.to_str().unwrap();
+
+        let config = SimConfig::default();
+        open(path).unwrap();
+        let (raw_data, pool_id) = sim::run(&config).await.unwrap();
+        close().unwrap();
+
+        let lines = std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect::<Vec<serde_json::Value>>();
+
+        // The warmup row and each step's row follow a price step.
+        let rows = raw_data.get_reported_price(pool_id).unwrap().len();
+        assert_eq!(count(&lines, "price_step"), rows);
+        assert_eq!(count(&lines, "allocation"), 1);
+
+        // Each swap changes the pool's reserves between two recorded steps.
+        let reserves = raw_data.get_pool_x_total_float(pool_id).unwrap();
+        let changes = reserves.windows(2).filter(|w| w[0] != w[1]).count();
+        assert_eq!(count(&lines, "swap"), changes);
+    }
+}
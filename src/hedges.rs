@@ -0,0 +1,87 @@
+/// Exchange trades the arbitrageur deferred, hedging its portfolio swaps some steps later
+/// to model settlement lag. Until a trade settles, its input is the arbitrageur's pending exposure.
+/// Each run keeps its own queue and passes it to every step, so runs never share pending trades.
+use arbiter::utils::wad_to_float;
+use ethers::types::U256;
+
+/// # ExchangeTrade
+/// A trade of the arbitrageur on an exchange venue.
+///
+/// # Fields
+/// * `venue` - Name of the exchange the trade is made on. (String)
+/// * `sell_asset` - Direction of the exchange trade, true sells the asset for the quote. (bool)
+/// * `amount_in` - Amount of the sold token, in wad. (u128)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExchangeTrade {
+    pub venue: String,
+    pub sell_asset: bool,
+    pub amount_in: u128,
+}
+
+impl ExchangeTrade {
+    /// Value of the trade's input at `price`, in units of the quote token.
+    pub fn value(&self, price: f64) -> f64 {
+        let amount = wad_to_float(U256::from(self.amount_in));
+        if self.sell_asset {
+            amount * price
+        } else {
+            amount
+        }
+    }
+}
+
+/// # PendingTrade
+/// An exchange trade waiting to settle.
+///
+/// # Fields
+/// * `due_step` - Step index the trade settles on. (usize)
+/// * `trade` - Trade made when it settles. (ExchangeTrade)
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingTrade {
+    pub due_step: usize,
+    pub trade: ExchangeTrade,
+}
+
+/// # HedgeQueue
+/// Exchange trades waiting to settle, in the order they were deferred.
+///
+/// # Fields
+/// * `pending` - Trades waiting to settle. (Vec<PendingTrade>)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HedgeQueue {
+    pending: Vec<PendingTrade>,
+}
+
+impl HedgeQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues `trade` to settle on the step with index `due_step`.
+    pub fn defer(&mut self, due_step: usize, trade: ExchangeTrade) {
+        self.pending.push(PendingTrade { due_step, trade });
+    }
+
+    /// Removes and returns the trades due on or before `step`, in the order they were deferred.
+    pub fn take_due(&mut self, step: usize) -> Vec<ExchangeTrade> {
+        let (due, waiting): (Vec<PendingTrade>, Vec<PendingTrade>) = self
+            .pending
+            .drain(..)
+            .partition(|pending| pending.due_step <= step);
+        self.pending = waiting;
+        due.into_iter().map(|pending| pending.trade).collect()
+    }
+
+    /// Trades waiting to settle.
+    pub fn pending(&self) -> &[PendingTrade] {
+        &self.pending
+    }
+
+    /// Value of every pending trade's input at `price`, in units of the quote token.
+    pub fn exposure(&self, price: f64) -> f64 {
+        self.pending
+            .iter()
+            .map(|pending| pending.trade.value(price))
+            .sum()
+    }
+}
@@ -1,19 +1,34 @@
 use clap::Parser;
+use colored::*;
 use ethers::types::I256;
+use polars::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
 
-use arbiter::{manager::SimulationManager, utils::*};
-use ethers::abi::Tokenize;
+use arbiter::{
+    agent::Agent,
+    environment::contract::{IsDeployed, SimulationContract},
+    manager::SimulationManager,
+    utils::*,
+};
+use ethers::abi::{Tokenizable, Tokenize};
 use ethers::core::utils;
 use ethers::prelude::U256;
 
 use super::{
     calls::{Caller, DecodedReturns},
+    common::BASIS_POINT_DIVISOR,
+    config::{SimConfig, ValuationPrice},
+    math::{self, NormalCurve},
     raw_data::*,
+    task,
 };
 
 // dynamic, must be built wth ./build.sh or forge bind.
-use bindings::i_portfolio::PoolsReturn;
+use bindings::{
+    external_normal_strategy_lib::NormalCurve as SolidityNormalCurve, i_portfolio::PoolsReturn,
+    shared_types::Order,
+};
 
 /// Defines the output file directory and name for the plots and csv data.
 #[derive(Clone, Parser, Serialize, Deserialize, Debug)]
@@ -27,86 +42,764 @@ pub struct OutputStorage {
 /// it to the raw_data container.
 ///
 /// # Data collected
+/// - Step index
 /// - Arbitrageur balances for each token
 /// - Portfolio pool data
 /// - Portfolio reported price
 /// - Exchange price
+/// - Arbitrageur realized profit since the last recorded step
+/// - Pool portfolio value in wad and as a float, derived from the reserves and the exchange price in 3a
+/// - Theoretical portfolio value of an ideally arbitraged pool at the exchange price
 ///
 /// # Notes
 /// - Must log an entry for each series point so all vectors are equal in length!
+/// - In `on_change_only` mode, a row is only recorded if the pool's reserves or reported price
+///   changed since the last recorded row. Returns true if a row was recorded.
 pub fn run(
     manager: &SimulationManager,
     raw_data_container: &mut RawData,
     pool_id: u64,
-) -> Result<(), Box<dyn std::error::Error>> {
+    config: &SimConfig,
+) -> Result<bool, Box<dyn std::error::Error>> {
     let admin = manager.agents.get("admin").unwrap();
     let arbitrageur = manager.agents.get("arbitrageur").unwrap();
     let token0 = manager.deployed_contracts.get("token0").unwrap();
     let token1 = manager.deployed_contracts.get("token1").unwrap();
+    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+    let library = manager.deployed_contracts.get("library").unwrap();
 
     // Gracefully handles REVM calls for us.
-    let mut graceful = Caller::new(admin);
+    let graceful = Caller::new(admin);
     let mut graceful_arber = Caller::new(arbitrageur);
 
+    // 0. Fetch the pool state, which is compared to the last recorded row in on change only mode.
+    let step = raw_data_container.advance_step(pool_id);
+    let pool_data: PoolsReturn = graceful.view(portfolio, "pools", pool_id.into_tokens())?;
+    let portfolio_prices: U256 = graceful.view(portfolio, "getSpotPrice", pool_id.into_tokens())?;
+
+    if config.log.on_change_only
+        && raw_data_container.is_pool_unchanged(pool_id, &pool_data, portfolio_prices)
+    {
+        return Ok(false);
+    }
+
+    raw_data_container.add_step_index(pool_id, step);
+
     // 1. Edit the arb balances
     let token_key_0 = "token0".to_string();
     let token_key_1 = "token1".to_string();
     let arbitrageur_balance_0 = graceful_arber.balance_of(token0).decoded(&token0)?;
     let arbitrageur_balance_1 = graceful_arber.balance_of(token1).decoded(&token1)?;
+    raw_data_container.add_token_decimals(token_key_0.clone(), config.tokens.decimals_x);
+    raw_data_container.add_token_decimals(token_key_1.clone(), config.tokens.decimals_y);
     raw_data_container.add_arbitrageur_balance(token_key_0, arbitrageur_balance_0);
     raw_data_container.add_arbitrageur_balance(token_key_1, arbitrageur_balance_1);
 
     // 2. Edit the exchange price
     let exchange = manager.deployed_contracts.get("exchange").unwrap();
-    let exchange_price = graceful
-        .call(
-            exchange,
-            "getPrice",
-            recast_address(token0.address).into_tokens(),
-        )?
-        .decoded(exchange)?;
+    let exchange_price = graceful.view(
+        exchange,
+        "getPrice",
+        recast_address(token0.address).into_tokens(),
+    )?;
     raw_data_container.add_exchange_price(pool_id, exchange_price);
 
     let price_token0 = utils::format_units(exchange_price, "ether")?.parse::<f64>()?;
-    let price_token1 = 1.0 / price_token0;
 
-    let arb_balance_token0_float =
-        utils::format_units(arbitrageur_balance_0, "ether")?.parse::<f64>()?;
-    let arb_balance_token1_float =
-        utils::format_units(arbitrageur_balance_1, "ether")?.parse::<f64>()?;
+    // Balances are in each token's decimals, unlike the exchange price and pool data which are in wad.
+    let arb_balance_token0_float = units_to_float(arbitrageur_balance_0, config.tokens.decimals_x);
+    let arb_balance_token1_float = units_to_float(arbitrageur_balance_1, config.tokens.decimals_y);
 
-    let portfolio_value =
-        arb_balance_token0_float * price_token0 + arb_balance_token1_float * price_token1;
+    // Valued in the quote token, like the pool's portfolio value, see `pool_portfolio_value_wad`.
+    let portfolio_value = arb_balance_token0_float * price_token0 + arb_balance_token1_float;
 
+    // Realized profit of the arbitrageur since the last recorded step.
+    let previous_portfolio_value = raw_data_container
+        .derived_data
+        .get(&pool_id)
+        .and_then(|derived_data| derived_data.arbitrageur_portfolio_value.last().copied());
+    let arb_profit = previous_portfolio_value.map_or(0.0, |previous| portfolio_value - previous);
+    raw_data_container.add_arb_profit(pool_id, arb_profit);
     raw_data_container.add_arbitrageur_portfolio_value(pool_id, portfolio_value);
 
-    // 3a. Edit portfolio pool data
-    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
-    let pool_data: PoolsReturn = graceful
-        .call(portfolio, "pools", pool_id.into_tokens())?
-        .decoded(portfolio)?;
-
+    // 3a. Edit portfolio pool data, which portfolio keeps in wad whatever the tokens' decimals.
     let pool_reserve_x = utils::format_units(pool_data.virtual_x, "ether")?.parse::<f64>()?;
     let pool_reserve_y = utils::format_units(pool_data.virtual_y, "ether")?.parse::<f64>()?;
 
-    let pool_value = pool_reserve_x * price_token0 + pool_reserve_y * price_token1;
+    // Marks x at the reference price, or at the pool's own price to compare the two marks.
+    let mark_wad = match config.log.valuation_price {
+        ValuationPrice::Reference => exchange_price,
+        ValuationPrice::Reported => portfolio_prices,
+    };
+    let mark_x = wad_to_float(mark_wad);
 
+    // The float value is converted from the wad value, so the two can't drift apart.
+    let pool_value_wad = pool_portfolio_value_wad(&pool_data, mark_wad);
+    let pool_value = wad_to_float(pool_value_wad);
+    raw_data_container.add_portfolio_value(pool_id, pool_value_wad);
     raw_data_container.add_pool_portfolio_value(pool_id, pool_value);
+
+    // Records the fee the arbitrageur pays, the priority fee if it's the pool's controller.
+    let arbitrageur_fee_basis_points = task::effective_fee_basis_points(
+        pool_data.controller,
+        pool_data.fee_basis_points,
+        pool_data.priority_fee_basis_points,
+        recast_address(arbitrageur.address()),
+    );
+    raw_data_container.add_arbitrageur_fee_basis_points(pool_id, arbitrageur_fee_basis_points);
+
+    // Classifies the reference price against the arbitrageur's no-arb band, to count how often the pool is mispriced.
+    let regime = task::classify(
+        portfolio_prices,
+        exchange_price,
+        task::no_arb_fee_wad(arbitrageur_fee_basis_points),
+    );
+    raw_data_container.add_price_regime(pool_id, regime);
+
+    let liquidity = utils::format_units(pool_data.liquidity, "ether")?.parse::<f64>()?;
+
+    // The pool's curve at the block's time remaining, which decays for non-perpetual pools.
+    let mut curve = live_curve(manager, pool_id)?;
+
+    // Benchmarks the pool's value against an ideally arbitraged pool with the same liquidity.
+    let fair_value = math::fair_portfolio_value(&curve, liquidity, price_token0);
+    raw_data_container.add_pool_portfolio_value_theoretical(pool_id, fair_value);
+
+    // Loss versus passively holding the reserves the pool has at its initial price, not its current reserves, at the same marks.
+    // Both are on the live curve, so the loss is the price's divergence and not the curve's time decay.
+    let (initial_x_per_liquidity, initial_y_per_liquidity) =
+        curve.reserves_given_price(config.initial_pool_price());
+    let held_value =
+        initial_x_per_liquidity * liquidity * mark_x + initial_y_per_liquidity * liquidity;
+    raw_data_container.add_divergence_loss(pool_id, pool_value - held_value);
+
+    // Records if the reserves per liquidity are at the edge of the trading function's domain.
+    curve.reserve_x_per_wad = pool_reserve_x / liquidity;
+    curve.reserve_y_per_wad = pool_reserve_y / liquidity;
+    raw_data_container.add_domain_edge_hit(pool_id, curve.is_at_domain_edge());
+
+    // Flags reserves no swap should leave, in case of an underflow or a mis-sized swap.
+    let reserves_valid = are_reserves_valid(&pool_data, config.economic.pool_strike_price_f);
+    if !reserves_valid {
+        let message = format!(
+            "log.rs: Invalid reserves on pool {} at step {}: x {}, y {}, liquidity {}",
+            pool_id, step, pool_data.virtual_x, pool_data.virtual_y, pool_data.liquidity
+        );
+        if config.log.strict_reserves {
+            return Err(message.into());
+        }
+        println!("{} {}", "Warning:".yellow(), message);
+    }
+    raw_data_container.add_reserves_valid(pool_id, reserves_valid);
     raw_data_container.add_pool_data(pool_id, pool_data);
 
     // 3b. Edit portfolio reported price
-    let portfolio_prices = graceful
-        .call(portfolio, "getSpotPrice", pool_id.into_tokens())?
-        .decoded(portfolio)?;
     raw_data_container.add_reported_price(pool_id, portfolio_prices);
 
-    // 3c. Edit portfolio invariant
-    let portfolio_invariant: I256 = I256::zero(); // todo: get actual invariant
+    // 3c. Edit portfolio invariant, of the reserves per liquidity recorded in 3a.
+    let portfolio_invariant = trading_function(admin, library, &curve)?;
     raw_data_container.add_invariant(pool_id, portfolio_invariant);
 
-    // 3d. Edit portfolio value
-    let portfolio_value = U256::zero(); // todo: get actual portfolio value
-    raw_data_container.add_portfolio_value(pool_id, portfolio_value);
+    Ok(true)
+}
+
+/// Value of the pool's reserves in wad units of the quote token, with x marked at `price_wad`:
+/// `virtual_x * price + virtual_y`.
+pub fn pool_portfolio_value_wad(pool_data: &PoolsReturn, price_wad: U256) -> U256 {
+    U256::from(pool_data.virtual_x) * price_wad / utils::parse_ether(1.0).unwrap()
+        + U256::from(pool_data.virtual_y)
+}
+
+/// Fee the pool takes from a swap's input, valued in the quote token at the exchange price `price_wad`, in wad.
+pub fn swap_fee_take(order: &Order, fee_basis_points: u16, price_wad: U256) -> U256 {
+    let fee =
+        U256::from(order.input) * U256::from(fee_basis_points) / U256::from(BASIS_POINT_DIVISOR);
+    if order.sell_asset {
+        fee * price_wad / utils::parse_ether(1.0).unwrap()
+    } else {
+        fee
+    }
+}
+
+/// Accumulates the fee the pool took from the arbitrageur's swap onto the pool's fee revenue,
+/// valued at the fee and exchange price of the last recorded row. `None` if the arbitrageur did not swap.
+pub fn record_fee_revenue(raw_data_container: &mut RawData, pool_id: u64, swapped: Option<&Order>) {
+    let accrued = raw_data_container
+        .fee_revenue_wad
+        .get(&pool_id)
+        .and_then(|fee_revenue| fee_revenue.last().copied())
+        .unwrap_or_default();
+
+    let take = match swapped {
+        Some(order) => {
+            let fee_basis_points = raw_data_container
+                .derived_data
+                .get(&pool_id)
+                .and_then(|derived_data| derived_data.arbitrageur_fee_basis_points.last())
+                .copied()
+                .unwrap_or(0.0) as u16;
+            let price_wad = raw_data_container
+                .exchange_prices_wad
+                .get(&pool_id)
+                .and_then(|prices| prices.last().copied())
+                .unwrap_or_default();
+            swap_fee_take(order, fee_basis_points, price_wad)
+        }
+        None => U256::zero(),
+    };
+
+    raw_data_container.add_fee_revenue(pool_id, accrued + take);
+}
+
+/// Records the value of the arbitrageur's swap input at `price`, the reference price of the step,
+/// as the pool's volume. Zero if the arbitrageur did not swap.
+pub fn record_volume(
+    raw_data_container: &mut RawData,
+    pool_id: u64,
+    swapped: Option<&Order>,
+    price: f64,
+) {
+    let volume = match swapped {
+        Some(order) => {
+            let input = wad_to_float(U256::from(order.input));
+            if order.sell_asset {
+                input * price
+            } else {
+                input
+            }
+        }
+        None => 0.0,
+    };
+
+    raw_data_container.add_volume(pool_id, volume);
+}
+
+/// True if the pool's reserves and liquidity are nonzero and the reserves per liquidity are within
+/// the trading function's domain, at most 1 for x and the strike price for y.
+pub fn are_reserves_valid(pool_data: &PoolsReturn, strike_price_f: f64) -> bool {
+    if pool_data.virtual_x == 0 || pool_data.virtual_y == 0 || pool_data.liquidity == 0 {
+        return false;
+    }
+
+    let x_per_liquidity = pool_data.virtual_x as f64 / pool_data.liquidity as f64;
+    let y_per_liquidity = pool_data.virtual_y as f64 / pool_data.liquidity as f64;
+    x_per_liquidity <= 1.0 && y_per_liquidity <= strike_price_f
+}
+
+/// Writes a snapshot of the pool's trading curve as `curve_{step}.csv` in the configured snapshot directory.
+/// The curve's parameters are the pool's live parameters and its reserves are the last recorded reserves per liquidity,
+/// so the frames of a run can be animated to show the pool's state evolving.
+///
+/// # Columns
+/// * `x`, `y` - Coordinates of the trading curve.
+/// * `reserve_x`, `reserve_y` - Pool's reserves per liquidity, the same on each row.
+pub fn snapshot_curve(
+    manager: &SimulationManager,
+    raw_data_container: &RawData,
+    pool_id: u64,
+    config: &SimConfig,
+    step: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reserve_x = *raw_data_container
+        .get_pool_x_per_lq_float(pool_id)?
+        .last()
+        .ok_or("log.rs: No pool data recorded to snapshot.")?;
+    let reserve_y = *raw_data_container
+        .get_pool_y_per_lq_float(pool_id)?
+        .last()
+        .ok_or("log.rs: No pool data recorded to snapshot.")?;
+
+    let mut curve = live_curve(manager, pool_id)?;
+    curve.reserve_x_per_wad = reserve_x;
+    curve.reserve_y_per_wad = reserve_y;
+
+    let (x, y): (Vec<f64>, Vec<f64>) = curve.get_trading_function_coordinates().into_iter().unzip();
+    let len = x.len();
+    let mut frame = df!(
+        "x" => x,
+        "y" => y,
+        "reserve_x" => vec![reserve_x; len],
+        "reserve_y" => vec![reserve_y; len],
+    )?;
+
+    fs::create_dir_all(&config.log.snapshot_directory)?;
+    let path = format!("{}/curve_{:05}.csv", config.log.snapshot_directory, step);
+    CsvWriter::new(File::create(path)?).finish(&mut frame)?;
 
     Ok(())
 }
+
+/// Reads the pool's curve from the strategy library's `getCurveConfiguration`.
+/// Its time remaining is computed from the block timestamp, so it decays for non-perpetual pools.
+pub fn live_curve(manager: &SimulationManager, pool_id: u64) -> Result<NormalCurve, anyhow::Error> {
+    let admin = manager.agents.get("admin").unwrap();
+    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+    let library = manager.deployed_contracts.get("library").unwrap();
+
+    let curve: SolidityNormalCurve = Caller::new(admin).view(
+        library,
+        "getCurveConfiguration",
+        (recast_address(portfolio.address), pool_id).into_tokens(),
+    )?;
+    Ok(NormalCurve::from_solidity_input(&curve))
+}
+
+/// Computes the invariant of the curve with the strategy library's `tradingFunction`, in wad.
+/// Zero on the curve, signed off of it.
+pub fn trading_function(
+    admin: &dyn Agent,
+    library: &SimulationContract<IsDeployed>,
+    curve: &NormalCurve,
+) -> Result<I256, anyhow::Error> {
+    Caller::new(admin).view(
+        library,
+        "tradingFunction",
+        vec![curve.to_solidity_input().into_token()],
+    )
+}
+
+/// Gets the current virtual x and y reserves of the pool `pool_id`, as floats.
+#[allow(unused)]
+pub fn get_pool_reserves_float(
+    admin: &dyn Agent,
+    portfolio: &SimulationContract<IsDeployed>,
+    pool_id: u64,
+) -> Result<(f64, f64), anyhow::Error> {
+    let pool_data: PoolsReturn =
+        Caller::new(admin).view(portfolio, "pools", pool_id.into_tokens())?;
+
+    let reserve_x = utils::format_units(pool_data.virtual_x, "ether")?.parse::<f64>()?;
+    let reserve_y = utils::format_units(pool_data.virtual_y, "ether")?.parse::<f64>()?;
+
+    Ok((reserve_x, reserve_y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hedges::HedgeQueue;
+    use crate::setup;
+    use crate::step;
+
+    /// Runs a logged sim step at each price on a fresh pool, without generating a price path or writing the run's output.
+    fn logged_steps(prices: &[f64]) -> (RawData, u64) {
+        let config = SimConfig::default();
+        let (mut manager, pool_id) = setup::setup_pool(&config);
+
+        let mut raw_data = RawData::new();
+        let mut hedges = HedgeQueue::new();
+        for (step, price) in prices.iter().enumerate() {
+            task::run_with_price(
+                &mut manager,
+                &mut raw_data,
+                &mut hedges,
+                step,
+                *price,
+                pool_id,
+                &config,
+            )
+            .unwrap();
+        }
+
+        (raw_data, pool_id)
+    }
+
+    #[test]
+    fn pool_reserves_match_created_pool() {
+        let config = SimConfig::default();
+        let mut manager = SimulationManager::new();
+        setup::run(&mut manager, &config).unwrap();
+
+        // Reserves per liquidity the pool is created with.
+        let create_pool_args = setup::build_create_pool_call(&manager, &config).unwrap();
+        let expected_x = wad_to_float(create_pool_args.reserve_x_per_wad);
+        let expected_y = wad_to_float(create_pool_args.reserve_y_per_wad);
+
+        // Allocates one unit of liquidity, so reserves equal the reserves per liquidity.
+        let pool_id = setup::init_pool(&manager, &config).unwrap();
+        setup::allocate_liquidity(&manager, pool_id).unwrap();
+
+        let admin = manager.agents.get("admin").unwrap();
+        let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+        let (reserve_x, reserve_y) = get_pool_reserves_float(admin, portfolio, pool_id).unwrap();
+
+        assert!((reserve_x - expected_x).abs() < 1e-9);
+        assert!((reserve_y - expected_y).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fee_revenue_accrues_each_swap_fee() {
+        let config = SimConfig::default();
+        let (manager, pool_id) = setup::setup_pool(&config);
+
+        // Up then down, so the arbitrageur swaps in each direction.
+        let mut raw_data = RawData::new();
+        let mut takes = Vec::new();
+        for price in [1.1, 1.1, 0.9, 0.9] {
+            let outcome = task::run(&manager, price, pool_id, &config).unwrap();
+            assert!(run(&manager, &mut raw_data, pool_id, &config).unwrap());
+            record_fee_revenue(&mut raw_data, pool_id, outcome.swap.as_ref());
+            takes.push(outcome.swap.map_or(U256::zero(), |order| {
+                swap_fee_take(
+                    &order,
+                    config.economic.pool_fee_basis_points,
+                    *raw_data
+                        .get_exchange_price(pool_id)
+                        .unwrap()
+                        .last()
+                        .unwrap(),
+                )
+            }));
+            step::run(&manager, price).unwrap();
+        }
+
+        let fee_revenue = raw_data.get_fee_revenue(pool_id).unwrap();
+        let mut accrued = U256::zero();
+        for (revenue, take) in fee_revenue.iter().zip(takes.iter()) {
+            accrued += *take;
+            assert_eq!(*revenue, accrued);
+        }
+        assert!(takes[0] > U256::zero());
+        assert!(takes[2] > U256::zero());
+    }
+
+    #[test]
+    fn volume_values_swap_input_in_quote() {
+        let mut raw_data = RawData::new();
+        let order = |sell_asset| Order {
+            use_max: false,
+            pool_id: 0,
+            input: utils::parse_ether(2.0).unwrap().as_u128(),
+            output: 0,
+            sell_asset,
+        };
+
+        record_volume(&mut raw_data, 0, Some(&order(true)), 1.5);
+        record_volume(&mut raw_data, 0, Some(&order(false)), 1.5);
+        record_volume(&mut raw_data, 0, None, 1.5);
+
+        assert_eq!(raw_data.get_volume(0).unwrap(), vec![3.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn theoretical_value_matches_realized_at_initial_price() {
+        let config = SimConfig::default();
+        let (manager, pool_id) = setup::setup_pool(&config);
+
+        let mut raw_data = RawData::new();
+        run(&manager, &mut raw_data, pool_id, &config).unwrap();
+
+        let realized = raw_data.get_portfolio_value(pool_id).unwrap()[0];
+        let theoretical = raw_data.get_portfolio_value_theoretical(pool_id).unwrap()[0];
+        assert!(
+            (realized - theoretical).abs() < 1e-5,
+            "realized: {}, theoretical: {}",
+            realized,
+            theoretical
+        );
+    }
+
+    #[test]
+    fn valuation_prices_differ_only_when_mispriced() {
+        let config = SimConfig::default();
+        let (manager, pool_id) = setup::setup_pool(&config);
+
+        // Pool value of the current state under each valuation price.
+        let values = |manager: &SimulationManager| {
+            [ValuationPrice::Reference, ValuationPrice::Reported].map(|valuation_price| {
+                let mut config = config.clone();
+                config.log.valuation_price = valuation_price;
+                let mut raw_data = RawData::new();
+                run(manager, &mut raw_data, pool_id, &config).unwrap();
+                raw_data.get_portfolio_value(pool_id).unwrap()[0]
+            })
+        };
+
+        // Pegged: the exchange is at the pool's initial price.
+        let [reference, reported] = values(&manager);
+        assert!((reference - reported).abs() < 1e-6);
+
+        // Mispriced: the exchange moves without the arbitrageur.
+        step::run(&manager, 1.2).unwrap();
+        let [reference, reported] = values(&manager);
+        assert!((reference - reported).abs() > 1e-3);
+    }
+
+    #[test]
+    fn divergence_loss_starts_at_zero_and_grows_with_the_move() {
+        let config = SimConfig::default();
+        let (mut manager, pool_id) = setup::setup_pool(&config);
+
+        let mut raw_data = RawData::new();
+        let mut hedges = HedgeQueue::new();
+        run(&manager, &mut raw_data, pool_id, &config).unwrap();
+        // The exchange is set to a step's price after it is logged, so the last step marks at 1.2.
+        for (step, price) in [1.1, 1.2, 1.2].into_iter().enumerate() {
+            task::run_with_price(
+                &mut manager,
+                &mut raw_data,
+                &mut hedges,
+                step,
+                price,
+                pool_id,
+                &config,
+            )
+            .unwrap();
+        }
+
+        // The pool holds its allocation at first, then the arbitrageur trades it out of the appreciating x.
+        let divergence_loss = raw_data.get_divergence_loss(pool_id).unwrap();
+        assert!(divergence_loss[0].abs() < 1e-6, "{:?}", divergence_loss);
+        assert!(
+            divergence_loss[3] < divergence_loss[1],
+            "{:?}",
+            divergence_loss
+        );
+        assert!(divergence_loss[3] < 0.0, "{:?}", divergence_loss);
+    }
+
+    #[test]
+    fn last_timestamp_increases_as_block_time_advances() {
+        let config = SimConfig::default();
+        let (mut manager, pool_id) = setup::setup_pool(&config);
+
+        // Advances block time before each step, the arbitrageur's swaps sync the pool to it.
+        let mut raw_data = RawData::new();
+        let mut hedges = HedgeQueue::new();
+        for (step, price) in [1.05, 1.1, 1.15].into_iter().enumerate() {
+            manager.environment.evm.env.block.timestamp += revm::primitives::U256::from(60);
+            task::run_with_price(
+                &mut manager,
+                &mut raw_data,
+                &mut hedges,
+                step,
+                price,
+                pool_id,
+                &config,
+            )
+            .unwrap();
+        }
+
+        let timestamps = raw_data.get_last_timestamp(pool_id).unwrap();
+        assert_eq!(timestamps.len(), 3);
+        assert!(
+            timestamps.windows(2).all(|w| w[1] > w[0]),
+            "{:?}",
+            timestamps
+        );
+
+        use crate::spreadsheetorizer::Spreadsheet;
+        let spreadsheet = raw_data.to_spreadsheet(pool_id).unwrap();
+        assert_eq!(spreadsheet.column("last_timestamp").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn on_change_only_skips_unchanged_rows() {
+        let logged_rows = |on_change_only: bool| {
+            let mut config = SimConfig::default();
+            config.log.on_change_only = on_change_only;
+
+            let (mut manager, pool_id) = setup::setup_pool(&config);
+
+            // Constant price at the pool's initial price, so the pool never trades.
+            let mut raw_data = RawData::new();
+            let mut hedges = HedgeQueue::new();
+            for step in 0..10 {
+                task::run_with_price(
+                    &mut manager,
+                    &mut raw_data,
+                    &mut hedges,
+                    step,
+                    config.process.initial_price,
+                    pool_id,
+                    &config,
+                )
+                .unwrap();
+            }
+
+            (
+                raw_data.get_reported_price(pool_id).unwrap().len(),
+                raw_data.get_step_index(pool_id).unwrap(),
+            )
+        };
+
+        let (all_rows, all_steps) = logged_rows(false);
+        let (changed_rows, changed_steps) = logged_rows(true);
+
+        assert_eq!(all_rows, 10);
+        assert_eq!(all_steps, (0..10).collect::<Vec<u64>>());
+        assert!(changed_rows < all_rows);
+        assert_eq!(changed_steps, vec![0]);
+    }
+
+    #[test]
+    fn invariant_stays_near_zero_over_flat_and_moving_prices() {
+        let config = SimConfig::default();
+        let (mut manager, pool_id) = setup::setup_pool(&config);
+
+        // A flat price path at the pool's initial price, so the pool never trades.
+        let mut raw_data = RawData::new();
+        let mut hedges = HedgeQueue::new();
+        for step in 0..5 {
+            task::run_with_price(
+                &mut manager,
+                &mut raw_data,
+                &mut hedges,
+                step,
+                config.process.initial_price,
+                pool_id,
+                &config,
+            )
+            .unwrap();
+        }
+
+        let invariant = raw_data.get_invariant_float(pool_id).unwrap();
+        assert_eq!(invariant.len(), 5);
+        assert!(
+            invariant.iter().all(|i| i.abs() < 1e-6),
+            "invariant: {:?}",
+            invariant
+        );
+
+        // Then a moving price path, so the arbitrageur swaps each step.
+        for (step, price) in [1.05, 1.1, 1.0, 0.9, 0.95].into_iter().enumerate() {
+            task::run_with_price(
+                &mut manager,
+                &mut raw_data,
+                &mut hedges,
+                5 + step,
+                price,
+                pool_id,
+                &config,
+            )
+            .unwrap();
+        }
+
+        // The reserves moved, so the recorded invariant is of the swapped reserves, not a constant.
+        let reserves_x = raw_data.get_pool_x_per_lq_float(pool_id).unwrap();
+        assert!(reserves_x[5..].windows(2).all(|w| w[0] != w[1]));
+
+        // Swaps can't decrease the invariant, and the fees only grow it by a little.
+        let invariant = raw_data.get_invariant_float(pool_id).unwrap();
+        assert_eq!(invariant.len(), 10);
+        assert!(
+            invariant[5..].iter().all(|i| i.abs() < 1e-3),
+            "invariant: {:?}",
+            invariant
+        );
+        assert!(
+            invariant[5..].windows(2).all(|w| w[1] >= w[0] - 1e-9),
+            "invariant: {:?}",
+            invariant
+        );
+    }
+
+    #[test]
+    fn pvf_is_reserves_valued_at_reference_price() {
+        let (raw_data, pool_id) = logged_steps(&[1.05, 1.1, 0.95, 1.0]);
+
+        let reserve_x = raw_data.get_pool_x_total_float(pool_id).unwrap();
+        let reserve_y = raw_data.get_pool_y_total_float(pool_id).unwrap();
+        let price_x = raw_data.get_exchange_price_float(pool_id).unwrap();
+        let pvf = raw_data.get_portfolio_value_float(pool_id).unwrap();
+        let pvf_wad = raw_data.get_portfolio_value_wad(pool_id).unwrap();
+
+        assert!(!pvf.is_empty());
+        for i in 0..pvf.len() {
+            let expected = reserve_x[i] * price_x[i] + reserve_y[i];
+            assert!((pvf[i] - expected).abs() < 1e-9 * expected.abs().max(1.0));
+            // The wad and float values agree up to the float's rounding.
+            assert!((wad_to_float(pvf_wad[i]) - pvf[i]).abs() < 1e-12 * pvf[i].abs().max(1.0));
+        }
+    }
+
+    #[test]
+    fn arb_profit_values_a_known_swap_in_the_quote_token() {
+        let config = SimConfig::default();
+        let (manager, pool_id) = setup::setup_pool(&config);
+        let arbitrageur = manager.agents.get("arbitrageur").unwrap();
+        let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+
+        // Marks x away from 1, where valuing y at 1 and at the inverse price agree.
+        let price = 1.25;
+        step::run(&manager, price).unwrap();
+        let mut raw_data = RawData::new();
+        assert!(run(&manager, &mut raw_data, pool_id, &config).unwrap());
+
+        // Buys x with y, for a little less x than the pool quotes so the swap goes through.
+        let input = float_to_wad(0.01);
+        let output = task::get_amount_out(&manager, pool_id, false, input).unwrap() * 99 / 100;
+        Caller::new(arbitrageur)
+            .swap(
+                portfolio,
+                Order {
+                    use_max: false,
+                    pool_id,
+                    input: input.as_u128(),
+                    output: output.as_u128(),
+                    sell_asset: false,
+                },
+            )
+            .unwrap();
+        assert!(run(&manager, &mut raw_data, pool_id, &config).unwrap());
+
+        let profit = raw_data.get_arb_profit(pool_id).unwrap();
+        let expected = wad_to_float(output) * price - wad_to_float(input);
+        assert_eq!(profit[0], 0.0);
+        assert!(
+            (profit[1] - expected).abs() < 1e-9,
+            "{} vs. {}",
+            profit[1],
+            expected
+        );
+    }
+
+    #[test]
+    fn normal_steps_keep_reserves_valid() {
+        let (raw_data, pool_id) = logged_steps(&[1.05, 1.1, 1.2, 0.9, 0.95, 1.0]);
+
+        let valid = raw_data.get_reserves_valid(pool_id).unwrap();
+        assert!(!valid.is_empty());
+        assert!(valid.iter().all(|valid| *valid));
+    }
+
+    #[test]
+    fn drained_reserve_is_invalid() {
+        let wad = 1_000_000_000_000_000_000_u128;
+        let pool_data = PoolsReturn {
+            virtual_x: wad / 2,
+            virtual_y: wad / 2,
+            liquidity: wad,
+            fee_basis_points: 10,
+            priority_fee_basis_points: 0,
+            last_timestamp: 0,
+            controller: ethers::types::Address::zero(),
+            strategy: ethers::types::Address::zero(),
+        };
+        assert!(are_reserves_valid(&pool_data, 1.0));
+
+        // An extreme swap that drains the y reserve, paid in more x than the domain allows.
+        let drained = PoolsReturn {
+            virtual_x: wad * 2,
+            virtual_y: 0,
+            ..pool_data.clone()
+        };
+        assert!(!are_reserves_valid(&drained, 1.0));
+    }
+
+    #[tokio::test]
+    async fn curve_snapshots_follow_cadence() {
+        let directory = std::env::temp_dir().join("proto_sim_curve_snapshots");
+        let _ = fs::remove_dir_all(&directory);
+
+        // Nine arbitrage steps after the first price, snapshotting every third.
+        let mut config = SimConfig::default();
+        config.process.num_steps = 10;
+        config.log.snapshot_every = 3;
+        config.log.snapshot_directory = directory.to_str().unwrap().to_string();
+        crate::sim::run(&config).await.unwrap();
+
+        let snapshots = fs::read_dir(&directory).unwrap().count();
+        assert_eq!(snapshots, 3);
+    }
+}
@@ -1,18 +1,27 @@
+mod aggregate;
 mod analysis;
 mod bisection;
 mod calls;
 mod cli;
 mod common;
 mod config;
+mod counters;
+mod estimate;
+mod event_log;
+mod hedges;
 mod log;
 mod math;
 mod plots;
 mod raw_data;
+mod scenario;
+mod seed_sweep;
 mod setup;
 mod sim;
 mod spreadsheetorizer;
 mod step;
+mod summary;
 mod task;
+mod validate;
 
 /// # Proto Sim
 /// Proof of concept simulation of EVM execution with an arbitrageur agent,
@@ -26,6 +35,11 @@ mod task;
 /// cargo run sim
 /// cargo run analyze -n trading_function -s error
 /// cargo run analyze -n trading_function -s curve
+/// cargo run analyze -n trading_function -s error --step 0.0001
+/// cargo run seed-sweep --seeds 100
+/// cargo run estimate --steps 1000000
+/// cargo run validate --sim out_data/results.csv --theory theory.csv
+/// cargo run sim --event-log out_data/events.ndjson
 /// ```
 ///
 /// # Errors
@@ -1,14 +1,85 @@
 /// Implements the portfolio "Normal Strategy" math functions in rust.
-use arbiter::utils::wad_to_float;
-use ethers::{types::U256, utils::parse_ether};
+use arbiter::utils::{float_to_wad, wad_to_float};
+use ethers::{
+    types::{I256, U256},
+    utils::parse_ether,
+};
+use serde::Deserialize;
 use statrs::distribution::{ContinuousCDF, Normal};
+use std::sync::OnceLock;
 
-use super::bisection;
-use bindings::{portfolio::PoolsReturn, shared_types::PortfolioConfig};
+use super::bisection::{self, BisectionError};
+use super::common::BASIS_POINT_DIVISOR;
+use super::config::{Economic, SimConfig};
+use bindings::{
+    external_normal_strategy_lib::NormalCurve as SolidityNormalCurve, portfolio::PoolsReturn,
+    shared_types::PortfolioConfig,
+};
 
 /// Amount of seconds per year used in the smart contracts.
 pub static SECONDS_PER_YEAR: f64 = 31556953.0;
 
+/// Whether the `VERBOSE` env variable is set, read once since the solvers check it on every iteration.
+pub fn verbose() -> bool {
+    static VERBOSE: OnceLock<bool> = OnceLock::new();
+    *VERBOSE.get_or_init(|| std::env::var("VERBOSE").is_ok())
+}
+
+/// Side of the current invariant that the root finders target.
+/// Above - rounds the other reserve in favor of the pool, i.e. less output.
+/// Below - rounds the other reserve in favor of the swapper, i.e. more output.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum InvariantSign {
+    Above,
+    Below,
+}
+
+/// Payoff the trading function replicates.
+/// Call - covered call, k = Φ⁻¹(y/K) - Φ⁻¹(1-x) + σ√τ.
+/// Put - cash secured put, the roles of the reserves are swapped, k = Φ⁻¹(x) - Φ⁻¹(1-y/K) + σ√τ.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CurveVariant {
+    #[default]
+    Call,
+    Put,
+}
+
+/// Offset from the current invariant that the root finders target.
+/// epsilon - absolute distance from the current invariant.
+/// sign - side of the current invariant to target.
+///
+/// The trading function increases with both reserves, and a swap is only valid
+/// if the invariant does not decrease. Targeting slightly above the current invariant
+/// leaves a bit more of the other reserve in the pool, so the approximated output
+/// is conservative and does not revert due to floating point error.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct InvariantOffset {
+    pub epsilon: f64,
+    pub sign: InvariantSign,
+}
+
+/// Targets 1e-5 above the current invariant.
+impl Default for InvariantOffset {
+    fn default() -> Self {
+        Self {
+            epsilon: 1e-5,
+            sign: InvariantSign::Above,
+        }
+    }
+}
+
+impl InvariantOffset {
+    /// The invariant value the root finders solve for.
+    pub fn target(&self, invariant: f64) -> f64 {
+        match self.sign {
+            InvariantSign::Above => invariant + self.epsilon,
+            InvariantSign::Below => invariant - self.epsilon,
+        }
+    }
+}
+
 /// Normal curve contains the parameters for the normal distribution trading function
 /// reserve_x_per_wad - x reserves per liquidity, scaled from wad to float.
 /// reserve_y_per_wad - y reserves per liquidity, scaled from wad to float.
@@ -16,6 +87,7 @@ pub static SECONDS_PER_YEAR: f64 = 31556953.0;
 /// std_dev_f - standard deviation, scaled from wad to float.
 /// time_remaining_sec - time remaining in seconds, same units.
 /// invariant_f - invariant, scaled from wad to float.
+/// variant - payoff the trading function replicates, call or put. The root finders solve the variant's trading function.
 #[derive(Clone)]
 pub struct NormalCurve {
     pub reserve_x_per_wad: f64,
@@ -24,6 +96,7 @@ pub struct NormalCurve {
     pub std_dev_f: f64,
     pub time_remaining_sec: f64,
     pub invariant_f: f64,
+    pub variant: CurveVariant,
 }
 
 /// Math functions of the trading function,
@@ -49,6 +122,13 @@ pub struct NormalCurve {
 ///  -> Φ⁻¹(1-x) = Φ⁻¹(y/K) + σ√τ - k
 ///      -> 1-x = Φ(Φ⁻¹(y/K) + σ√τ - k)
 ///          -> x = 1 - Φ(Φ⁻¹(y/K) + σ√τ - k)
+///
+/// Put adjusted trading function, the reserves swap roles
+/// k = Φ⁻¹(x) - Φ⁻¹(1-y/K) + σ√τ
+///  -> Φ⁻¹(1-y/K) = Φ⁻¹(x) + σ√τ - k
+///      -> y = K(1 - Φ(Φ⁻¹(x) + σ√τ - k))
+///  -> Φ⁻¹(x) = Φ⁻¹(1-y/K) - σ√τ + k
+///      -> x = Φ(Φ⁻¹(1-y/K) - σ√τ + k)
 /// todo: fixed point arithmetic?
 ///
 /// note: uses floating point math and depends on
@@ -73,15 +153,18 @@ impl NormalCurve {
             std_dev_f,
             time_remaining_sec,
             invariant_f,
+            variant: CurveVariant::Call,
         }
     }
 
     /// constructor from portfolio pool
     /// pool_return - Return from calling the portfolio contract's `pools(uint64 poolId)` function.
     /// portfolio_config - Return from calling the pool's __strategy__ contract's `configs(uint64 poolId)` function.
+    /// variant - payoff the pool's trading function replicates.
     pub fn new_from_portfolio(
         pool_return: &PoolsReturn,
         portfolio_config: &PortfolioConfig,
+        variant: CurveVariant,
     ) -> Self {
         Self {
             reserve_x_per_wad: wad_to_float(
@@ -102,9 +185,78 @@ impl NormalCurve {
             std_dev_f: (portfolio_config.volatility_basis_points as f64) / 10000.0,
             time_remaining_sec: portfolio_config.duration_seconds as f64,
             invariant_f: 0.0,
+            variant,
+        }
+    }
+
+    /// constructor from the solidity `NormalCurve` struct, scaling the wad values to floats.
+    pub fn from_solidity_input(curve: &SolidityNormalCurve) -> Self {
+        Self {
+            reserve_x_per_wad: wad_to_float(curve.reserve_x_per_wad),
+            reserve_y_per_wad: wad_to_float(curve.reserve_y_per_wad),
+            strike_price_f: wad_to_float(curve.strike_price_wad),
+            std_dev_f: wad_to_float(curve.standard_deviation_wad),
+            time_remaining_sec: curve.time_remaining_seconds.as_u64() as f64,
+            invariant_f: curve.invariant.as_i128() as f64 / 1e18,
+            variant: CurveVariant::Call,
+        }
+    }
+
+    /// converts to the solidity `NormalCurve` struct, scaling the floats to wad values.
+    pub fn to_solidity_input(&self) -> SolidityNormalCurve {
+        SolidityNormalCurve {
+            reserve_x_per_wad: float_to_wad(self.reserve_x_per_wad),
+            reserve_y_per_wad: float_to_wad(self.reserve_y_per_wad),
+            strike_price_wad: float_to_wad(self.strike_price_f),
+            standard_deviation_wad: float_to_wad(self.std_dev_f),
+            time_remaining_seconds: U256::from(self.time_remaining_sec as u64),
+            invariant: I256::from((self.invariant_f * 1e18) as i128),
         }
     }
 
+    /// constructor from the sim config's pool parameters, with reserves at `price`.
+    /// Perpetual pools use a constant time remaining of one year, same as the contracts.
+    pub fn new_from_economic(economic: &Economic, price: f64) -> Self {
+        let time_remaining_sec = if economic.pool_is_perpetual {
+            SECONDS_PER_YEAR
+        } else {
+            economic.pool_time_remaining_years_f * SECONDS_PER_YEAR
+        };
+
+        let mut curve = Self::new(
+            0.0,
+            0.0,
+            economic.pool_strike_price_f,
+            economic.pool_volatility_f,
+            time_remaining_sec,
+            0.0,
+        );
+        (curve.reserve_x_per_wad, curve.reserve_y_per_wad) = curve.reserves_given_price(price);
+        curve
+    }
+
+    /// computes the reserves per liquidity of a pool priced at `price`.
+    /// x = 1 - Φ(ln(P/K)/σ√τ + σ√τ/2)
+    /// y = KΦ(ln(P/K)/σ√τ - σ√τ/2)
+    pub fn reserves_given_price(&self, price: f64) -> (f64, f64) {
+        let n = Normal::new(0.0, 1.0).unwrap();
+        // σ√τ
+        let std_dev_sqrt_tau =
+            self.std_dev_f * f64::sqrt(self.time_remaining_sec / SECONDS_PER_YEAR);
+        // ln(P/K)/σ√τ
+        let d = (price / self.strike_price_f).ln() / std_dev_sqrt_tau;
+
+        let x = 1.0 - n.cdf(d + std_dev_sqrt_tau / 2.0);
+        let y = self.strike_price_f * n.cdf(d - std_dev_sqrt_tau / 2.0);
+        (x, y)
+    }
+
+    /// computes the value per liquidity of a pool priced at `price`, denominated in y.
+    pub fn value_given_price(&self, price: f64) -> f64 {
+        let (x, y) = self.reserves_given_price(price);
+        x * price + y
+    }
+
     /// computes the adjusted trading function invariant
     /// invariant = Φ⁻¹(y/K) - Φ⁻¹(1-x) + σ√τ
     pub fn trading_function_floating(&self) -> f64 {
@@ -120,12 +272,16 @@ impl NormalCurve {
             return std_dev_sqrt_tau;
         }
         // Φ⁻¹(1 - x)
-        let invariant_term_x = n.inverse_cdf(1.0 - self.reserve_x_per_wad);
+        let invariant_term_x = n.inverse_cdf(1.0 - clamp_to_domain(self.reserve_x_per_wad, 1.0));
         // Φ⁻¹(y/K)
-        let invariant_term_y = n.inverse_cdf(self.reserve_y_per_wad / self.strike_price_f);
-        println!("invariant_term_x: {}", invariant_term_x);
-        println!("invariant_term_y: {}", invariant_term_y);
-        println!("std_dev_sqrt_tau: {}", std_dev_sqrt_tau);
+        let invariant_term_y = n.inverse_cdf(
+            clamp_to_domain(self.reserve_y_per_wad, self.strike_price_f) / self.strike_price_f,
+        );
+        if verbose() {
+            println!("invariant_term_x: {}", invariant_term_x);
+            println!("invariant_term_y: {}", invariant_term_y);
+            println!("std_dev_sqrt_tau: {}", std_dev_sqrt_tau);
+        }
         // k = Φ⁻¹(y/K) - Φ⁻¹(1-x) + σ√τ
         let k = invariant_term_y - invariant_term_x + std_dev_sqrt_tau;
 
@@ -146,7 +302,7 @@ impl NormalCurve {
         let std_dev_sqrt_tau =
             self.std_dev_f * f64::sqrt(self.time_remaining_sec / SECONDS_PER_YEAR);
         // Φ⁻¹(1 - x)
-        let invariant_term_x = n.inverse_cdf(1.0 - self.reserve_x_per_wad);
+        let invariant_term_x = n.inverse_cdf(1.0 - clamp_to_domain(self.reserve_x_per_wad, 1.0));
         // y = KΦ(Φ⁻¹(1-x) - σ√τ + k)
         let k = 0.0; // if we are solving for y, k = 0.0
         let y = self.strike_price_f * n.cdf(invariant_term_x - std_dev_sqrt_tau + k);
@@ -168,7 +324,9 @@ impl NormalCurve {
         let std_dev_sqrt_tau =
             self.std_dev_f * f64::sqrt(self.time_remaining_sec / SECONDS_PER_YEAR);
         // Φ⁻¹(y/K)
-        let invariant_term_y = n.inverse_cdf(self.reserve_y_per_wad / self.strike_price_f);
+        let invariant_term_y = n.inverse_cdf(
+            clamp_to_domain(self.reserve_y_per_wad, self.strike_price_f) / self.strike_price_f,
+        );
         // x = 1 - Φ(Φ⁻¹(y/K) + σ√τ - k)
         let k = self.trading_function_floating();
         let x = 1.0 - n.cdf(invariant_term_y + std_dev_sqrt_tau - k);
@@ -176,6 +334,97 @@ impl NormalCurve {
         x
     }
 
+    /// computes the trading function invariant of the curve's variant.
+    pub fn trading_function_variant(&self) -> f64 {
+        match self.variant {
+            CurveVariant::Call => self.trading_function_floating(),
+            CurveVariant::Put => self.trading_function_put(),
+        }
+    }
+
+    /// computes the y variable of the curve's variant.
+    pub fn approximate_y_given_x_variant(&self) -> f64 {
+        match self.variant {
+            CurveVariant::Call => self.approximate_y_given_x_floating(),
+            CurveVariant::Put => self.approximate_y_given_x_put(),
+        }
+    }
+
+    /// computes the x variable of the curve's variant.
+    pub fn approximate_x_given_y_variant(&self) -> f64 {
+        match self.variant {
+            CurveVariant::Call => self.approximate_x_given_y_floating(),
+            CurveVariant::Put => self.approximate_x_given_y_put(),
+        }
+    }
+
+    /// computes the put adjusted trading function invariant
+    /// invariant = Φ⁻¹(x) - Φ⁻¹(1-y/K) + σ√τ
+    pub fn trading_function_put(&self) -> f64 {
+        // standard normal distribution...
+        let n = Normal::new(0.0, 1.0).unwrap();
+        // σ√τ
+        let std_dev_sqrt_tau =
+            self.std_dev_f * f64::sqrt(self.time_remaining_sec / SECONDS_PER_YEAR);
+
+        // Return early if we hit the bounds, mirrors the call variant.
+        if self.reserve_x_per_wad >= 1.0 || self.reserve_y_per_wad >= self.strike_price_f {
+            return std_dev_sqrt_tau;
+        }
+        // Φ⁻¹(x)
+        let invariant_term_x = n.inverse_cdf(clamp_to_domain(self.reserve_x_per_wad, 1.0));
+        // Φ⁻¹(1 - y/K)
+        let invariant_term_y = n.inverse_cdf(
+            1.0 - clamp_to_domain(self.reserve_y_per_wad, self.strike_price_f)
+                / self.strike_price_f,
+        );
+        // k = Φ⁻¹(x) - Φ⁻¹(1-y/K) + σ√τ
+        invariant_term_x - invariant_term_y + std_dev_sqrt_tau
+    }
+
+    /// computes the put adjusted trading function y variable.
+    /// y = K(1 - Φ(Φ⁻¹(x) + σ√τ - k))
+    pub fn approximate_y_given_x_put(&self) -> f64 {
+        // todo: handle bounds better, this assumes all reserves are in x
+        if self.reserve_x_per_wad >= 1.0 {
+            return 0.0;
+        }
+
+        // standard normal distribution...
+        let n = Normal::new(0.0, 1.0).unwrap();
+        // σ√τ
+        let std_dev_sqrt_tau =
+            self.std_dev_f * f64::sqrt(self.time_remaining_sec / SECONDS_PER_YEAR);
+        // Φ⁻¹(x)
+        let invariant_term_x = n.inverse_cdf(clamp_to_domain(self.reserve_x_per_wad, 1.0));
+        // y = K(1 - Φ(Φ⁻¹(x) + σ√τ - k))
+        let k = 0.0; // if we are solving for y, k = 0.0
+        self.strike_price_f * (1.0 - n.cdf(invariant_term_x + std_dev_sqrt_tau - k))
+    }
+
+    /// computes the put adjusted trading function x variable.
+    /// x = Φ(Φ⁻¹(1-y/K) - σ√τ + k)
+    pub fn approximate_x_given_y_put(&self) -> f64 {
+        // todo: handle bounds better. This assumes all tokens are in the y reserve.
+        if self.reserve_y_per_wad >= self.strike_price_f {
+            return 0.0;
+        }
+
+        // standard normal distribution...
+        let n = Normal::new(0.0, 1.0).unwrap();
+        // σ√τ
+        let std_dev_sqrt_tau =
+            self.std_dev_f * f64::sqrt(self.time_remaining_sec / SECONDS_PER_YEAR);
+        // Φ⁻¹(1 - y/K)
+        let invariant_term_y = n.inverse_cdf(
+            1.0 - clamp_to_domain(self.reserve_y_per_wad, self.strike_price_f)
+                / self.strike_price_f,
+        );
+        // x = Φ(Φ⁻¹(1-y/K) - σ√τ + k)
+        let k = self.trading_function_put();
+        n.cdf(invariant_term_y - std_dev_sqrt_tau + k)
+    }
+
     /// gets the y coordinates for the trading function across the range (0, 1)
     pub fn get_trading_function_coordinates(&self) -> Vec<(f64, f64)> {
         let mut points = Vec::new();
@@ -191,7 +440,7 @@ impl NormalCurve {
             let _ = y; // silence! shh!!
 
             copy.reserve_x_per_wad = x;
-            y = self.approximate_y_given_x_floating();
+            y = copy.approximate_y_given_x_variant();
             points.push((x, y));
             x += 0.01;
         }
@@ -200,88 +449,318 @@ impl NormalCurve {
     }
 
     /// approximates the maximum amount out of a given trade.
-    pub fn approximate_amount_out(&self, sell_asset: bool, amount_in: f64) -> f64 {
+    pub fn approximate_amount_out(
+        &self,
+        sell_asset: bool,
+        amount_in: f64,
+    ) -> Result<f64, BisectionError> {
+        self.approximate_amount_out_with_offset(sell_asset, amount_in, InvariantOffset::default())
+    }
+
+    /// approximates the maximum amount out of a given trade, targeting the invariant offset.
+    pub fn approximate_amount_out_with_offset(
+        &self,
+        sell_asset: bool,
+        amount_in: f64,
+        offset: InvariantOffset,
+    ) -> Result<f64, BisectionError> {
         if sell_asset {
             let reserve_in = self.reserve_x_per_wad + amount_in;
-            let reserve_out = self.approximate_other_reserve(true, reserve_in);
-            self.reserve_y_per_wad - reserve_out // current reserve - new reserve
+            let reserve_out = self.approximate_other_reserve(true, reserve_in, offset)?;
+            Ok(self.reserve_y_per_wad - reserve_out) // current reserve - new reserve
         } else {
-            println!("reserve y per wad: {}", self.reserve_y_per_wad);
+            if verbose() {
+                println!("reserve y per wad: {}", self.reserve_y_per_wad);
+            }
             let reserve_in = self.reserve_y_per_wad + amount_in;
-            let reserve_out = self.approximate_other_reserve(false, reserve_in);
-            self.reserve_x_per_wad - reserve_out // current reserve - new reserve
+            let reserve_out = self.approximate_other_reserve(false, reserve_in, offset)?;
+            Ok(self.reserve_x_per_wad - reserve_out) // current reserve - new reserve
         }
     }
 
-    /// finds the root such that the invariant is 1e-18 more than the current invariant.
+    /// approximates the amount in required to get `amount_out` out of a trade,
+    /// the inverse of `approximate_amount_out`.
+    pub fn approximate_amount_in(
+        &self,
+        sell_asset: bool,
+        amount_out: f64,
+    ) -> Result<f64, BisectionError> {
+        self.approximate_amount_in_with_offset(sell_asset, amount_out, InvariantOffset::default())
+    }
+
+    /// approximates the amount in required to get `amount_out` out of a trade, targeting the invariant offset.
+    /// Solves for the reserve in given the reserve out, so the other direction of `approximate_other_reserve`.
+    pub fn approximate_amount_in_with_offset(
+        &self,
+        sell_asset: bool,
+        amount_out: f64,
+        offset: InvariantOffset,
+    ) -> Result<f64, BisectionError> {
+        if sell_asset {
+            let reserve_out = self.reserve_y_per_wad - amount_out;
+            let reserve_in = self.approximate_other_reserve(false, reserve_out, offset)?;
+            Ok(reserve_in - self.reserve_x_per_wad) // new reserve - current reserve
+        } else {
+            let reserve_out = self.reserve_x_per_wad - amount_out;
+            let reserve_in = self.approximate_other_reserve(true, reserve_out, offset)?;
+            Ok(reserve_in - self.reserve_y_per_wad) // new reserve - current reserve
+        }
+    }
+
+    /// finds the root such that the invariant is offset from the current invariant.
     /// sell_asset - if true, we are increasing the x reserve, else we are increasing the y reserve
     /// amount_in_f - the known x or y reserve value
-    pub fn approximate_other_reserve(&self, sell_asset: bool, reserve_in: f64) -> f64 {
+    /// offset - distance and side of the current invariant to target
+    /// errors if neither root finder finds the other reserve.
+    pub fn approximate_other_reserve(
+        &self,
+        sell_asset: bool,
+        reserve_in: f64,
+        offset: InvariantOffset,
+    ) -> Result<f64, BisectionError> {
         // if sell asset, use the find root swapping x, else use the find root swapping y in the bisection's fx argument
-
         let mut copy = self.clone();
         let lower_bound;
         let upper_bound;
         if sell_asset {
             copy.reserve_x_per_wad = reserve_in;
-            let approximated = copy.approximate_y_given_x_floating();
-            println!("x reserve: {}", reserve_in);
-            println!("approximated y: {}", approximated);
-            upper_bound = approximated * 1.1;
-            lower_bound = approximated * 0.9;
+            let approximated = copy.approximate_y_given_x_variant();
+            if verbose() {
+                println!("x reserve: {}", reserve_in);
+                println!("approximated y: {}", approximated);
+            }
+            (lower_bound, upper_bound) = search_bounds(approximated, self.strike_price_f);
         } else {
             copy.reserve_y_per_wad = reserve_in;
-            let approximated = copy.approximate_x_given_y_floating();
-            println!("y reserve: {}", reserve_in);
-            println!("approximated x: {}", approximated);
-            upper_bound = approximated * 1.1;
-            lower_bound = approximated * 0.9;
+            let approximated = copy.approximate_x_given_y_variant();
+            if verbose() {
+                println!("y reserve: {}", reserve_in);
+                println!("approximated x: {}", approximated);
+            }
+            (lower_bound, upper_bound) = search_bounds(approximated, 1.0);
         }
 
-        let bisect = bisection::Bisection::new(lower_bound, upper_bound, 1e-9, 1000.0);
+        // Newton-Raphson converges faster, but falls back to Brent's method if it leaves the reserve's domain.
+        let newton = bisection::NewtonRaphson::new((lower_bound + upper_bound) / 2.0, 1e-12, 100.0);
+        let brent = bisection::Brent::new(lower_bound, upper_bound, 1e-9, 1000.0);
 
         if sell_asset {
-            let other_reserve = bisect.bisection(|x| copy.find_root_swapping_x(x));
+            let fx = |x| copy.find_root_swapping_x(x, offset);
+            let other_reserve = newton
+                .newton_raphson(fx)
+                .filter(|root| *root > 0.0 && *root < self.strike_price_f);
+            let other_reserve = match other_reserve {
+                Some(root) => root,
+                None => brent.solve(fx)?,
+            };
 
             copy.reserve_y_per_wad = other_reserve;
-            let k = copy.trading_function_floating();
-            println!("k: {}", k);
+            if verbose() {
+                println!("k: {}", copy.trading_function_variant());
+            }
 
-            other_reserve
+            Ok(other_reserve)
         } else {
-            let other_reserve = bisect.bisection(|x| copy.find_root_swapping_y(x));
+            let fx = |x| copy.find_root_swapping_y(x, offset);
+            let other_reserve = newton
+                .newton_raphson(fx)
+                .filter(|root| *root > 0.0 && *root < 1.0);
+            let other_reserve = match other_reserve {
+                Some(root) => root,
+                None => brent.solve(fx)?,
+            };
 
             copy.reserve_x_per_wad = other_reserve;
-            let k = copy.trading_function_floating();
-            println!("k: {}", k);
+            if verbose() {
+                println!("k: {}", copy.trading_function_variant());
+            }
 
-            other_reserve
+            Ok(other_reserve)
         }
     }
 
-    /// finds the root such that the invariant is 1e-18 more than the current invariant.
-    /// value - the known x reserve value
-    /// returns the y value that would result in the invariant being 1e-18 more than the current invariant.
-    pub fn find_root_swapping_x(&self, value: f64) -> f64 {
+    /// finds the root such that the invariant is offset from the current invariant.
+    /// value - the candidate y reserve value
+    /// offset - distance and side of the current invariant to target, see `InvariantOffset`.
+    /// returns the distance of the invariant at `value` from the targeted invariant.
+    pub fn find_root_swapping_x(&self, value: f64, offset: InvariantOffset) -> f64 {
         let mut copy = self.clone();
         copy.reserve_y_per_wad = value;
-        let result = copy.trading_function_floating() - (self.invariant_f + 1e-5);
+        let result = copy.trading_function_variant() - offset.target(self.invariant_f);
 
-        println!("swap x in, y reserve: {}, invariant: {}", value, result);
+        if verbose() {
+            println!("swap x in, y reserve: {}, invariant: {}", value, result);
+        }
         result
     }
 
-    /// finds the root such that the invariant is 1e-18 less than the current invariant.
-    /// value - the known y reserve value
-    /// returns the x value that would result in the invariant being 1e-18 less than the current invariant.
-    pub fn find_root_swapping_y(&self, value: f64) -> f64 {
+    /// finds the root such that the invariant is offset from the current invariant.
+    /// Uses the same side as swapping x, since the invariant also increases with the x reserve.
+    /// value - the candidate x reserve value
+    /// offset - distance and side of the current invariant to target, see `InvariantOffset`.
+    /// returns the distance of the invariant at `value` from the targeted invariant.
+    pub fn find_root_swapping_y(&self, value: f64, offset: InvariantOffset) -> f64 {
         let mut copy = self.clone();
         copy.reserve_x_per_wad = value;
-        let result = copy.trading_function_floating() - (self.invariant_f + 1e-5);
+        let result = copy.trading_function_variant() - offset.target(self.invariant_f);
 
-        println!("swap y in, x reserve: {}, invariant: {}", value, result);
+        if verbose() {
+            println!("swap y in, x reserve: {}, invariant: {}", value, result);
+        }
         result
     }
+
+    /// true if a reserve is at the edge of its valid domain, (0, 1) for x and (0, K) for y,
+    /// where the trading function is undefined and gets clamped.
+    pub fn is_at_domain_edge(&self) -> bool {
+        clamp_to_domain(self.reserve_x_per_wad, 1.0) != self.reserve_x_per_wad
+            || clamp_to_domain(self.reserve_y_per_wad, self.strike_price_f)
+                != self.reserve_y_per_wad
+    }
+
+    /// computes the delta of the pool per liquidity at `price`, i.e. the x reserve it holds.
+    /// Δ = 1 - Φ(ln(P/K)/σ√τ + σ√τ/2)
+    pub fn delta(&self, price: f64) -> f64 {
+        self.reserves_given_price(price).0
+    }
+
+    /// solves for the pool parameters that have a delta of `target_delta` at `price`.
+    /// A single delta target pins a single parameter, so the volatility is held at the curve's
+    /// and the strike is found with the root finder. The delta increases with the strike.
+    /// target_delta - delta to solve for, in (0, 1).
+    /// returns (strike, volatility).
+    pub fn solve_params_for_delta(
+        &self,
+        target_delta: f64,
+        price: f64,
+    ) -> Result<(f64, f64), BisectionError> {
+        let fx = |strike: f64| {
+            let mut copy = self.clone();
+            copy.strike_price_f = strike;
+            copy.delta(price) - target_delta
+        };
+
+        // strikes far enough from the price to have a delta near 0 and 1 for typical volatilities.
+        let bisection = bisection::Bisection::new(price * 1e-3, price * 1e3, 1e-12, 1000.0);
+        let strike = bisection.bisection(fx)?;
+
+        Ok((strike, self.std_dev_f))
+    }
+}
+
+/// Distance from the edges of the reserves' domains that reserves are clamped to.
+pub static DOMAIN_EPSILON: f64 = 1e-9;
+
+/// Bounds of the root finders' search for a reserve, 10% either side of its closed form `approximated` value,
+/// clamped to the reserve's valid range (0, `upper`). Near the edges of the range, the unclamped bounds
+/// would leave the domain, where the trading function is clamped flat and has no root.
+pub fn search_bounds(approximated: f64, upper: f64) -> (f64, f64) {
+    (
+        clamp_to_domain(approximated * 0.9, upper),
+        clamp_to_domain(approximated * 1.1, upper),
+    )
+}
+
+/// Clamps a reserve into its domain (0, upper), keeping it `DOMAIN_EPSILON` from the edges
+/// so the inverse normal cdf stays finite. NaN reserves are clamped to the lower edge.
+pub fn clamp_to_domain(reserve: f64, upper: f64) -> f64 {
+    if reserve.is_nan() || reserve <= DOMAIN_EPSILON {
+        DOMAIN_EPSILON
+    } else if reserve > upper - DOMAIN_EPSILON {
+        upper - DOMAIN_EPSILON
+    } else {
+        reserve
+    }
+}
+
+/// Estimates the number of steps an LP must hold before the expected fees
+/// earned equal the expected impermanent loss, or `None` if fees never catch up
+/// within the configured number of steps.
+///
+/// Approximations, per liquidity and starting at the initial price:
+/// - Fees per step are the fee taken on the x reserves traded to rebalance the pool
+///   after a one standard deviation move of the price, `realized_vol * √timestep`.
+/// - Impermanent loss after `n` steps is the value of holding the initial reserves
+///   minus the pool's value, after a one standard deviation move over `n` steps.
+/// Both moves are averaged over an up and a down move.
+///
+/// # Arguments
+/// * `config` - Sim config, defines the pool parameters, the timestep in years, and the number of steps.
+/// * `realized_vol` - Annualized volatility of the price. (f64)
+/// * `fee_bps` - Fee of the pool in basis points. (u16)
+#[allow(unused)]
+pub fn break_even_steps(config: &SimConfig, realized_vol: f64, fee_bps: u16) -> Option<f64> {
+    let fee = fee_bps as f64 / BASIS_POINT_DIVISOR as f64;
+    if fee <= 0.0 {
+        return None;
+    }
+
+    let price = config.initial_pool_price();
+    let curve = NormalCurve::new_from_economic(&config.economic, price);
+    let (x_0, y_0) = (curve.reserve_x_per_wad, curve.reserve_y_per_wad);
+
+    // Averages a function of the price over a move up and down by `log_move`.
+    let averaged = |log_move: f64, f: &dyn Fn(f64) -> f64| {
+        (f(price * log_move.exp()) + f(price * (-log_move).exp())) / 2.0
+    };
+
+    let step_move = realized_vol * config.process.timestep.sqrt();
+    let traded_value = averaged(step_move, &|p: f64| {
+        (curve.reserves_given_price(p).0 - x_0).abs() * p
+    });
+    let fees_per_step = fee * traded_value;
+
+    for n in 1..=config.process.num_steps {
+        let horizon_move = realized_vol * (n as f64 * config.process.timestep).sqrt();
+        let impermanent_loss = averaged(horizon_move, &|p: f64| {
+            x_0 * p + y_0 - curve.value_given_price(p)
+        });
+
+        if fees_per_step * n as f64 >= impermanent_loss {
+            return Some(n as f64);
+        }
+    }
+
+    None
+}
+
+/// Computes the value an ideally arbitraged pool with `liquidity` has at `reference_price`,
+/// the value of the reserves the trading function prescribes at that price, for benchmarking the pool's realized value.
+/// Valued the same way as the pool's realized portfolio value, `x * price + y`.
+///
+/// # Arguments
+/// * `curve` - Curve of the pool, with its current time remaining. (NormalCurve)
+/// * `liquidity` - Liquidity of the pool. (f64)
+/// * `reference_price` - Reference price, in the contracts' quote per base units. (f64)
+pub fn fair_portfolio_value(curve: &NormalCurve, liquidity: f64, reference_price: f64) -> f64 {
+    let (reserve_x_per_wad, reserve_y_per_wad) = curve.reserves_given_price(reference_price);
+    let reserve_x = reserve_x_per_wad * liquidity;
+    let reserve_y = reserve_y_per_wad * liquidity;
+
+    reserve_x * reference_price + reserve_y
+}
+
+/// Computes the initial reserves per liquidity, in wad, that `createPool` is called with for the config's pool,
+/// without the actor's `getCreatePoolComputedArgs` contract call.
+/// Volatility and duration are truncated to basis points and seconds, same as the contract call's arguments.
+///
+/// # Returns
+/// * `(reserve_x_per_wad, reserve_y_per_wad)` - Initial reserves per liquidity at the initial price. (U256, U256)
+#[allow(unused)]
+pub fn compute_create_pool_reserves(config: &SimConfig) -> (U256, U256) {
+    let mut economic = config.economic.clone();
+    economic.pool_volatility_f = (economic.pool_volatility_f * BASIS_POINT_DIVISOR as f64) as u32
+        as f64
+        / BASIS_POINT_DIVISOR as f64;
+    economic.pool_time_remaining_years_f =
+        (economic.pool_time_remaining_years_f * SECONDS_PER_YEAR) as u32 as f64 / SECONDS_PER_YEAR;
+
+    let curve = NormalCurve::new_from_economic(&economic, config.initial_pool_price());
+
+    (
+        float_to_wad(curve.reserve_x_per_wad),
+        float_to_wad(curve.reserve_y_per_wad),
+    )
 }
 
 /// Exposes nice methods to easily graph whatever data!
@@ -330,6 +809,7 @@ mod tests {
         std_dev_f: 1.0,
         time_remaining_sec: 31556953.0,
         invariant_f: 0.0,
+        variant: CurveVariant::Call,
     };
 
     #[test]
@@ -338,11 +818,200 @@ mod tests {
         assert_eq!(k, 0.00000000000007427392034742297);
     }
 
+    #[test]
+    fn math_trading_function_put() {
+        // x = y at the symmetric point, so swapping their roles keeps the invariant at zero.
+        let curve = NormalCurve {
+            variant: CurveVariant::Put,
+            ..CURVE
+        };
+        let k = curve.trading_function_variant();
+        assert!(k.abs() < 1e-12);
+        assert!((k - CURVE.trading_function_floating()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn math_put_approximations_invert() {
+        let curve = NormalCurve {
+            variant: CurveVariant::Put,
+            ..CURVE
+        };
+        assert!((curve.approximate_y_given_x_put() - curve.reserve_y_per_wad).abs() < 1e-9);
+        assert!((curve.approximate_x_given_y_put() - curve.reserve_x_per_wad).abs() < 1e-9);
+    }
+
+    #[test]
+    fn math_put_root_solves_put_invariant() {
+        let curve = NormalCurve {
+            variant: CurveVariant::Put,
+            ..CURVE
+        };
+        let offset = InvariantOffset::default();
+        let amount_out = curve.approximate_amount_out(true, 0.1).unwrap();
+
+        let mut swapped = curve.clone();
+        swapped.reserve_x_per_wad += 0.1;
+        swapped.reserve_y_per_wad -= amount_out;
+        assert!((swapped.trading_function_put() - offset.target(curve.invariant_f)).abs() < 1e-6);
+    }
+
     #[test]
     fn math_approximate_amount_out() {
         let amount_in = 0.1;
         let sell_asset = true;
-        let amount_out = CURVE.clone().approximate_amount_out(sell_asset, amount_in);
+        let amount_out = CURVE
+            .clone()
+            .approximate_amount_out(sell_asset, amount_in)
+            .unwrap();
         assert!(amount_out < 1.0); // price should go down...
     }
+
+    #[test]
+    fn math_approximate_amount_in_round_trips() {
+        let amount_in = 0.1;
+        for sell_asset in [true, false] {
+            let amount_out = CURVE.approximate_amount_out(sell_asset, amount_in).unwrap();
+            let round_trip = CURVE.approximate_amount_in(sell_asset, amount_out).unwrap();
+            // both directions solve for the same targeted invariant.
+            assert!(
+                (round_trip - amount_in).abs() < 1e-8,
+                "sell_asset: {}, round trip: {}",
+                sell_asset,
+                round_trip
+            );
+        }
+    }
+
+    #[test]
+    fn math_root_near_reserve_edge_is_found() {
+        // x reserve of ~0.99, so the unclamped search bounds reach past 1.
+        let curve = NormalCurve::new(0.0, 0.0, 1.0, 1.0, SECONDS_PER_YEAR, 0.0);
+        let (x, y) = curve.reserves_given_price(0.0593);
+        assert!((x - 0.99).abs() < 1e-3);
+        let curve = NormalCurve::new(x, y, 1.0, 1.0, SECONDS_PER_YEAR, 0.0);
+
+        let (lower, upper) = search_bounds(x, 1.0);
+        assert!(lower < x && upper < 1.0);
+
+        let offset = InvariantOffset::default();
+        let root = curve.approximate_other_reserve(false, y, offset).unwrap();
+        assert!(root > lower && root < upper);
+        assert!(curve.find_root_swapping_y(root, offset).abs() < 1e-6);
+    }
+
+    #[test]
+    fn math_newton_raphson_matches_bisection() {
+        let curve = NormalCurve {
+            reserve_x_per_wad: CURVE.reserve_x_per_wad + 0.1,
+            ..CURVE
+        };
+        let offset = InvariantOffset::default();
+        let fx = |y| curve.find_root_swapping_x(y, offset);
+
+        let approximated = curve.approximate_y_given_x_floating();
+        let bisected =
+            bisection::Bisection::new(approximated * 0.9, approximated * 1.1, 1e-9, 1000.0)
+                .bisection(fx)
+                .unwrap();
+        let newton = bisection::NewtonRaphson::new(approximated, 1e-12, 100.0)
+            .newton_raphson(fx)
+            .unwrap();
+
+        assert!((newton - bisected).abs() < 1e-8);
+        assert!(fx(newton).abs() <= fx(bisected).abs());
+    }
+
+    #[test]
+    fn math_smaller_invariant_epsilon_is_closer_to_ideal() {
+        let amount_in = 0.1;
+
+        // ideal output lands exactly on the current invariant.
+        let ideal = CURVE.reserve_y_per_wad - CURVE.y_equals(CURVE.reserve_x_per_wad + amount_in);
+
+        let default_out = CURVE.approximate_amount_out(true, amount_in).unwrap();
+        let precise_out = CURVE
+            .approximate_amount_out_with_offset(
+                true,
+                amount_in,
+                InvariantOffset {
+                    epsilon: 1e-10,
+                    sign: InvariantSign::Above,
+                },
+            )
+            .unwrap();
+
+        assert!((precise_out - ideal).abs() < (default_out - ideal).abs());
+    }
+
+    #[test]
+    fn math_solidity_input_round_trip() {
+        let curve = NormalCurve {
+            invariant_f: -0.000123,
+            ..CURVE
+        };
+        let round_trip = NormalCurve::from_solidity_input(&curve.to_solidity_input());
+
+        assert!((round_trip.reserve_x_per_wad - curve.reserve_x_per_wad).abs() < 1e-12);
+        assert!((round_trip.reserve_y_per_wad - curve.reserve_y_per_wad).abs() < 1e-12);
+        assert!((round_trip.strike_price_f - curve.strike_price_f).abs() < 1e-12);
+        assert!((round_trip.std_dev_f - curve.std_dev_f).abs() < 1e-12);
+        assert_eq!(round_trip.time_remaining_sec, curve.time_remaining_sec);
+        assert!((round_trip.invariant_f - curve.invariant_f).abs() < 1e-12);
+    }
+
+    #[test]
+    fn math_solve_params_for_delta() {
+        let price = 1.5;
+        let target_delta = 0.3;
+        let (strike, vol) = CURVE.solve_params_for_delta(target_delta, price).unwrap();
+
+        let mut curve = CURVE.clone();
+        curve.strike_price_f = strike;
+        curve.std_dev_f = vol;
+
+        assert!((curve.delta(price) - target_delta).abs() < 1e-9);
+    }
+
+    #[test]
+    fn math_domain_edge_is_clamped() {
+        let mut curve = CURVE.clone();
+        curve.reserve_x_per_wad = 0.0;
+
+        assert!(curve.is_at_domain_edge());
+        assert!(curve.approximate_y_given_x_floating().is_finite());
+        assert!(curve.trading_function_floating().is_finite());
+        assert!(!CURVE.is_at_domain_edge());
+    }
+
+    #[test]
+    fn math_break_even_steps() {
+        let mut config = SimConfig::default();
+        config.process.num_steps = 1000;
+
+        // high fees and low volatility catch up to the impermanent loss.
+        let high_fee = break_even_steps(&config, 0.01, 100);
+        assert!(high_fee.is_some());
+        assert!(high_fee.unwrap().is_finite());
+
+        // no fees never catch up.
+        assert_eq!(break_even_steps(&config, 0.01, 0), None);
+    }
+
+    #[test]
+    fn math_create_pool_reserves_match_actor() {
+        let config = SimConfig::default();
+        let mut manager = arbiter::manager::SimulationManager::new();
+        crate::setup::run(&mut manager, &config).unwrap();
+
+        let expected = crate::setup::build_create_pool_call(&manager, &config).unwrap();
+        let (reserve_x, reserve_y) = compute_create_pool_reserves(&config);
+
+        let tolerance = 1e-6;
+        assert!(
+            (wad_to_float(reserve_x) - wad_to_float(expected.reserve_x_per_wad)).abs() < tolerance
+        );
+        assert!(
+            (wad_to_float(reserve_y) - wad_to_float(expected.reserve_y_per_wad)).abs() < tolerance
+        );
+    }
 }
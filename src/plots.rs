@@ -2,10 +2,63 @@
 use polars::prelude::*;
 use visualize::{design::*, plot::*};
 
+use super::math::NormalCurve;
+use super::raw_data::{profit_rates, RawData};
+use super::spreadsheetorizer::Spreadsheet;
+
+/// Columns of the simulation data that the plots read.
+pub static REQUIRED_COLUMNS: [&str; 6] = [
+    "reported_price",
+    "ref_price",
+    "pvf",
+    "arb_pvf",
+    "reserves_x",
+    "reserves_y",
+];
+
+/// Errors from loading the simulation data to plot.
+#[derive(Debug, PartialEq)]
+pub enum PlotError {
+    /// A column the plots read is missing from the data.
+    MissingColumn(String),
+}
+
+impl std::fmt::Display for PlotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlotError::MissingColumn(column) => {
+                write!(
+                    f,
+                    "plots.rs: data is missing the required column `{}`",
+                    column
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PlotError {}
+
+/// Destination of the curves and axes of each plot.
+pub trait PlotSink {
+    /// Draws the curves on the axes, titled `title`. `path` is the file the plot is written to, if the sink writes files.
+    fn draw(&self, curves: Vec<Curve>, axes: Axes, title: String, display: Display, path: String);
+}
+
+/// Writes each plot as an html file to its path.
+pub struct HtmlSink;
+
+impl PlotSink for HtmlSink {
+    fn draw(&self, curves: Vec<Curve>, axes: Axes, title: String, display: Display, path: String) {
+        transparent_plot(Some(curves), None, axes, title, display, Some(path));
+    }
+}
+
 /// Uses a Plot Display and DataFrame (i.e. csv) to make plots of the simulation data.
 pub struct Plot {
     display: Display,
     data: DataFrame,
+    sink: Box<dyn PlotSink>,
 }
 
 /// Implements utilites for plotting the csv data output from simulations.
@@ -13,16 +66,52 @@ pub struct Plot {
 impl Plot {
     /// constructor
     pub fn new(display: Display, data: DataFrame) -> Self {
-        Self { display, data }
+        Self {
+            display,
+            data,
+            sink: Box::new(HtmlSink),
+        }
+    }
+
+    /// Draws the plots to `sink` instead of writing html files.
+    pub fn with_sink(mut self, sink: Box<dyn PlotSink>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    /// Builds the plot data from the raw data of the pool `pool_id` in memory,
+    /// skipping the lossy round trip through a csv file.
+    /// Errors if a series of the pool was never recorded.
+    pub fn from_raw_data(
+        raw_data: &RawData,
+        pool_id: u64,
+        display: Display,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::new(display, raw_data.to_spreadsheet(pool_id)?))
     }
 
     /// Loads a csv file from the given path.
+    /// Errors if the csv is missing any of the required columns.
     pub fn load_from_path(
         display: Display,
         path: &str,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let data = CsvReader::from_path(path)?.finish()?;
-        Ok(Self::new(display, data))
+        let plot = Self::new(display, data);
+        plot.validate_schema()?;
+        Ok(plot)
+    }
+
+    /// Checks the data has every column the plots read, so a renamed column fails up front.
+    pub fn validate_schema(&self) -> Result<(), PlotError> {
+        let columns = self.data.get_column_names();
+        for required in REQUIRED_COLUMNS.iter() {
+            if !columns.contains(required) {
+                return Err(PlotError::MissingColumn(required.to_string()));
+            }
+        }
+
+        Ok(())
     }
 
     pub fn prices(&self) -> Vec<Series> {
@@ -33,6 +122,14 @@ impl Plot {
         vec![reported_price.clone(), ref_price.clone()]
     }
 
+    pub fn reserves(&self) -> Vec<Series> {
+        // gets the x and y reserves per liquidity
+        let reserves_x = self.data.column("reserves_x").unwrap();
+        let reserves_y = self.data.column("reserves_y").unwrap();
+
+        vec![reserves_x.clone(), reserves_y.clone()]
+    }
+
     pub fn pvfs(&self) -> Vec<Series> {
         let pvf = self.data.column("pvf").unwrap();
         let arb_pvf = self.data.column("arb_pvf").unwrap();
@@ -84,6 +181,41 @@ impl Plot {
         curves
     }
 
+    /// Gets the trading function curve of `base` at each of the `strikes`, colored with the multi-color palette.
+    /// # Arguments
+    /// * `base` - Curve whose parameters, other than the strike, are shared by each curve.
+    /// * `strikes` - Strike price of each curve.
+    pub fn strike_curves(&self, base: &NormalCurve, strikes: &[f64]) -> Vec<Curve> {
+        let series = strikes
+            .iter()
+            .map(|strike| {
+                let mut curve = base.clone();
+                curve.strike_price_f = *strike;
+                let (x, y): (Vec<f64>, Vec<f64>) =
+                    curve.get_trading_function_coordinates().into_iter().unzip();
+                (Series::new("x", x), Series::new("y", y))
+            })
+            .collect::<Vec<(Series, Series)>>();
+
+        let mut curves = self.make_curves("strike", series);
+        for (curve, strike) in curves.iter_mut().zip(strikes.iter()) {
+            curve.name = Some(format!("strike {}", strike));
+        }
+
+        curves
+    }
+
+    /// Overlays the trading function curves of `base` at each of the `strikes` on one plot.
+    pub fn strike_comparison_plot(&self, base: &NormalCurve, strikes: &[f64]) {
+        let curves = self.strike_curves(base, strikes);
+        self.plot(
+            "./out_data",
+            "strike_comparison",
+            "strike comparison",
+            curves,
+        );
+    }
+
     /// Plots each line of (x,y) coordinates.
     /// # Arguments
     /// * `directory` - The directory to save the plot to. It should exist.
@@ -121,17 +253,12 @@ impl Plot {
                 ),
             };
 
-            transparent_plot(
-                Some(curves),
-                None,
+            self.sink.draw(
+                curves,
                 axes,
                 title.to_string(),
                 self.display.clone(),
-                Some(format!(
-                    "{}/{}.html",
-                    directory.to_string(),
-                    file.to_string()
-                )),
+                format!("{}/{}.html", directory.to_string(), file.to_string()),
             );
         } else {
             panic!("no x coordinates found");
@@ -142,30 +269,48 @@ impl Plot {
     /// # Arguments
     /// * `y_coords_vec` - For each line, a series a y coordinates. Each element in the root vector should have the same length.
     pub fn stacked_line_plot(&self, y_coords_vec: Vec<Vec<f64>>, title: &str) {
-        let length = y_coords_vec[0].len();
+        let lines = y_coords_vec
+            .into_iter()
+            .enumerate()
+            .map(|(i, y_coordinates)| {
+                let name = match i {
+                    0 => "spot",
+                    _ => "ref",
+                };
+                let color = match i {
+                    0 => Color::Purple,
+                    1 => Color::Blue,
+                    2 => Color::Green,
+                    _ => Color::Black,
+                };
+                (name.to_string(), color, y_coordinates)
+            })
+            .collect();
+
+        self.stacked_named_line_plot(lines, title);
+    }
+
+    /// Makes a line plot for each given series of y coordinates, with its own name and color.
+    /// # Arguments
+    /// * `lines` - For each line, its name, color, and y coordinates. Each line should have the same length.
+    pub fn stacked_named_line_plot(&self, lines: Vec<(String, Color, Vec<f64>)>, title: &str) {
+        let length = lines[0].2.len();
         // Equally spaced x coordinates.
         let x_coordinates =
             itertools_num::linspace(0.0, length as f64, length).collect::<Vec<f64>>();
 
-        let names = vec!["spot".to_string(), "ref".to_string()];
         // get a curve for each y coordinate vector
-        let curves = y_coords_vec
-            .iter()
-            .enumerate()
-            .map(|(i, y_coordinates)| Curve {
+        let curves = lines
+            .into_iter()
+            .map(|(name, color, y_coordinates)| Curve {
                 x_coordinates: x_coordinates.clone(),
-                y_coordinates: y_coordinates.clone(),
+                y_coordinates,
                 design: CurveDesign {
-                    color: match i {
-                        0 => Color::Purple,
-                        1 => Color::Blue,
-                        2 => Color::Green,
-                        _ => Color::Black,
-                    },
+                    color,
                     color_slot: 1,
                     style: Style::Lines(LineEmphasis::Light),
                 },
-                name: Some(format!("{}", names[i])),
+                name: Some(name),
             })
             .collect::<Vec<Curve>>();
 
@@ -200,8 +345,30 @@ impl Plot {
     }
 
     /// Plots the x and y reserves of a given pool data series on two lines on the same graph.
+    /// A reserve that is zero on every step is skipped, and nothing is plotted if both are.
     pub fn stacked_reserves_plot(&self) {
-        todo!()
+        let reserves = self.reserves();
+
+        let lines = [("x", Color::Purple), ("y", Color::Blue)]
+            .into_iter()
+            .zip(reserves.iter())
+            .map(|((name, color), series)| {
+                let y_coordinates = series
+                    .f64()
+                    .expect("error converting reserves to f64")
+                    .into_iter()
+                    .filter_map(|opt_f| opt_f)
+                    .collect::<Vec<f64>>();
+                (name.to_string(), color, y_coordinates)
+            })
+            .filter(|(_, _, y_coordinates)| y_coordinates.iter().any(|y| *y != 0.0))
+            .collect::<Vec<(String, Color, Vec<f64>)>>();
+
+        if lines.is_empty() {
+            return;
+        }
+
+        self.stacked_named_line_plot(lines, "reserves");
     }
 
     /// Plots the LP potfolio value and the arbitrageur's portfolio value on two lines on the same graph.
@@ -247,6 +414,123 @@ impl Plot {
         );
     }
 
+    /// Gets a float column, `None` if the data doesn't have it, e.g. a csv from before it was recorded.
+    fn optional_column(&self, name: &str) -> Option<Vec<f64>> {
+        Some(
+            self.data
+                .column(name)
+                .ok()?
+                .f64()
+                .ok()?
+                .into_iter()
+                .filter_map(|opt_f| opt_f)
+                .collect(),
+        )
+    }
+
+    /// Plots the arbitrageur's profit per second at each step, see `raw_data::profit_rates`.
+    /// Nothing is plotted if the data has no profit or step columns.
+    pub fn profit_rate_plot(&self, seconds_per_step: f64) {
+        let steps = self
+            .data
+            .column("step")
+            .ok()
+            .and_then(|steps| steps.cast(&DataType::UInt64).ok())
+            .map(|steps| {
+                steps
+                    .u64()
+                    .unwrap()
+                    .into_no_null_iter()
+                    .collect::<Vec<u64>>()
+            });
+        let (profits, steps) = match (self.optional_column("arb_profit"), steps) {
+            (Some(profits), Some(steps)) if !profits.is_empty() => (profits, steps),
+            _ => return,
+        };
+
+        self.stacked_named_line_plot(
+            vec![(
+                "profit rate".to_string(),
+                Color::Purple,
+                profit_rates(&profits, &steps, seconds_per_step),
+            )],
+            "profit_rate",
+        );
+    }
+
+    /// Plots the LP's divergence loss, its portfolio value minus holding the reserves it was allocated.
+    /// Nothing is plotted if the data has no divergence loss.
+    pub fn divergence_loss_plot(&self) {
+        let divergence_loss = match self.optional_column("divergence_loss") {
+            Some(divergence_loss) if !divergence_loss.is_empty() => divergence_loss,
+            _ => return,
+        };
+
+        self.stacked_named_line_plot(
+            vec![(
+                "divergence loss".to_string(),
+                Color::Purple,
+                divergence_loss,
+            )],
+            "divergence_loss",
+        );
+    }
+
+    /// Plots the pool's accrued fee revenue and the size of its divergence loss on two lines on the same graph.
+    /// Nothing is plotted if the data has no fee revenue or divergence loss.
+    pub fn fee_revenue_plot(&self) {
+        let (fee_revenue, divergence_loss) = match (
+            self.optional_column("fee_revenue"),
+            self.optional_column("divergence_loss"),
+        ) {
+            (Some(f), Some(d)) if !f.is_empty() => (f, d),
+            _ => return,
+        };
+
+        // The loss is negative when the LP is behind, so its size is compared to the fees.
+        let divergence_loss = divergence_loss
+            .iter()
+            .map(|loss| -loss)
+            .collect::<Vec<f64>>();
+
+        self.stacked_named_line_plot(
+            vec![
+                ("fee revenue".to_string(), Color::Purple, fee_revenue),
+                ("divergence loss".to_string(), Color::Blue, divergence_loss),
+            ],
+            "fee_revenue",
+        );
+    }
+
+    /// Plots the volume swapped on the pool in each step, in units of the quote token.
+    /// Nothing is plotted if the data has no volume.
+    pub fn portfolio_volume_plot(&self) {
+        let volume = match self.optional_column("volume") {
+            Some(volume) if !volume.is_empty() => volume,
+            _ => return,
+        };
+
+        self.stacked_named_line_plot(vec![("volume".to_string(), Color::Green, volume)], "volume");
+    }
+
+    /// Plots the running sum of the volume swapped on the pool, in units of the quote token.
+    /// Nothing is plotted if the data has no volume.
+    pub fn portfolio_volume_cumulative_plot(&self) {
+        let volume = match self.optional_column("volume") {
+            Some(volume) if !volume.is_empty() => volume,
+            _ => return,
+        };
+
+        self.stacked_named_line_plot(
+            vec![(
+                "cumulative volume".to_string(),
+                Color::Green,
+                cumulative_sum(&volume),
+            )],
+            "volume_cumulative",
+        );
+    }
+
     pub fn arbitrageur_pvf_plot(&self) {
         // get the LP pvf and arber pvf
         let pvfs = self.pvfs();
@@ -265,6 +549,17 @@ impl Plot {
     }
 }
 
+/// Running sum of the values.
+pub fn cumulative_sum(values: &[f64]) -> Vec<f64> {
+    values
+        .iter()
+        .scan(0.0, |sum, value| {
+            *sum += value;
+            Some(*sum)
+        })
+        .collect()
+}
+
 /// Gets the minimum and maximum values from a list of coordinates.
 pub fn get_coordinate_bounds(coords_list: Vec<Vec<f64>>) -> (f64, f64) {
     let flat = coords_list
@@ -284,3 +579,141 @@ pub fn get_coordinate_bounds(coords_list: Vec<Vec<f64>>) -> (f64, f64) {
 
     (*min, *max)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    /// Captures the curves and axes of each plot in memory.
+    #[derive(Clone, Default)]
+    struct MemorySink {
+        plots: Rc<RefCell<Vec<(String, Vec<Curve>, Axes)>>>,
+    }
+
+    impl PlotSink for MemorySink {
+        fn draw(&self, curves: Vec<Curve>, axes: Axes, title: String, _: Display, _: String) {
+            self.plots.borrow_mut().push((title, curves, axes));
+        }
+    }
+
+    #[test]
+    fn strike_curves_are_distinct() {
+        let display = Display {
+            transparent: false,
+            mode: DisplayMode::Light,
+            show: false,
+        };
+        let plot = Plot::new(display, DataFrame::default());
+        let base = NormalCurve::new(0.5, 0.5, 1.0, 0.1, crate::math::SECONDS_PER_YEAR, 0.0);
+
+        let curves = plot.strike_curves(&base, &[0.5, 1.0, 2.0]);
+        assert_eq!(curves.len(), 3);
+
+        // Same x coordinate, different y on each curve.
+        let i = 50;
+        assert_eq!(curves[0].x_coordinates[i], curves[1].x_coordinates[i]);
+        assert_eq!(curves[1].x_coordinates[i], curves[2].x_coordinates[i]);
+        assert_ne!(curves[0].y_coordinates[i], curves[1].y_coordinates[i]);
+        assert_ne!(curves[1].y_coordinates[i], curves[2].y_coordinates[i]);
+        assert_ne!(curves[0].y_coordinates[i], curves[2].y_coordinates[i]);
+    }
+
+    #[tokio::test]
+    async fn price_plot_from_raw_data() {
+        let config = crate::config::SimConfig::default();
+        let (raw_data, pool_id) = crate::sim::run(&config).await.unwrap();
+
+        let display = Display {
+            transparent: true,
+            mode: DisplayMode::Dark,
+            show: false,
+        };
+        let plot = Plot::from_raw_data(&raw_data, pool_id, display).unwrap();
+        plot.validate_schema().unwrap();
+        assert_eq!(
+            plot.prices()[0].len(),
+            raw_data.get_reported_price(pool_id).unwrap().len()
+        );
+
+        std::fs::create_dir_all(crate::sim::OUTPUT_DIRECTORY).unwrap();
+        plot.stacked_price_plot();
+    }
+
+    #[test]
+    fn price_plot_draws_spot_and_ref_series() {
+        let data = df!(
+            "reported_price" => [1.0, 1.1, 1.2],
+            "ref_price" => [1.0, 1.05, 1.25],
+        )
+        .unwrap();
+        let display = Display {
+            transparent: false,
+            mode: DisplayMode::Light,
+            show: false,
+        };
+        let sink = MemorySink::default();
+        let plot = Plot::new(display, data).with_sink(Box::new(sink.clone()));
+
+        plot.stacked_price_plot();
+
+        let plots = sink.plots.borrow();
+        assert_eq!(plots.len(), 1);
+        let (title, curves, axes) = &plots[0];
+        assert_eq!(title, "prices");
+        assert_eq!(curves.len(), 2);
+        assert_eq!(curves[0].name.as_deref(), Some("spot"));
+        assert_eq!(curves[1].name.as_deref(), Some("ref"));
+        assert_eq!(curves[0].y_coordinates, vec![1.0, 1.1, 1.2]);
+        assert_eq!(curves[1].y_coordinates, vec![1.0, 1.05, 1.25]);
+        assert_eq!(curves[0].x_coordinates.len(), 3);
+        assert_eq!(axes.bounds.1, vec![1.0, 1.25]);
+    }
+
+    #[test]
+    fn cumulative_volume_is_running_sum() {
+        let data = df!(
+            "volume" => [0.0, 1.5, 0.0, 2.0],
+        )
+        .unwrap();
+        let display = Display {
+            transparent: false,
+            mode: DisplayMode::Light,
+            show: false,
+        };
+        let sink = MemorySink::default();
+        let plot = Plot::new(display, data).with_sink(Box::new(sink.clone()));
+
+        plot.portfolio_volume_plot();
+        plot.portfolio_volume_cumulative_plot();
+
+        let plots = sink.plots.borrow();
+        assert_eq!(plots.len(), 2);
+        assert_eq!(plots[0].0, "volume");
+        assert_eq!(plots[0].1[0].y_coordinates, vec![0.0, 1.5, 0.0, 2.0]);
+        assert_eq!(plots[1].0, "volume_cumulative");
+        assert_eq!(plots[1].1[0].y_coordinates, vec![0.0, 1.5, 1.5, 3.5]);
+    }
+
+    #[test]
+    fn csv_missing_pvf_fails_schema_validation() {
+        let path = std::env::temp_dir().join("proto_sim_missing_pvf.csv");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "reported_price,ref_price,arb_pvf\n1.0,1.0,100.0\n").unwrap();
+
+        let display = Display {
+            transparent: false,
+            mode: DisplayMode::Light,
+            show: false,
+        };
+        let err = match Plot::load_from_path(display, path) {
+            Ok(_) => panic!("loaded a csv without the pvf column"),
+            Err(err) => err,
+        };
+
+        assert_eq!(
+            err.downcast_ref::<PlotError>(),
+            Some(&PlotError::MissingColumn("pvf".to_string()))
+        );
+    }
+}
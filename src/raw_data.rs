@@ -3,11 +3,15 @@ use ethers::{
     prelude::{I256, U256},
     utils::parse_ether,
 };
+use serde::{Deserialize, Serialize};
 /// Implements the storage of raw simulation data.
 use std::collections::HashMap;
 
 use bindings::{i_portfolio::*, normal_strategy::ConfigsReturn};
 
+use super::common::SECONDS_PER_YEAR;
+use super::task::PriceRegime;
+
 /// # RawData
 /// ==================
 /// This is the storage of raw simulation data. All direct
@@ -19,18 +23,92 @@ use bindings::{i_portfolio::*, normal_strategy::ConfigsReturn};
 /// * arbitrageur_balances_wad - Stores the arbitrageur's balances in wad format.
 /// * exchange_prices_wad - Stores the series exchange prices in wad format, indexed by the pool id.
 /// * pools - Stores the series pool data, indexed by the pool id.
+/// * steps - Stores the number of steps each pool has been logged on, including unrecorded steps.
+/// * step_indices - Stores the step index of each recorded row, indexed by the pool id.
+/// * fee_revenue_wad - Stores the fees the pool has taken from the arbitrageur's swaps so far, in wad units of the quote token, indexed by the pool id.
+/// * token_decimals - Stores the decimals of each token the arbitrageur's balances are in, indexed by the token key. 18 if not stored.
 pub struct RawData {
     pub keys: Vec<u64>,
+    pub steps: HashMap<u64, u64>,
+    pub step_indices: HashMap<u64, Vec<u64>>,
     pub arbitrageur_balances_wad: HashMap<String, Vec<U256>>,
     pub exchange_prices_wad: HashMap<u64, Vec<U256>>,
+    pub fee_revenue_wad: HashMap<u64, Vec<U256>>,
     pub pools: HashMap<u64, PoolSeries>,
     pub derived_data: HashMap<u64, DerivedData>,
     pub configs: HashMap<u64, PoolConfig>,
+    pub token_decimals: HashMap<String, u8>,
+}
+
+/// Errors from reading the recorded series.
+#[derive(Debug, PartialEq)]
+pub enum RawDataError {
+    /// Nothing was recorded in the series for the key, e.g. a pool id that was never allocated or a misspelled token key.
+    MissingKey { series: &'static str, key: String },
+}
+
+impl RawDataError {
+    fn missing(series: &'static str, key: impl ToString) -> Self {
+        RawDataError::MissingKey {
+            series,
+            key: key.to_string(),
+        }
+    }
 }
 
+impl std::fmt::Display for RawDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RawDataError::MissingKey { series, key } => {
+                write!(
+                    f,
+                    "raw_data.rs: no `{}` series was recorded for the key `{}`",
+                    series, key
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RawDataError {}
+
+/// # DerivedData
+/// Stores the series computed from the raw EVM data while the sim runs.
+///
+/// # Fields
+/// * `arbitrageur_portfolio_value` - Value of the arbitrageur's tokens at the reference price, in units of the quote token.
+/// * `pool_portfolio_value` - Value of the pool's reserves at the reference price.
+/// * `divergence_loss` - Pool's portfolio value minus the value of holding the reserves it was allocated, at the same price.
+/// * `pool_portfolio_value_theoretical` - Value an ideally arbitraged pool with the same liquidity has at the reference price.
+/// * `arbitrageur_fee_basis_points` - Fee the pool charges the arbitrageur, which sets the width of its no-arb band.
+/// * `arbitrageur_gas_used` - Gas used by the arbitrageur's transactions in the step.
+/// * `arbitrageur_gas_cost` - Cost of the gas used in the step, in units of the quote token.
+/// * `domain_edge_hits` - 1 if the pool's reserves were at the edge of the trading function's domain, else 0.
+/// * `reserves_valid` - 1 if the pool's reserves were nonzero and within the trading function's domain, else 0.
+/// * `swap_retries` - Failed swap attempts before the arbitrageur's swap went through, zero if it did not swap.
+/// * `arb_profit` - Change in the arbitrageur's portfolio value since the last recorded step, zero on the first.
+/// * `liquidity_too_low` - 1 if the arbitrageur skipped the step because the pool's liquidity was below the minimum, else 0.
+/// * `unhedged` - 1 if the arbitrageur's exchange trade failed after its swap, leaving its position unhedged, else 0.
+/// * `pending_exposure` - Value of the arbitrageur's deferred exchange trades' inputs at the reference price, in units of the quote token.
+/// * `volume` - Input of the arbitrageur's swap on the pool at the reference price, in units of the quote token, zero if it did not swap.
+/// * `price_regimes` - Where the reference price was relative to the arbitrageur's no-arb band around the reported price.
 pub struct DerivedData {
     pub arbitrageur_portfolio_value: Vec<f64>,
     pub pool_portfolio_value: Vec<f64>,
+    pub pool_portfolio_value_theoretical: Vec<f64>,
+    pub divergence_loss: Vec<f64>,
+    pub arbitrageur_fee_basis_points: Vec<f64>,
+    pub arbitrageur_gas_used: Vec<f64>,
+    pub arbitrageur_gas_cost: Vec<f64>,
+    pub domain_edge_hits: Vec<f64>,
+    pub reserves_valid: Vec<f64>,
+    pub swap_retries: Vec<f64>,
+    pub arb_profit: Vec<f64>,
+    pub liquidity_too_low: Vec<f64>,
+    pub unhedged: Vec<f64>,
+    pub pending_exposure: Vec<f64>,
+    pub volume: Vec<f64>,
+    pub price_regimes: Vec<PriceRegime>,
 }
 
 impl Default for DerivedData {
@@ -38,10 +116,38 @@ impl Default for DerivedData {
         Self {
             arbitrageur_portfolio_value: Vec::new(),
             pool_portfolio_value: Vec::new(),
+            pool_portfolio_value_theoretical: Vec::new(),
+            divergence_loss: Vec::new(),
+            arbitrageur_fee_basis_points: Vec::new(),
+            arbitrageur_gas_used: Vec::new(),
+            arbitrageur_gas_cost: Vec::new(),
+            domain_edge_hits: Vec::new(),
+            reserves_valid: Vec::new(),
+            swap_retries: Vec::new(),
+            arb_profit: Vec::new(),
+            liquidity_too_low: Vec::new(),
+            unhedged: Vec::new(),
+            pending_exposure: Vec::new(),
+            volume: Vec::new(),
+            price_regimes: Vec::new(),
         }
     }
 }
 
+/// # LpPerformance
+/// Standard performance metrics of the LP's portfolio value.
+///
+/// # Fields
+/// * `annualized_return` - Compounded return of the portfolio value per year. (f64)
+/// * `annualized_vol` - Standard deviation of the per step log returns, scaled to a year. (f64)
+/// * `sharpe` - Annualized return over annualized volatility, with a zero risk free rate. Zero if there is no volatility. (f64)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LpPerformance {
+    pub annualized_return: f64,
+    pub annualized_vol: f64,
+    pub sharpe: f64,
+}
+
 /// Aliased type from the actual config stored in the pool's strategy contract.
 /// source: normal_strategy.rs
 pub type PoolConfig = ConfigsReturn;
@@ -53,7 +159,10 @@ pub type PoolConfig = ConfigsReturn;
 /// * `pool_data` - Return value from calling `pools(uint64 poolId)` on portfolio.
 /// * `reported_price_wad_sol` - Reported price of the pool, in wad format.
 /// * `invariant_wad_sol` - Invariant value of the pool, in wad format.
-/// * `portfolio_value_wad_sol` - Portfolio value function is the sum of the value of tokens, in wad format.
+/// * `portfolio_value_wad_sol` - Value of the pool's reserves in the quote token, in wad format, see `log::pool_portfolio_value_wad`.
+///
+/// # Notes
+/// - `DerivedData::pool_portfolio_value` is the same value converted to a float.
 pub struct PoolSeries {
     pub pool_data: Vec<PoolsReturn>,
     pub reported_price_wad_sol: Vec<U256>,
@@ -79,11 +188,15 @@ impl RawData {
     pub fn new() -> Self {
         RawData {
             keys: Vec::new(),
+            steps: HashMap::new(),
+            step_indices: HashMap::new(),
             arbitrageur_balances_wad: HashMap::new(),
             exchange_prices_wad: HashMap::new(),
+            fee_revenue_wad: HashMap::new(),
             pools: HashMap::new(),
             derived_data: HashMap::new(),
             configs: HashMap::new(),
+            token_decimals: HashMap::new(),
         }
     }
 
@@ -95,6 +208,144 @@ impl RawData {
         self.keys.push(key);
     }
 
+    /// Counts a logged step of the pool `key` and returns its index.
+    pub fn advance_step(&mut self, key: u64) -> u64 {
+        let steps = self.steps.entry(key).or_insert(0);
+        *steps += 1;
+        *steps - 1
+    }
+
+    /// Drops all but the last recorded row of the pool `key` and of the arbitrageur's balances.
+    /// Keeps the memory of a run bounded when each row is folded into running aggregates instead.
+    pub fn retain_last(&mut self, key: u64) {
+        self.retain_window(key, 1);
+    }
+
+    /// Drops all but the last `window` recorded rows of the pool `key` and of the arbitrageur's balances.
+    pub fn retain_window(&mut self, key: u64, window: usize) {
+        fn keep_last<T>(series: &mut Vec<T>, window: usize) {
+            let len = series.len();
+            if len > window {
+                series.drain(..len - window);
+            }
+        }
+
+        if let Some(step_indices) = self.step_indices.get_mut(&key) {
+            keep_last(step_indices, window);
+        }
+        for balances in self.arbitrageur_balances_wad.values_mut() {
+            keep_last(balances, window);
+        }
+        if let Some(prices) = self.exchange_prices_wad.get_mut(&key) {
+            keep_last(prices, window);
+        }
+        if let Some(fee_revenue) = self.fee_revenue_wad.get_mut(&key) {
+            keep_last(fee_revenue, window);
+        }
+        if let Some(series) = self.pools.get_mut(&key) {
+            keep_last(&mut series.pool_data, window);
+            keep_last(&mut series.reported_price_wad_sol, window);
+            keep_last(&mut series.invariant_wad_sol, window);
+            keep_last(&mut series.portfolio_value_wad_sol, window);
+        }
+        if let Some(derived_data) = self.derived_data.get_mut(&key) {
+            keep_last(&mut derived_data.arbitrageur_portfolio_value, window);
+            keep_last(&mut derived_data.pool_portfolio_value, window);
+            keep_last(&mut derived_data.pool_portfolio_value_theoretical, window);
+            keep_last(&mut derived_data.divergence_loss, window);
+            keep_last(&mut derived_data.arbitrageur_fee_basis_points, window);
+            keep_last(&mut derived_data.arbitrageur_gas_used, window);
+            keep_last(&mut derived_data.arbitrageur_gas_cost, window);
+            keep_last(&mut derived_data.domain_edge_hits, window);
+            keep_last(&mut derived_data.reserves_valid, window);
+            keep_last(&mut derived_data.swap_retries, window);
+            keep_last(&mut derived_data.arb_profit, window);
+            keep_last(&mut derived_data.liquidity_too_low, window);
+            keep_last(&mut derived_data.unhedged, window);
+            keep_last(&mut derived_data.pending_exposure, window);
+            keep_last(&mut derived_data.volume, window);
+            keep_last(&mut derived_data.price_regimes, window);
+        }
+    }
+
+    /// Copies the last recorded row of the pool `key` and of the arbitrageur's balances,
+    /// so a single row can be exported without converting every recorded row.
+    pub fn last_row(&self, key: u64) -> RawData {
+        fn last<T: Clone>(series: &[T]) -> Vec<T> {
+            series.last().cloned().into_iter().collect()
+        }
+
+        let mut row = RawData::new();
+        row.keys.push(key);
+        row.token_decimals = self.token_decimals.clone();
+        if let Some(steps) = self.steps.get(&key) {
+            row.steps.insert(key, *steps);
+        }
+        if let Some(step_indices) = self.step_indices.get(&key) {
+            row.step_indices.insert(key, last(step_indices));
+        }
+        for (token, balances) in self.arbitrageur_balances_wad.iter() {
+            row.arbitrageur_balances_wad
+                .insert(token.clone(), last(balances));
+        }
+        if let Some(prices) = self.exchange_prices_wad.get(&key) {
+            row.exchange_prices_wad.insert(key, last(prices));
+        }
+        if let Some(fee_revenue) = self.fee_revenue_wad.get(&key) {
+            row.fee_revenue_wad.insert(key, last(fee_revenue));
+        }
+        if let Some(series) = self.pools.get(&key) {
+            row.pools.insert(
+                key,
+                PoolSeries {
+                    pool_data: last(&series.pool_data),
+                    reported_price_wad_sol: last(&series.reported_price_wad_sol),
+                    invariant_wad_sol: last(&series.invariant_wad_sol),
+                    portfolio_value_wad_sol: last(&series.portfolio_value_wad_sol),
+                },
+            );
+        }
+        if let Some(derived_data) = self.derived_data.get(&key) {
+            row.derived_data.insert(
+                key,
+                DerivedData {
+                    arbitrageur_portfolio_value: last(&derived_data.arbitrageur_portfolio_value),
+                    pool_portfolio_value: last(&derived_data.pool_portfolio_value),
+                    pool_portfolio_value_theoretical: last(
+                        &derived_data.pool_portfolio_value_theoretical,
+                    ),
+                    divergence_loss: last(&derived_data.divergence_loss),
+                    arbitrageur_fee_basis_points: last(&derived_data.arbitrageur_fee_basis_points),
+                    arbitrageur_gas_used: last(&derived_data.arbitrageur_gas_used),
+                    arbitrageur_gas_cost: last(&derived_data.arbitrageur_gas_cost),
+                    domain_edge_hits: last(&derived_data.domain_edge_hits),
+                    reserves_valid: last(&derived_data.reserves_valid),
+                    swap_retries: last(&derived_data.swap_retries),
+                    arb_profit: last(&derived_data.arb_profit),
+                    liquidity_too_low: last(&derived_data.liquidity_too_low),
+                    unhedged: last(&derived_data.unhedged),
+                    pending_exposure: last(&derived_data.pending_exposure),
+                    volume: last(&derived_data.volume),
+                    price_regimes: last(&derived_data.price_regimes),
+                },
+            );
+        }
+
+        row
+    }
+
+    pub fn add_step_index(&mut self, key: u64, step: u64) {
+        self.step_indices
+            .entry(key)
+            .or_insert_with(Vec::new)
+            .push(step);
+    }
+
+    /// Sets the decimals of the token `key`, which the arbitrageur's balances of it are converted with.
+    pub fn add_token_decimals(&mut self, key: String, decimals: u8) {
+        self.token_decimals.insert(key, decimals);
+    }
+
     pub fn add_arbitrageur_balance(&mut self, key: String, balance: U256) {
         self.arbitrageur_balances_wad
             .entry(key)
@@ -109,6 +360,14 @@ impl RawData {
             .push(price);
     }
 
+    /// Records the pool's accrued fee revenue, the running total of the fees taken so far.
+    pub fn add_fee_revenue(&mut self, key: u64, fee_revenue: U256) {
+        self.fee_revenue_wad
+            .entry(key)
+            .or_insert_with(Vec::new)
+            .push(fee_revenue);
+    }
+
     pub fn add_pool_data(&mut self, key: u64, pool_data: PoolsReturn) {
         self.pools
             .entry(key)
@@ -125,20 +384,20 @@ impl RawData {
             .push(price);
     }
 
-    pub fn add_invariant(&mut self, key: u64, invariant: I256) {
+    pub fn add_portfolio_value(&mut self, key: u64, value: U256) {
         self.pools
             .entry(key)
             .or_insert_with(PoolSeries::default)
-            .invariant_wad_sol
-            .push(invariant);
+            .portfolio_value_wad_sol
+            .push(value);
     }
 
-    pub fn add_portfolio_value(&mut self, key: u64, value: U256) {
+    pub fn add_invariant(&mut self, key: u64, invariant: I256) {
         self.pools
             .entry(key)
             .or_insert_with(PoolSeries::default)
-            .portfolio_value_wad_sol
-            .push(value);
+            .invariant_wad_sol
+            .push(invariant);
     }
 
     pub fn add_arbitrageur_portfolio_value(&mut self, key: u64, value: f64) {
@@ -149,6 +408,14 @@ impl RawData {
             .push(value);
     }
 
+    pub fn add_arb_profit(&mut self, key: u64, profit: f64) {
+        self.derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default)
+            .arb_profit
+            .push(profit);
+    }
+
     pub fn add_pool_portfolio_value(&mut self, key: u64, value: f64) {
         self.derived_data
             .entry(key)
@@ -157,83 +424,483 @@ impl RawData {
             .push(value);
     }
 
-    pub fn get_arbitrageur_balance(&self, key: &str) -> Vec<U256> {
-        self.arbitrageur_balances_wad.get(key).unwrap().clone()
+    pub fn add_pool_portfolio_value_theoretical(&mut self, key: u64, value: f64) {
+        self.derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default)
+            .pool_portfolio_value_theoretical
+            .push(value);
+    }
+
+    pub fn add_divergence_loss(&mut self, key: u64, loss: f64) {
+        self.derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default)
+            .divergence_loss
+            .push(loss);
     }
 
-    pub fn get_exchange_price(&self, key: u64) -> Vec<U256> {
-        self.exchange_prices_wad.get(&key).unwrap().clone()
+    pub fn add_arbitrageur_fee_basis_points(&mut self, key: u64, fee_basis_points: u16) {
+        self.derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default)
+            .arbitrageur_fee_basis_points
+            .push(fee_basis_points as f64);
     }
 
-    pub fn get_pool_data(&self, key: u64) -> Vec<PoolsReturn> {
-        self.pools.get(&key).unwrap().pool_data.clone()
+    pub fn add_arbitrageur_gas(&mut self, key: u64, gas_used: u64, gas_cost: f64) {
+        let derived_data = self
+            .derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default);
+        derived_data.arbitrageur_gas_used.push(gas_used as f64);
+        derived_data.arbitrageur_gas_cost.push(gas_cost);
     }
 
-    pub fn get_pool_x_per_lq_float(&self, key: u64) -> Vec<f64> {
-        self.get_pool_data(key).map_x_per_lq().vec_wad_to_float()
+    pub fn add_domain_edge_hit(&mut self, key: u64, hit: bool) {
+        self.derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default)
+            .domain_edge_hits
+            .push(if hit { 1.0 } else { 0.0 });
     }
 
-    pub fn get_pool_y_per_lq_float(&self, key: u64) -> Vec<f64> {
-        self.get_pool_data(key).map_y_per_lq().vec_wad_to_float()
+    /// Records the retries the arbitrageur's swap took in the step, `None` if it did not swap.
+    pub fn add_swap_retries(&mut self, key: u64, retries: Option<usize>) {
+        self.derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default)
+            .swap_retries
+            .push(retries.unwrap_or(0) as f64);
     }
 
-    pub fn get_reported_price(&self, key: u64) -> Vec<U256> {
-        self.pools.get(&key).unwrap().reported_price_wad_sol.clone()
+    pub fn add_price_regime(&mut self, key: u64, regime: PriceRegime) {
+        self.derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default)
+            .price_regimes
+            .push(regime);
+    }
+
+    /// Records whether the arbitrageur skipped the step for the pool's liquidity being below the minimum.
+    pub fn add_liquidity_too_low(&mut self, key: u64, too_low: bool) {
+        self.derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default)
+            .liquidity_too_low
+            .push(if too_low { 1.0 } else { 0.0 });
+    }
+
+    /// Records whether the arbitrageur's exchange trade failed after its swap, leaving its position unhedged.
+    pub fn add_unhedged(&mut self, key: u64, unhedged: bool) {
+        self.derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default)
+            .unhedged
+            .push(if unhedged { 1.0 } else { 0.0 });
     }
 
-    pub fn get_invariant(&self, key: u64) -> Vec<I256> {
-        self.pools.get(&key).unwrap().invariant_wad_sol.clone()
+    /// Records the value of the arbitrageur's deferred exchange trades waiting to settle.
+    pub fn add_pending_exposure(&mut self, key: u64, exposure: f64) {
+        self.derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default)
+            .pending_exposure
+            .push(exposure);
     }
 
-    // note: portfolio value is translated to float in the data collection stage
-    // @kinrezc be careful about where you are converting data. in general,
-    // we store raw EVM data in the raw data and do the conversions outside.
-    pub fn get_portfolio_value(&self, key: u64) -> Vec<f64> {
+    /// Records the value of the arbitrageur's swap input on the pool in the step.
+    pub fn add_volume(&mut self, key: u64, volume: f64) {
         self.derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default)
+            .volume
+            .push(volume);
+    }
+
+    pub fn add_reserves_valid(&mut self, key: u64, valid: bool) {
+        self.derived_data
+            .entry(key)
+            .or_insert_with(DerivedData::default)
+            .reserves_valid
+            .push(if valid { 1.0 } else { 0.0 });
+    }
+
+    /// Series of the pool `key`, or an error naming the key if none was recorded.
+    fn pool(&self, key: u64) -> Result<&PoolSeries, RawDataError> {
+        self.pools
             .get(&key)
-            .unwrap()
-            .pool_portfolio_value
-            .clone()
+            .ok_or_else(|| RawDataError::missing("pools", key))
+    }
+
+    /// Derived series of the pool `key`, or an error naming the key if none was recorded.
+    fn derived(&self, key: u64) -> Result<&DerivedData, RawDataError> {
+        self.derived_data
+            .get(&key)
+            .ok_or_else(|| RawDataError::missing("derived_data", key))
+    }
+
+    pub fn get_arbitrageur_balance(&self, key: &str) -> Result<Vec<U256>, RawDataError> {
+        self.arbitrageur_balances_wad
+            .get(key)
+            .cloned()
+            .ok_or_else(|| RawDataError::missing("arbitrageur_balances_wad", key))
+    }
+
+    pub fn get_exchange_price(&self, key: u64) -> Result<Vec<U256>, RawDataError> {
+        self.exchange_prices_wad
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| RawDataError::missing("exchange_prices_wad", key))
+    }
+
+    pub fn get_fee_revenue(&self, key: u64) -> Result<Vec<U256>, RawDataError> {
+        self.fee_revenue_wad
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| RawDataError::missing("fee_revenue_wad", key))
+    }
+
+    pub fn get_step_index(&self, key: u64) -> Result<Vec<u64>, RawDataError> {
+        self.step_indices
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| RawDataError::missing("step_indices", key))
+    }
+
+    /// True if the reserves and reported price equal the last recorded ones of the pool `key`.
+    /// False if nothing has been recorded for the pool yet.
+    pub fn is_pool_unchanged(
+        &self,
+        key: u64,
+        pool_data: &PoolsReturn,
+        reported_price: U256,
+    ) -> bool {
+        let series = match self.pools.get(&key) {
+            Some(series) => series,
+            None => return false,
+        };
+
+        match (
+            series.pool_data.last(),
+            series.reported_price_wad_sol.last(),
+        ) {
+            (Some(last), Some(last_price)) => {
+                last.virtual_x == pool_data.virtual_x
+                    && last.virtual_y == pool_data.virtual_y
+                    && *last_price == reported_price
+            }
+            _ => false,
+        }
     }
 
-    pub fn get_arbitrageur_balance_float(&self, key: &str) -> Vec<f64> {
-        self.get_arbitrageur_balance(key).vec_wad_to_float()
+    pub fn get_pool_data(&self, key: u64) -> Result<Vec<PoolsReturn>, RawDataError> {
+        Ok(self.pool(key)?.pool_data.clone())
     }
 
-    pub fn get_exchange_price_float(&self, key: u64) -> Vec<f64> {
-        self.get_exchange_price(key).vec_wad_to_float()
+    pub fn get_pool_x_per_lq_float(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.get_pool_data(key)?.map_x_per_lq().vec_wad_to_float())
     }
 
-    pub fn get_reported_price_float(&self, key: u64) -> Vec<f64> {
-        self.get_reported_price(key).vec_wad_to_float()
+    pub fn get_pool_y_per_lq_float(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.get_pool_data(key)?.map_y_per_lq().vec_wad_to_float())
     }
 
-    pub fn get_invariant_float(&self, key: u64) -> Vec<f64> {
-        self.get_invariant(key).vec_wad_to_float()
+    /// Block timestamp of the pool's last update, in seconds, at each step.
+    /// Only moves if block time is advanced, which fee accrual and expiry depend on.
+    pub fn get_last_timestamp(&self, key: u64) -> Result<Vec<u64>, RawDataError> {
+        Ok(self
+            .get_pool_data(key)?
+            .iter()
+            .map(|pool| pool.last_timestamp as u64)
+            .collect())
     }
 
-    pub fn get_portfolio_value_float(&self, key: u64) -> Vec<f64> {
+    /// Total virtual x reserves of the pool, in absolute token units.
+    pub fn get_pool_x_total_float(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.get_pool_data(key)?.map_x_total().vec_wad_to_float())
+    }
+
+    /// Total virtual y reserves of the pool, in absolute token units.
+    pub fn get_pool_y_total_float(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.get_pool_data(key)?.map_y_total().vec_wad_to_float())
+    }
+
+    pub fn get_pool_liquidity_float(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.get_pool_data(key)?.map_liquidity().vec_wad_to_float())
+    }
+
+    pub fn get_reported_price(&self, key: u64) -> Result<Vec<U256>, RawDataError> {
+        Ok(self.pool(key)?.reported_price_wad_sol.clone())
+    }
+
+    pub fn get_invariant(&self, key: u64) -> Result<Vec<I256>, RawDataError> {
+        Ok(self.pool(key)?.invariant_wad_sol.clone())
+    }
+
+    pub fn get_portfolio_value_wad(&self, key: u64) -> Result<Vec<U256>, RawDataError> {
+        Ok(self.pool(key)?.portfolio_value_wad_sol.clone())
+    }
+
+    /// Value of the pool's total reserves in the quote token, `reserve_x * price_x + reserve_y`,
+    /// recorded by `log::run` at each step. The float conversion of `get_portfolio_value_wad`.
+    pub fn get_portfolio_value(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.derived(key)?.pool_portfolio_value.clone())
+    }
+
+    /// Arbitrageur's balances of the token `key`, converted from the token's decimals.
+    pub fn get_arbitrageur_balance_float(&self, key: &str) -> Result<Vec<f64>, RawDataError> {
+        let decimals = self.token_decimals.get(key).copied().unwrap_or(18);
+        Ok(self
+            .get_arbitrageur_balance(key)?
+            .into_iter()
+            .map(|balance| units_to_float(balance, decimals))
+            .collect())
+    }
+
+    pub fn get_exchange_price_float(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.get_exchange_price(key)?.vec_wad_to_float())
+    }
+
+    pub fn get_fee_revenue_float(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.get_fee_revenue(key)?.vec_wad_to_float())
+    }
+
+    pub fn get_reported_price_float(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.get_reported_price(key)?.vec_wad_to_float())
+    }
+
+    pub fn get_invariant_float(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.get_invariant(key)?.vec_wad_to_float())
+    }
+
+    /// Value an ideally arbitraged pool has at each step's reference price, see `math::fair_portfolio_value`.
+    pub fn get_portfolio_value_theoretical(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.derived(key)?.pool_portfolio_value_theoretical.clone())
+    }
+
+    /// LP's loss versus holding the reserves the pool was allocated at each step, negative if the LP is behind.
+    pub fn get_divergence_loss(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.derived(key)?.divergence_loss.clone())
+    }
+
+    /// Same as `get_portfolio_value`, which is already a float.
+    pub fn get_portfolio_value_float(&self, key: u64) -> Result<Vec<f64>, RawDataError> {
         self.get_portfolio_value(key)
     }
 
     /// Balance of arbitrageur's "token0", or x, tokens.
-    pub fn get_arber_reserve_x_float(&self) -> Vec<f64> {
+    pub fn get_arber_reserve_x_float(&self) -> Result<Vec<f64>, RawDataError> {
         // todo: fix token0 getter so we know its the right x token for a given pool...
         self.get_arbitrageur_balance_float("token0")
     }
 
     /// Balance of arbitrageur's "token1", or y, tokens.
-    pub fn get_arber_reserve_y_float(&self) -> Vec<f64> {
+    pub fn get_arber_reserve_y_float(&self) -> Result<Vec<f64>, RawDataError> {
         self.get_arbitrageur_balance_float("token1")
     }
 
+    /// Fee the pool charged the arbitrageur at each step, in basis points.
+    pub fn get_arbitrageur_fee_basis_points(&self, pool_id: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.derived(pool_id)?.arbitrageur_fee_basis_points.clone())
+    }
+
+    /// Gas used by the arbitrageur at each step.
+    pub fn get_arbitrageur_gas_used(&self, pool_id: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.derived(pool_id)?.arbitrageur_gas_used.clone())
+    }
+
+    /// Cost of the gas used by the arbitrageur at each step, in units of the quote token.
+    pub fn get_arbitrageur_gas_cost(&self, pool_id: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.derived(pool_id)?.arbitrageur_gas_cost.clone())
+    }
+
+    /// Failed swap attempts the arbitrageur's swap took at each step.
+    pub fn get_swap_retries(&self, pool_id: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.derived(pool_id)?.swap_retries.clone())
+    }
+
+    /// Regime of the reference price at each step, by its name.
+    pub fn get_price_regimes(&self, pool_id: u64) -> Result<Vec<&'static str>, RawDataError> {
+        Ok(self
+            .derived(pool_id)?
+            .price_regimes
+            .iter()
+            .map(|regime| regime.as_str())
+            .collect())
+    }
+
+    /// Whether the arbitrageur skipped each step for the pool's liquidity being below the minimum.
+    pub fn get_liquidity_too_low(&self, pool_id: u64) -> Result<Vec<bool>, RawDataError> {
+        Ok(self
+            .derived(pool_id)?
+            .liquidity_too_low
+            .iter()
+            .map(|too_low| *too_low > 0.0)
+            .collect())
+    }
+
+    /// Whether the arbitrageur was left with an unhedged position in each step.
+    pub fn get_unhedged(&self, pool_id: u64) -> Result<Vec<bool>, RawDataError> {
+        Ok(self
+            .derived(pool_id)?
+            .unhedged
+            .iter()
+            .map(|unhedged| *unhedged > 0.0)
+            .collect())
+    }
+
+    /// Value of the arbitrageur's deferred exchange trades at each step, in units of the quote token.
+    pub fn get_pending_exposure(&self, pool_id: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.derived(pool_id)?.pending_exposure.clone())
+    }
+
+    /// Value of the arbitrageur's swap input on the pool at each step, in units of the quote token.
+    pub fn get_volume(&self, pool_id: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.derived(pool_id)?.volume.clone())
+    }
+
+    /// True at each step the pool's reserves were nonzero and within the trading function's domain.
+    pub fn get_reserves_valid(&self, pool_id: u64) -> Result<Vec<bool>, RawDataError> {
+        Ok(self
+            .derived(pool_id)?
+            .reserves_valid
+            .iter()
+            .map(|valid| *valid > 0.0)
+            .collect())
+    }
+
+    /// Arbitrageur's realized profit at each step, the change in its portfolio value since the last recorded step.
+    pub fn get_arb_profit(&self, pool_id: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.derived(pool_id)?.arb_profit.clone())
+    }
+
+    /// Arbitrageur's profit per second at each step, see `profit_rates`.
+    pub fn profit_rate(
+        &self,
+        pool_id: u64,
+        seconds_per_step: f64,
+    ) -> Result<Vec<f64>, RawDataError> {
+        Ok(profit_rates(
+            &self.get_arb_profit(pool_id)?,
+            &self.get_step_index(pool_id)?,
+            seconds_per_step,
+        ))
+    }
+
+    /// Number of steps the pool's reserves were at the edge of the trading function's domain.
+    pub fn get_domain_edge_hits(&self, pool_id: u64) -> Result<usize, RawDataError> {
+        Ok(self
+            .derived(pool_id)?
+            .domain_edge_hits
+            .iter()
+            .filter(|hit| **hit > 0.0)
+            .count())
+    }
+
+    /// Arbitrageur's profit since the first step, net of the cumulative gas cost since then.
+    pub fn get_arbitrageur_net_profit_float(&self, pool_id: u64) -> Result<Vec<f64>, RawDataError> {
+        let values = self.get_arber_portfolio_value_float(pool_id)?;
+        let gas_costs = self.get_arbitrageur_gas_cost(pool_id)?;
+        let initial = values.first().copied().unwrap_or(0.0);
+
+        let mut cumulative_gas_cost = 0.0;
+        Ok(values
+            .iter()
+            .zip(gas_costs.iter())
+            .enumerate()
+            .map(|(i, (value, gas_cost))| {
+                // The first step is the baseline, so its gas is already reflected in the initial value.
+                if i > 0 {
+                    cumulative_gas_cost += gas_cost;
+                }
+                value - initial - cumulative_gas_cost
+            })
+            .collect())
+    }
+
+    /// Computes the annualized return, volatility, and sharpe ratio of the LP's portfolio value.
+    /// # Arguments
+    /// * `pool_id` - Pool to compute the performance of.
+    /// * `seconds_per_step` - Amount of time between each logged step, in seconds.
+    pub fn lp_performance(
+        &self,
+        pool_id: u64,
+        seconds_per_step: f64,
+    ) -> Result<LpPerformance, RawDataError> {
+        let values = self.get_portfolio_value_float(pool_id)?;
+        if values.len() < 2 || seconds_per_step <= 0.0 {
+            return Ok(LpPerformance {
+                annualized_return: 0.0,
+                annualized_vol: 0.0,
+                sharpe: 0.0,
+            });
+        }
+
+        let steps_per_year = SECONDS_PER_YEAR as f64 / seconds_per_step;
+        let years = (values.len() - 1) as f64 / steps_per_year;
+
+        let log_returns = values
+            .windows(2)
+            .map(|pair| (pair[1] / pair[0]).ln())
+            .collect::<Vec<f64>>();
+        let mean = log_returns.iter().sum::<f64>() / log_returns.len() as f64;
+        let variance =
+            log_returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / log_returns.len() as f64;
+
+        let annualized_return = (values[values.len() - 1] / values[0]).powf(1.0 / years) - 1.0;
+        let annualized_vol = variance.sqrt() * steps_per_year.sqrt();
+        let sharpe = if annualized_vol > 0.0 {
+            annualized_return / annualized_vol
+        } else {
+            0.0
+        };
+
+        Ok(LpPerformance {
+            annualized_return,
+            annualized_vol,
+            sharpe,
+        })
+    }
+
+    /// Computes percentiles of the absolute error between the reported and reference prices.
+    /// Percentiles are in [0, 100] and linearly interpolated between the sorted errors.
+    /// Returns zeros if no prices were recorded.
+    /// # Arguments
+    /// * `pool_id` - Pool to compute the tracking error of.
+    /// * `percentiles` - Percentiles to compute, e.g. 50 for the median.
+    pub fn tracking_error_percentiles(
+        &self,
+        pool_id: u64,
+        percentiles: &[f64],
+    ) -> Result<Vec<f64>, RawDataError> {
+        let reported = self.get_reported_price_float(pool_id)?;
+        let reference = self.get_exchange_price_float(pool_id)?;
+
+        let mut errors = reported
+            .iter()
+            .zip(reference.iter())
+            .map(|(r, e)| (r - e).abs())
+            .collect::<Vec<f64>>();
+        errors.sort_by(|a, b| a.total_cmp(b));
+
+        Ok(percentiles
+            .iter()
+            .map(|percentile| {
+                if errors.is_empty() {
+                    return 0.0;
+                }
+                let rank = percentile.clamp(0.0, 100.0) / 100.0 * (errors.len() - 1) as f64;
+                let lower = rank.floor() as usize;
+                let upper = rank.ceil() as usize;
+                errors[lower] + (errors[upper] - errors[lower]) * (rank - lower as f64)
+            })
+            .collect())
+    }
+
     /// Gets the portfolio value of the arbitrageur, which is the sum of its value of token reserves.
-    pub fn get_arber_portfolio_value_float(&self, pool_id: u64) -> Vec<f64> {
-        self.derived_data
-            .get(&pool_id)
-            .unwrap()
-            .arbitrageur_portfolio_value
-            .clone()
+    pub fn get_arber_portfolio_value_float(&self, pool_id: u64) -> Result<Vec<f64>, RawDataError> {
+        Ok(self.derived(pool_id)?.arbitrageur_portfolio_value.clone())
     }
 }
 
@@ -267,20 +934,78 @@ impl FloatToWad for Vec<f64> {
     }
 }
 
+/// Signed wad integers keep their sign, e.g. an invariant below the curve.
 impl WadToFloat for Vec<I256> {
     fn vec_wad_to_float(&self) -> Vec<f64> {
         self.clone()
             .into_iter()
-            .map(|x| x.as_i128() as f64)
+            .map(|x| {
+                let magnitude = wad_to_float(x.unsigned_abs());
+                if x.is_negative() {
+                    -magnitude
+                } else {
+                    magnitude
+                }
+            })
             .collect()
     }
 }
 
+/// Divides each row's profit by the time since the previous row, the steps between them times `seconds_per_step`.
+/// Rows can be more than a step apart, e.g. in on change only mode. Zero on the first row, which has no previous row.
+pub fn profit_rates(profits: &[f64], steps: &[u64], seconds_per_step: f64) -> Vec<f64> {
+    profits
+        .iter()
+        .enumerate()
+        .map(|(i, profit)| {
+            if i == 0 || i >= steps.len() {
+                return 0.0;
+            }
+
+            let dt = steps[i].saturating_sub(steps[i - 1]) as f64 * seconds_per_step;
+            if dt > 0.0 {
+                profit / dt
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Scales an amount of a token with `decimals` decimals to wad.
+pub fn scale_to_wad(amount: U256, decimals: u8) -> U256 {
+    match decimals.cmp(&18) {
+        std::cmp::Ordering::Less => amount * U256::exp10((18 - decimals) as usize),
+        std::cmp::Ordering::Equal => amount,
+        std::cmp::Ordering::Greater => amount / U256::exp10((decimals - 18) as usize),
+    }
+}
+
+/// Scales a wad amount to a token with `decimals` decimals, rounding down.
+pub fn scale_from_wad(amount: U256, decimals: u8) -> U256 {
+    match decimals.cmp(&18) {
+        std::cmp::Ordering::Less => amount / U256::exp10((18 - decimals) as usize),
+        std::cmp::Ordering::Equal => amount,
+        std::cmp::Ordering::Greater => amount * U256::exp10((decimals - 18) as usize),
+    }
+}
+
+/// Converts an amount of a token with `decimals` decimals to a float.
+pub fn units_to_float(amount: U256, decimals: u8) -> f64 {
+    wad_to_float(scale_to_wad(amount, decimals))
+}
+
+/// Converts a float to an amount of a token with `decimals` decimals.
+pub fn float_to_units(value: f64, decimals: u8) -> U256 {
+    scale_from_wad(float_to_wad(value), decimals)
+}
+
 pub trait PoolTransformers {
     fn map_x_total(&self) -> Vec<U256>;
     fn map_y_total(&self) -> Vec<U256>;
     fn map_x_per_lq(&self) -> Vec<U256>;
     fn map_y_per_lq(&self) -> Vec<U256>;
+    fn map_liquidity(&self) -> Vec<U256>;
 }
 
 impl PoolTransformers for Vec<PoolsReturn> {
@@ -341,6 +1066,13 @@ impl PoolTransformers for Vec<PoolsReturn> {
             .into_iter()
             .collect()
     }
+
+    fn map_liquidity(&self) -> Vec<U256> {
+        self.clone()
+            .into_iter()
+            .map(|p: PoolsReturn| U256::from(p.liquidity))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -375,4 +1107,209 @@ mod tests {
         let x_per_lq_float = x_per_lq.vec_wad_to_float();
         assert_eq!(x_per_lq_float, vec![1.0]);
     }
+
+    #[test]
+    fn six_decimal_balances_convert_to_the_same_floats() {
+        assert_eq!(float_to_units(50.0, 6), U256::from(50_000_000));
+        assert_eq!(units_to_float(U256::from(50_000_000), 6), 50.0);
+        assert_eq!(float_to_units(50.0, 18), float_to_wad(50.0));
+
+        let mut raw_data = RawData::new();
+        raw_data.add_token_decimals("token1".to_string(), 6);
+        raw_data.add_arbitrageur_balance("token0".to_string(), float_to_wad(1.5));
+        raw_data.add_arbitrageur_balance("token1".to_string(), U256::from(1_500_000));
+
+        assert_eq!(raw_data.get_arber_reserve_x_float().unwrap(), vec![1.5]);
+        assert_eq!(raw_data.get_arber_reserve_y_float().unwrap(), vec![1.5]);
+    }
+
+    #[test]
+    fn negative_invariant_to_float() {
+        let mut raw_data = RawData::new();
+        raw_data.add_invariant(0, I256::from(-500_000_000_000_000_000_i128));
+        raw_data.add_invariant(0, I256::from(250_000_000_000_000_000_i128));
+
+        assert_eq!(raw_data.get_invariant_float(0).unwrap(), vec![-0.5, 0.25]);
+    }
+
+    #[test]
+    fn missing_key_errors_name_the_key() {
+        let mut raw_data = RawData::new();
+        raw_data.add_exchange_price(0, U256::from(1));
+
+        assert_eq!(
+            raw_data.get_exchange_price(7),
+            Err(RawDataError::MissingKey {
+                series: "exchange_prices_wad",
+                key: "7".to_string(),
+            })
+        );
+        assert_eq!(
+            raw_data.get_reported_price_float(0),
+            Err(RawDataError::MissingKey {
+                series: "pools",
+                key: "0".to_string(),
+            })
+        );
+
+        let err = raw_data.get_arbitrageur_balance("tokn0").unwrap_err();
+        assert!(err.to_string().contains("`tokn0`"), "{}", err);
+    }
+
+    #[test]
+    fn profit_rate_is_profit_over_time_since_last_row() {
+        let mut raw_data = RawData::new();
+        // The row on step 4 follows two unrecorded steps.
+        for (step, profit) in [(0, 0.0), (1, 0.5), (4, 1.5), (5, -0.2)] {
+            raw_data.add_step_index(0, step);
+            raw_data.add_arb_profit(0, profit);
+        }
+
+        let seconds_per_step = 12.0;
+        let rate = raw_data.profit_rate(0, seconds_per_step).unwrap();
+
+        assert_eq!(rate, vec![0.0, 0.5 / 12.0, 1.5 / 36.0, -0.2 / 12.0]);
+    }
+
+    #[test]
+    fn lp_performance_of_constructed_series() {
+        let mut raw_data = RawData::new();
+        for value in [100.0, 104.0, 101.0, 107.0, 110.0] {
+            raw_data.add_pool_portfolio_value(0, value);
+        }
+
+        // four steps per year, so the series spans exactly one year.
+        let seconds_per_step = SECONDS_PER_YEAR as f64 / 4.0;
+        let performance = raw_data.lp_performance(0, seconds_per_step).unwrap();
+
+        assert!((performance.annualized_return - 0.1).abs() < 1e-12);
+        assert!(performance.annualized_vol > 0.0);
+        assert!(performance.sharpe.is_finite());
+        assert!(performance.sharpe > 0.0);
+    }
+
+    #[test]
+    fn net_profit_is_gross_profit_less_gas_cost() {
+        let gas = crate::config::Gas {
+            gas_price_gwei: 20.0,
+            eth_price_f: 2000.0,
+            zero_evm_gas_price: false,
+        };
+
+        let mut raw_data = RawData::new();
+        for (value, gas_used) in [
+            (100.0, 0_u64),
+            (101.0, 150_000),
+            (103.0, 0),
+            (104.0, 90_000),
+        ] {
+            raw_data.add_arbitrageur_portfolio_value(0, value);
+            raw_data.add_arbitrageur_gas(0, gas_used, gas.cost(gas_used));
+        }
+
+        let gross = 104.0 - 100.0;
+        let gas_cost = (150_000.0 + 90_000.0) * 20.0 * 1e-9 * 2000.0;
+        let net = raw_data.get_arbitrageur_net_profit_float(0).unwrap();
+
+        assert_eq!(net.len(), 4);
+        assert!((net[3] - (gross - gas_cost)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn tracking_error_median_of_known_series() {
+        let mut raw_data = RawData::new();
+        // Absolute errors of 0.04, 0.01, 0.03, 0.02 and 0.05.
+        for (reported, reference) in [
+            (1.04, 1.0),
+            (0.99, 1.0),
+            (1.0, 1.03),
+            (1.02, 1.0),
+            (1.0, 0.95),
+        ] {
+            raw_data.add_reported_price(0, parse_ether(reported).unwrap());
+            raw_data.add_exchange_price(0, parse_ether(reference).unwrap());
+        }
+
+        let percentiles = raw_data
+            .tracking_error_percentiles(0, &[0.0, 50.0, 100.0])
+            .unwrap();
+        assert!((percentiles[0] - 0.01).abs() < 1e-12);
+        assert!((percentiles[1] - 0.03).abs() < 1e-12);
+        assert!((percentiles[2] - 0.05).abs() < 1e-12);
+
+        // Even length series interpolates between the middle errors.
+        raw_data.add_reported_price(0, parse_ether(1.0).unwrap());
+        raw_data.add_exchange_price(0, parse_ether(1.0).unwrap());
+        let median = raw_data.tracking_error_percentiles(0, &[50.0]).unwrap()[0];
+        assert!((median - 0.025).abs() < 1e-12);
+    }
+
+    #[test]
+    fn domain_edge_hits_are_counted() {
+        let mut raw_data = RawData::new();
+        raw_data.add_domain_edge_hit(0, false);
+        assert_eq!(raw_data.get_domain_edge_hits(0).unwrap(), 0);
+
+        // All of the x reserves are sold out of the pool.
+        let mut curve =
+            crate::math::NormalCurve::new(0.0, 1.0, 1.0, 1.0, crate::math::SECONDS_PER_YEAR, 0.0);
+        raw_data.add_domain_edge_hit(0, curve.is_at_domain_edge());
+        assert_eq!(raw_data.get_domain_edge_hits(0).unwrap(), 1);
+
+        curve.reserve_x_per_wad = 0.5;
+        curve.reserve_y_per_wad = 0.5;
+        raw_data.add_domain_edge_hit(0, curve.is_at_domain_edge());
+        assert_eq!(raw_data.get_domain_edge_hits(0).unwrap(), 1);
+    }
+
+    #[test]
+    fn absolute_reserves_equal_per_liquidity_times_liquidity() {
+        let mut raw_data = RawData::new();
+
+        // a few steps with different reserves and liquidity
+        let steps = vec![
+            (
+                1_000_000_000_000_000_000_u128,
+                2_000_000_000_000_000_000_u128,
+                1_000_000_000_000_000_000_u128,
+            ),
+            (
+                500_000_000_000_000_000,
+                3_000_000_000_000_000_000,
+                2_000_000_000_000_000_000,
+            ),
+            (
+                250_000_000_000_000_000,
+                750_000_000_000_000_000,
+                500_000_000_000_000_000,
+            ),
+        ];
+
+        for (virtual_x, virtual_y, liquidity) in steps.iter() {
+            raw_data.add_pool_data(
+                0,
+                PoolsReturn {
+                    virtual_x: *virtual_x,
+                    virtual_y: *virtual_y,
+                    liquidity: *liquidity,
+                    fee_basis_points: 0,
+                    priority_fee_basis_points: 0,
+                    last_timestamp: 0,
+                    controller: ethers::types::H160::zero(),
+                    strategy: ethers::types::H160::zero(),
+                },
+            );
+        }
+
+        let x_abs = raw_data.get_pool_x_total_float(0).unwrap();
+        let y_abs = raw_data.get_pool_y_total_float(0).unwrap();
+        let x_per_lq = raw_data.get_pool_x_per_lq_float(0).unwrap();
+        let y_per_lq = raw_data.get_pool_y_per_lq_float(0).unwrap();
+        let liquidity = raw_data.get_pool_liquidity_float(0).unwrap();
+
+        for i in 0..steps.len() {
+            assert!((x_abs[i] - x_per_lq[i] * liquidity[i]).abs() < 1e-12);
+            assert!((y_abs[i] - y_per_lq[i] * liquidity[i]).abs() < 1e-12);
+        }
+    }
 }
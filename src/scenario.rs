@@ -0,0 +1,125 @@
+/// Scenarios pair a sim config with expected outcome ranges,
+/// turning experiments into repeatable regression checks.
+use super::config::SimConfig;
+use super::sim;
+use super::summary::RunSummary;
+
+/// # Expectations
+/// Asserted outcome ranges of a scenario. `None` skips the check.
+///
+/// # Fields
+/// * `final_reported_price` - Inclusive range the last reported price must be within. (Option<(f64, f64)>)
+/// * `final_reference_price` - Inclusive range the last reference price must be within. (Option<(f64, f64)>)
+/// * `max_tracking_rmse` - Upper bound of the reported vs. reference price RMSE. (Option<f64>)
+#[derive(Clone, Debug, Default)]
+#[allow(unused)]
+pub struct Expectations {
+    pub final_reported_price: Option<(f64, f64)>,
+    pub final_reference_price: Option<(f64, f64)>,
+    pub max_tracking_rmse: Option<f64>,
+}
+
+/// # Scenario
+/// A config to run and the expectations its outcome is checked against.
+#[derive(Clone, Debug)]
+#[allow(unused)]
+pub struct Scenario {
+    pub config: SimConfig,
+    pub expectations: Expectations,
+}
+
+/// # ScenarioReport
+/// Result of running a scenario.
+///
+/// # Fields
+/// * `passed` - True if every expectation was met. (bool)
+/// * `failures` - Description of each expectation that was not met. (Vec<String>)
+/// * `summary` - Metrics of the run that the expectations were checked against. (RunSummary)
+#[derive(Clone, Debug)]
+#[allow(unused)]
+pub struct ScenarioReport {
+    pub passed: bool,
+    pub failures: Vec<String>,
+    pub summary: RunSummary,
+}
+
+#[allow(unused)]
+impl Scenario {
+    pub fn new(config: SimConfig, expectations: Expectations) -> Self {
+        Self {
+            config,
+            expectations,
+        }
+    }
+
+    /// Runs the simulation with the scenario's config and checks the expectations.
+    pub async fn run(&self) -> Result<ScenarioReport, Box<dyn std::error::Error>> {
+        let (raw_data, pool_id) = sim::run(&self.config).await?;
+        let summary = RunSummary::from_raw_data(&raw_data, pool_id, &self.config)?;
+
+        Ok(self.expectations.check(summary))
+    }
+}
+
+#[allow(unused)]
+impl Expectations {
+    /// Checks the run summary against each expectation.
+    pub fn check(&self, summary: RunSummary) -> ScenarioReport {
+        let mut failures = Vec::new();
+
+        if let Some((min, max)) = self.final_reported_price {
+            if summary.final_reported_price < min || summary.final_reported_price > max {
+                failures.push(format!(
+                    "final reported price {} not within [{}, {}]",
+                    summary.final_reported_price, min, max
+                ));
+            }
+        }
+
+        if let Some((min, max)) = self.final_reference_price {
+            if summary.final_reference_price < min || summary.final_reference_price > max {
+                failures.push(format!(
+                    "final reference price {} not within [{}, {}]",
+                    summary.final_reference_price, min, max
+                ));
+            }
+        }
+
+        if let Some(max) = self.max_tracking_rmse {
+            if summary.tracking_rmse >= max {
+                failures.push(format!(
+                    "tracking rmse {} not less than {}",
+                    summary.tracking_rmse, max
+                ));
+            }
+        }
+
+        ScenarioReport {
+            passed: failures.is_empty(),
+            failures,
+            summary,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn default_scenario_passes() {
+        // Default config is a short, low volatility OU process around a price of 1.
+        let scenario = Scenario::new(
+            SimConfig::default(),
+            Expectations {
+                final_reported_price: Some((0.8, 1.2)),
+                final_reference_price: Some((0.8, 1.2)),
+                max_tracking_rmse: Some(0.05),
+            },
+        );
+
+        let report = scenario.run().await.unwrap();
+        assert!(report.passed, "failures: {:?}", report.failures);
+        assert!(report.summary.steps > 0);
+    }
+}
@@ -0,0 +1,98 @@
+/// Runs the simulation across many seeds to characterize the distribution of outcomes.
+use colored::*;
+use polars::prelude::*;
+use std::{error::Error, fs::File};
+
+use super::config::SimConfig;
+use super::sim;
+use super::summary::RunSummary;
+
+static FILE: &str = "seed_sweep";
+
+/// Runs `seeds` simulations and writes the per seed summaries and their aggregates to `out_data`.
+pub async fn main(seeds: usize) -> Result<(), Box<dyn Error>> {
+    let sim_config = SimConfig::new()?;
+    let mut results = run(&sim_config, seeds).await?;
+
+    let path = format!("{}/{}.csv", sim::OUTPUT_DIRECTORY, FILE);
+    let file = File::create(&path)?;
+    CsvWriter::new(file).finish(&mut results)?;
+
+    println!("{}\n{}", "Seed sweep results:".bright_yellow(), results);
+
+    Ok(())
+}
+
+/// Runs the simulation for the seeds `config.process.seed..config.process.seed + seeds`,
+/// reusing a single deployment, and collects the summary of each run.
+///
+/// Returns a row per seed followed by a `mean` and a `std` row aggregating each metric.
+pub async fn run(config: &SimConfig, seeds: usize) -> Result<DataFrame, Box<dyn Error>> {
+    let configs = (0..seeds as u64)
+        .map(|i| {
+            let mut config = config.clone();
+            config.process.seed += i;
+            config
+        })
+        .collect::<Vec<SimConfig>>();
+
+    let summaries = sim::sweep(&configs, true)
+        .await?
+        .iter()
+        .zip(configs.iter())
+        .map(|((raw_data, pool_id), config)| RunSummary::from_raw_data(raw_data, *pool_id, config))
+        .collect::<Result<Vec<RunSummary>, _>>()?;
+
+    let mut labels = configs
+        .iter()
+        .map(|config| config.process.seed.to_string())
+        .collect::<Vec<String>>();
+    labels.push("mean".to_string());
+    labels.push("std".to_string());
+
+    let with_aggregates = |metric: &dyn Fn(&RunSummary) -> f64| {
+        let mut values = summaries.iter().map(metric).collect::<Vec<f64>>();
+        let (mean, std) = mean_and_std(&values);
+        values.push(mean);
+        values.push(std);
+        values
+    };
+
+    let results = df!(
+        "seed" => labels,
+        "final_lp_value" => with_aggregates(&|s| s.final_lp_value),
+        "arbitrageur_profit" => with_aggregates(&|s| s.arbitrageur_profit),
+        "tracking_rmse" => with_aggregates(&|s| s.tracking_rmse),
+        "cumulative_fees" => with_aggregates(&|s| s.cumulative_fees),
+    )?;
+
+    Ok(results)
+}
+
+/// Mean and population standard deviation of the values. Zero for no values.
+fn mean_and_std(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn three_seeds_produce_three_rows_and_aggregates() {
+        let results = run(&SimConfig::default(), 3).await.unwrap();
+
+        assert_eq!(results.height(), 3 + 2);
+
+        let seeds = results.column("seed").unwrap().utf8().unwrap();
+        assert_eq!(seeds.get(3), Some("mean"));
+        assert_eq!(seeds.get(4), Some("std"));
+    }
+}
@@ -8,17 +8,21 @@ use arbiter::{
 use bindings::{external_normal_strategy_lib, i_portfolio_actions::CreatePoolCall};
 // dynamic imports... generate with build.sh
 use bindings::{actor, entrypoint, exchange, mock_erc20, portfolio, weth};
+use colored::*;
 use ethers::{
-    abi::{encode_packed, Token, Tokenize},
+    abi::{encode_packed, Abi, Token, Tokenize},
     prelude::{Address, U128, U256},
-    types::H160,
+    types::{Bytes, H160},
 };
 use revm::primitives::B160;
+use std::{fs, str::FromStr};
 
 use super::calls;
 use super::common;
+use super::event_log::{self, Event};
 use crate::calls::DecodedReturns;
 use crate::config::SimConfig;
+use crate::raw_data::float_to_units;
 
 pub fn run(
     manager: &mut SimulationManager,
@@ -26,17 +30,31 @@ pub fn run(
 ) -> Result<(), Box<dyn std::error::Error>> {
     let _ = config; // todo: use config vars for create pool.
 
+    // Allocating fails if block.timestamp is 0.
+    ensure_block_timestamp(manager);
+
+    if config.gas.zero_evm_gas_price {
+        disable_gas_price(manager);
+    }
+
     let admin = manager.agents.get("admin").unwrap();
 
     // Deploy weth
-    let weth = SimulationContract::new(weth::WETH_ABI.clone(), weth::WETH_BYTECODE.clone());
+    let (abi, bytecode) = contract_source(
+        &config.artifacts.weth,
+        &weth::WETH_ABI,
+        &weth::WETH_BYTECODE,
+    )?;
+    let weth = SimulationContract::new(abi, bytecode);
     let (weth_contract, _result) = admin.deploy(weth, vec![])?;
 
     // Deploy portfolio
-    let portfolio = SimulationContract::new(
-        portfolio::PORTFOLIO_ABI.clone(),
-        portfolio::PORTFOLIO_BYTECODE.clone(),
-    );
+    let (abi, bytecode) = contract_source(
+        &config.artifacts.portfolio,
+        &portfolio::PORTFOLIO_ABI,
+        &portfolio::PORTFOLIO_BYTECODE,
+    )?;
+    let portfolio = SimulationContract::new(abi, bytecode);
     let (portfolio_contract, _result) = admin.deploy(
         portfolio,
         (
@@ -48,10 +66,12 @@ pub fn run(
     )?;
 
     // Deploy Entrypoint
-    let entrypoint = SimulationContract::new(
-        entrypoint::ENTRYPOINT_ABI.clone(),
-        entrypoint::ENTRYPOINT_BYTECODE.clone(),
-    );
+    let (abi, bytecode) = contract_source(
+        &config.artifacts.entrypoint,
+        &entrypoint::ENTRYPOINT_ABI,
+        &entrypoint::ENTRYPOINT_BYTECODE,
+    )?;
+    let entrypoint = SimulationContract::new(abi, bytecode);
     let (entrypoint_contract, _result) = admin.deploy(
         entrypoint,
         (
@@ -88,15 +108,11 @@ pub fn run(
     let token0_address: H160 =
         entrypoint_callable.decode_output("token0", unpack_execution(token0)?)?;
     let token0_address_bytes = B160::from(token0_address.as_fixed_bytes());
-    let token0_contract =
-        SimulationContract::bind(mock_erc20::MOCKERC20_ABI.clone(), token0_address_bytes);
 
     let token1 = admin.call(entrypoint_callable, "token1", vec![])?;
     let token1_address: H160 =
         entrypoint_callable.decode_output("token1", unpack_execution(token1)?)?;
     let token1_address_bytes = B160::from(token1_address.as_fixed_bytes());
-    let token1_contract =
-        SimulationContract::bind(mock_erc20::MOCKERC20_ABI.clone(), token1_address_bytes);
 
     let actor = admin.call(entrypoint_callable, "actor", vec![])?;
     let actor_address: H160 =
@@ -104,22 +120,60 @@ pub fn run(
     let actor_address_bytes = B160::from(actor_address.as_fixed_bytes());
     let actor_contract = SimulationContract::bind(actor::ACTOR_ABI.clone(), actor_address_bytes);
 
-    let mut exec = calls::Caller::new(admin);
+    // The entrypoint's tokens have 18 decimals, so tokens with other decimals are deployed here
+    // and paired on portfolio, see `pool_pair_id`.
+    let entrypoint_decimals = common::ENTRYPOINT_TOKEN_DECIMALS;
+    let deploys_tokens = config.tokens.decimals_x != entrypoint_decimals
+        || config.tokens.decimals_y != entrypoint_decimals;
+    let (token0_contract, token1_contract) = if deploys_tokens {
+        let token0_contract = deploy_token(admin, "Mock0", "X", config.tokens.decimals_x)?;
+        let token1_contract = deploy_token(admin, "Mock1", "Y", config.tokens.decimals_y)?;
+        calls::Caller::new(admin).create_pair(
+            &portfolio_contract,
+            recast_address(token0_contract.address),
+            recast_address(token1_contract.address),
+        )?;
+        (token0_contract, token1_contract)
+    } else {
+        (
+            SimulationContract::bind(mock_erc20::MOCKERC20_ABI.clone(), token0_address_bytes),
+            SimulationContract::bind(mock_erc20::MOCKERC20_ABI.clone(), token1_address_bytes),
+        )
+    };
 
     let approve_args = (recast_address(portfolio_contract.address), U256::MAX).into_tokens();
-    let mint_args = (
-        recast_address(B160::from_low_u64_be(common::ARBITRAGEUR_ADDRESS_BASE)),
-        float_to_wad(50.0),
-    )
-        .into_tokens();
-    let mint_exchange_args = (exchange_address, float_to_wad(88888888888888.0)).into_tokens();
 
-    exec.call(&token0_contract, "approve", approve_args.clone())?;
-    exec.call(&token1_contract, "approve", approve_args.clone())?;
-    exec.call(&token0_contract, "mint", mint_args.clone())?;
-    exec.call(&token1_contract, "mint", mint_args.clone())?;
-    exec.call(&token0_contract, "mint", mint_exchange_args.clone())?;
-    exec.call(&token1_contract, "mint", mint_exchange_args.clone())?;
+    // The EVM executes transactions serially, so the mints and approvals run one after another
+    // through a single caller, there is nothing to parallelize across agents.
+    let mut exec = calls::Caller::new(admin);
+    for (token, decimals) in [
+        (&token0_contract, config.tokens.decimals_x),
+        (&token1_contract, config.tokens.decimals_y),
+    ] {
+        // The entrypoint mints its own tokens to the admin.
+        if deploys_tokens {
+            let mint_admin_args = (
+                recast_address(admin.address()),
+                float_to_units(common::ADMIN_INITIAL_BALANCE_F, decimals),
+            )
+                .into_tokens();
+            exec.call(token, "mint", mint_admin_args)?;
+        }
+        let mint_args = (
+            recast_address(B160::from_low_u64_be(common::ARBITRAGEUR_ADDRESS_BASE)),
+            float_to_units(config.economic.arbitrageur_initial_balance_f, decimals),
+        )
+            .into_tokens();
+        let mint_exchange_args = (
+            exchange_address,
+            float_to_units(config.economic.exchange_initial_balance_f, decimals),
+        )
+            .into_tokens();
+
+        exec.call(token, "approve", approve_args.clone())?;
+        exec.call(token, "mint", mint_args)?;
+        exec.call(token, "mint", mint_exchange_args)?;
+    }
 
     manager
         .deployed_contracts
@@ -142,21 +196,102 @@ pub fn run(
 
     deploy_external_normal_strategy_lib(manager)?;
 
-    setup_agent(manager);
+    // The primary exchange is deployed by the entrypoint, any other venues are deployed here.
+    for venue in config.arbitrageur.venues.iter() {
+        if !manager.deployed_contracts.contains_key(venue) {
+            deploy_venue(manager, venue, config.economic.exchange_initial_balance_f)?;
+        }
+    }
+
+    if let Some(warning) = config.economic.arb_threshold_warning() {
+        println!("{} {}", "Warning:".yellow(), warning);
+    }
+    setup_agent(
+        manager,
+        &config.arbitrageur.venues,
+        config.economic.arb_threshold_bps(),
+    );
 
     Ok(())
 }
 
-fn setup_agent(manager: &mut SimulationManager) {
-    let exchange = manager.deployed_contracts.get("exchange").unwrap();
+/// Gets the decimals of the token.
+pub fn token_decimals(
+    agent: &dyn Agent,
+    token: &SimulationContract<IsDeployed>,
+) -> Result<u8, anyhow::Error> {
+    calls::Caller::new(agent).view(token, "decimals", vec![])
+}
+
+/// Deploys a mock ERC20 token with `decimals`.
+pub fn deploy_token(
+    admin: &dyn Agent,
+    name: &str,
+    symbol: &str,
+    decimals: u8,
+) -> Result<SimulationContract<IsDeployed>, Box<dyn std::error::Error>> {
+    let token = SimulationContract::new(
+        mock_erc20::MOCKERC20_ABI.clone(),
+        mock_erc20::MOCKERC20_BYTECODE.clone(),
+    );
+    let (token_contract, _result) = admin.deploy(
+        token,
+        (name.to_string(), symbol.to_string(), decimals).into_tokens(),
+    )?;
+
+    Ok(token_contract)
+}
+
+/// Deploys an additional exchange venue named `name` and mints it `balance_f` of each token,
+/// the same token liquidity as the primary exchange.
+pub fn deploy_venue(
+    manager: &mut SimulationManager,
+    name: &str,
+    balance_f: f64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let admin = manager.agents.get("admin").unwrap();
+    let token0 = manager.deployed_contracts.get("token0").unwrap();
+    let token1 = manager.deployed_contracts.get("token1").unwrap();
 
-    let event_filters = vec![SimulationEventFilter::new(exchange, "PriceChange")];
+    let exchange = SimulationContract::new(
+        exchange::EXCHANGE_ABI.clone(),
+        exchange::EXCHANGE_BYTECODE.clone(),
+    );
+    let (exchange_contract, _result) = admin.deploy(exchange, vec![])?;
+
+    let mut exec = calls::Caller::new(admin);
+    for token in [token0, token1] {
+        let mint_exchange_args = (
+            recast_address(exchange_contract.address),
+            float_to_units(balance_f, token_decimals(admin, token)?),
+        )
+            .into_tokens();
+        exec.call(token, "mint", mint_exchange_args)?;
+    }
+
+    manager
+        .deployed_contracts
+        .insert(name.to_string(), exchange_contract);
+
+    Ok(())
+}
+
+/// Activates the arbitrageur agent, which detects price changes beyond its fee tolerance.
+fn setup_agent(manager: &mut SimulationManager, venues: &[String], fee_tolerance_bps: u16) {
+    // Listens to the price changes of every venue.
+    let event_filters = venues
+        .iter()
+        .map(|venue| {
+            let exchange = manager.deployed_contracts.get(venue).unwrap();
+            SimulationEventFilter::new(exchange, "PriceChange")
+        })
+        .collect::<Vec<_>>();
 
     let agent = SimpleArbitrageur::new(
         "arbitrageur",
         event_filters,
         revm::primitives::U256::from(common::WAD as u128)
-            - revm::primitives::U256::from(common::FEE_BPS as f64 * 1e18),
+            - revm::primitives::U256::from(fee_tolerance_bps as f64 * 1e18),
     );
 
     manager
@@ -178,6 +313,103 @@ pub async fn init_arbitrageur(
     drop(prices);
 }
 
+/// Approves the portfolio and each exchange venue to spend the arbitrageur's tokens.
+pub fn approve_arbitrageur(
+    manager: &SimulationManager,
+    config: &SimConfig,
+) -> Result<(), anyhow::Error> {
+    let arbitrageur = manager.agents.get("arbitrageur").unwrap();
+    let token0 = manager.deployed_contracts.get("token0").unwrap();
+    let token1 = manager.deployed_contracts.get("token1").unwrap();
+    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+
+    let mut arb_caller = calls::Caller::new(arbitrageur);
+    arb_caller
+        .approve(token0, recast_address(portfolio.address), 0.0)
+        .res()?;
+    arb_caller
+        .approve(token1, recast_address(portfolio.address), 0.0)
+        .res()?;
+
+    for venue in config.arbitrageur.venues.iter() {
+        let exchange = manager.deployed_contracts.get(venue).unwrap();
+        arb_caller
+            .approve(token0, recast_address(exchange.address), 0.0)
+            .res()?;
+        arb_caller
+            .approve(token1, recast_address(exchange.address), 0.0)
+            .res()?;
+    }
+
+    Ok(())
+}
+
+/// Burns the arbitrageur's token balances and mints it `balance_f` of each token,
+/// so a new run on an existing deployment starts from the same arbitrageur state.
+pub fn reset_arbitrageur_balances(
+    manager: &SimulationManager,
+    balance_f: f64,
+) -> Result<(), anyhow::Error> {
+    let admin = manager.agents.get("admin").unwrap();
+    let arbitrageur = manager.agents.get("arbitrageur").unwrap();
+    let token0 = manager.deployed_contracts.get("token0").unwrap();
+    let token1 = manager.deployed_contracts.get("token1").unwrap();
+
+    let arbitrageur_address = recast_address(arbitrageur.address());
+    let mut exec = calls::Caller::new(admin);
+    let mut arb_exec = calls::Caller::new(arbitrageur);
+
+    for token in [token0, token1] {
+        let decimals = token_decimals(admin, token)?;
+        let balance: U256 = arb_exec.balance_of(token).decoded(token)?;
+        exec.call(token, "burn", (arbitrageur_address, balance).into_tokens())?;
+        exec.call(
+            token,
+            "mint",
+            (arbitrageur_address, float_to_units(balance_f, decimals)).into_tokens(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Mints the arbitrageur `amount_f` of each token, simulating capital flowing in from outside the sim.
+pub fn top_up_arbitrageur(manager: &SimulationManager, amount_f: f64) -> Result<(), anyhow::Error> {
+    let admin = manager.agents.get("admin").unwrap();
+    let arbitrageur = manager.agents.get("arbitrageur").unwrap();
+    let token0 = manager.deployed_contracts.get("token0").unwrap();
+    let token1 = manager.deployed_contracts.get("token1").unwrap();
+
+    let arbitrageur_address = recast_address(arbitrageur.address());
+    let mut exec = calls::Caller::new(admin);
+    for token in [token0, token1] {
+        let amount = float_to_units(amount_f, token_decimals(admin, token)?);
+        exec.call(token, "mint", (arbitrageur_address, amount).into_tokens())?;
+    }
+
+    Ok(())
+}
+
+/// Creates a pair of the deployed tokens named `asset` and `quote` on portfolio and returns its id,
+/// which pools are created on with `economic.pool_pair_id`.
+pub fn create_pair(
+    manager: &SimulationManager,
+    asset: &str,
+    quote: &str,
+) -> Result<u32, anyhow::Error> {
+    let admin = manager.agents.get("admin").unwrap();
+    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+    let token = |name: &str| {
+        manager
+            .deployed_contracts
+            .get(name)
+            .map(|contract| recast_address(contract.address))
+            .ok_or_else(|| anyhow::anyhow!("setup.rs: no deployed token named `{}`", name))
+    };
+
+    calls::Caller::new(admin).create_pair(portfolio, token(asset)?, token(quote)?)
+}
+
 pub fn init_pool(
     manager: &SimulationManager,
     config: &SimConfig,
@@ -215,7 +447,8 @@ pub fn init_pool(
     Ok(pool_id)
 }
 
-fn build_create_pool_call(
+/// Builds the `createPool` arguments from the config, using the actor to compute the initial reserves.
+pub fn build_create_pool_call(
     manager: &SimulationManager,
     config: &SimConfig,
 ) -> Result<CreatePoolCall, anyhow::Error> {
@@ -232,7 +465,7 @@ fn build_create_pool_call(
         (config_copy.economic.pool_volatility_f * common::BASIS_POINT_DIVISOR as f64) as u32, // vol bps
         (config_copy.economic.pool_time_remaining_years_f * common::SECONDS_PER_YEAR as f64) as u32, // 1 year duration in seconds
         config_copy.economic.pool_is_perpetual, // is perpetual
-        float_to_wad(config_copy.process.initial_price), // initial price wad
+        float_to_wad(config_copy.initial_pool_price()), // initial price wad
     )
         .into_tokens();
     let create_args: bindings::actor::GetCreatePoolComputedArgsReturn = exec
@@ -240,44 +473,169 @@ fn build_create_pool_call(
         .decoded(actor)?;
 
     Ok(CreatePoolCall {
-        pair_id: 1_u32, // pairId todo: fix this if running multiple pairs?
+        pair_id: pool_pair_id(manager, config)?,  // pairId
         reserve_x_per_wad: create_args.initial_x, // reserveXPerWad
         reserve_y_per_wad: create_args.initial_y, // reserveYPerWad
         fee_basis_points: config_copy.economic.pool_fee_basis_points, // feeBips
         priority_fee_basis_points: config_copy.economic.pool_priority_fee_basis_points, // priorityFeeBips
-        controller: H160::zero(),                 // controller,
-        strategy: H160::zero(),                   // address(0) == default strategy
-        strategy_args: create_args.strategy_data, // strategyArgs
+        controller: pool_controller(admin, config), // controller,
+        strategy: H160::zero(),                     // address(0) == default strategy
+        strategy_args: create_args.strategy_data,   // strategyArgs
     })
 }
 
-pub fn allocate_liquidity(manager: &SimulationManager, pool_id: u64) -> Result<(), anyhow::Error> {
+/// Id of the pair the pool is created on.
+/// `ENTRYPOINT_PAIR_ID` is the token0/token1 pair, which setup creates itself if it deployed the tokens,
+/// so its id is looked up on portfolio.
+fn pool_pair_id(manager: &SimulationManager, config: &SimConfig) -> Result<u32, anyhow::Error> {
+    if config.economic.pool_pair_id != common::ENTRYPOINT_PAIR_ID {
+        return Ok(config.economic.pool_pair_id);
+    }
+
     let admin = manager.agents.get("admin").unwrap();
     let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+    let token0 = manager.deployed_contracts.get("token0").unwrap();
+    let token1 = manager.deployed_contracts.get("token1").unwrap();
 
-    let recipient = recast_address(admin.address());
-    let mut exec = calls::Caller::new(admin);
-
-    // note: this can fail automatically if block.timestamp is 0.
-    // note: this can fail if maxDeltaAsset/maxDeltaQuote is larger than uint128
-    exec.call(
+    calls::Caller::new(admin).view(
         portfolio,
-        "allocate",
+        "getPairId",
         (
-            false, // use max
-            recipient,
-            pool_id,                   // poolId
-            float_to_wad(1.0),         // 100e18 liquidity
-            U128::MAX / U128::from(2), // tries scaling to wad by multiplying beyond word size, div to avoid.
-            U128::MAX / U128::from(2),
+            recast_address(token0.address),
+            recast_address(token1.address),
         )
             .into_tokens(),
-    )?
-    .res()?;
+    )
+}
+
+/// Controller of the pool, the admin if the fee schedule changes the pool's fee, else none.
+fn pool_controller(admin: &dyn Agent, config: &SimConfig) -> H160 {
+    if config.fee_schedule.is_dynamic() {
+        recast_address(admin.address())
+    } else {
+        H160::zero()
+    }
+}
+
+/// Sets the pool's fees as its controller, the admin.
+pub fn set_pool_fee(
+    manager: &SimulationManager,
+    pool_id: u64,
+    priority_fee_basis_points: u16,
+    fee_basis_points: u16,
+) -> Result<(), anyhow::Error> {
+    let admin = manager.agents.get("admin").unwrap();
+    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+
+    calls::Caller::new(admin).call(
+        portfolio,
+        "changeParameters",
+        (pool_id, priority_fee_basis_points, fee_basis_points).into_tokens(),
+    )?;
 
     Ok(())
 }
 
+/// Allocates one unit of liquidity to the pool from the admin.
+/// Retries once with max deltas clamped further below uint128's max if the first attempt fails,
+/// and errors with the attempted arguments if the retry fails too.
+pub fn allocate_liquidity(manager: &SimulationManager, pool_id: u64) -> Result<(), anyhow::Error> {
+    let admin = manager.agents.get("admin").unwrap();
+    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+
+    let recipient = recast_address(admin.address());
+    let mut exec = calls::Caller::new(admin);
+
+    // note: this can fail if maxDeltaAsset/maxDeltaQuote is larger than uint128
+    // tries scaling to wad by multiplying beyond word size, so the first attempt divs to avoid it
+    // and the retry clamps to a range that can be scaled to wad for any token decimals.
+    let max_deltas = [U128::MAX / U128::from(2), U128::from(u64::MAX)];
+
+    let mut last_error = None;
+    for max_delta in max_deltas.iter() {
+        let result = exec
+            .call(
+                portfolio,
+                "allocate",
+                (
+                    false, // use max
+                    recipient,
+                    pool_id,           // poolId
+                    float_to_wad(1.0), // 1e18 liquidity
+                    *max_delta,
+                    *max_delta,
+                )
+                    .into_tokens(),
+            )
+            .and_then(|exec| exec.res());
+
+        match result {
+            Ok(_) => {
+                event_log::emit(Event::Allocation {
+                    pool_id,
+                    delta_liquidity: float_to_wad(1.0).as_u128(),
+                })?;
+                return Ok(());
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "setup.rs: allocate on pool {} failed with max deltas {:?}, is the block timestamp nonzero? {:?}",
+        pool_id,
+        max_deltas,
+        last_error
+    ))
+}
+
+/// ABI and bytecode of a contract, loaded from the artifact at `path` if set, else the bindings' `abi` and `bytecode`.
+pub fn contract_source(
+    path: &Option<String>,
+    abi: &Abi,
+    bytecode: &Bytes,
+) -> Result<(Abi, Bytes), Box<dyn std::error::Error>> {
+    match path {
+        Some(path) => load_artifact(path),
+        None => Ok((abi.clone(), bytecode.clone())),
+    }
+}
+
+/// Loads the ABI and creation bytecode of a compiled contract artifact.
+/// The bytecode is either forge's `{ "object": "0x..." }` or a plain hex string.
+pub fn load_artifact(path: &str) -> Result<(Abi, Bytes), Box<dyn std::error::Error>> {
+    let artifact: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+    let abi: Abi = serde_json::from_value(artifact["abi"].clone())?;
+    let bytecode = match &artifact["bytecode"] {
+        serde_json::Value::String(hex) => hex.as_str(),
+        bytecode => bytecode["object"]
+            .as_str()
+            .ok_or(format!("artifact {} has no bytecode", path))?,
+    };
+    let bytecode = Bytes::from_str(bytecode)?;
+    if bytecode.is_empty() {
+        return Err(format!("artifact {} has empty bytecode, is it an interface?", path).into());
+    }
+
+    Ok((abi, bytecode))
+}
+
+/// Advances the block timestamp off of zero, which allocating on portfolio fails on.
+pub fn ensure_block_timestamp(manager: &mut SimulationManager) {
+    let block = &mut manager.environment.evm.env.block;
+    if block.timestamp == revm::primitives::U256::ZERO {
+        block.timestamp = revm::primitives::U256::from(1);
+    }
+}
+
+/// Prices the EVM's gas at zero, so calls never spend the caller's native balance.
+pub fn disable_gas_price(manager: &mut SimulationManager) {
+    let env = &mut manager.environment.evm.env;
+    env.block.basefee = revm::primitives::U256::ZERO;
+    env.tx.gas_price = revm::primitives::U256::ZERO;
+}
+
 pub fn deploy_external_normal_strategy_lib(
     manager: &mut SimulationManager,
 ) -> Result<&SimulationContract<IsDeployed>, Box<dyn std::error::Error>> {
@@ -294,3 +652,180 @@ pub fn deploy_external_normal_strategy_lib(
     let library = manager.deployed_contracts.get("library").unwrap();
     Ok(library)
 }
+
+/// Deploys the sim contracts and an allocated pool priced at the config's initial price, for tests.
+#[cfg(test)]
+pub fn setup_pool(config: &SimConfig) -> (SimulationManager, u64) {
+    let mut manager = SimulationManager::new();
+    run(&mut manager, config).unwrap();
+    approve_arbitrageur(&manager, config).unwrap();
+    let pool_id = init_pool(&manager, config).unwrap();
+    allocate_liquidity(&manager, pool_id).unwrap();
+    super::step::run(&manager, config.process.initial_price).unwrap();
+    (manager, pool_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocate_immediately_after_setup() {
+        let config = SimConfig::default();
+        let mut manager = SimulationManager::new();
+        run(&mut manager, &config).unwrap();
+
+        let pool_id = init_pool(&manager, &config).unwrap();
+        allocate_liquidity(&manager, pool_id).unwrap();
+    }
+
+    #[test]
+    fn six_decimal_quote_token_is_minted_in_its_units() {
+        let mut config = SimConfig::default();
+        config.tokens.decimals_y = 6;
+        let mut manager = SimulationManager::new();
+        run(&mut manager, &config).unwrap();
+
+        let admin = manager.agents.get("admin").unwrap();
+        let arbitrageur = recast_address(manager.agents.get("arbitrageur").unwrap().address());
+        let token1 = manager.deployed_contracts.get("token1").unwrap();
+        assert_eq!(token_decimals(admin, token1).unwrap(), 6);
+
+        let balance: U256 = calls::Caller::new(admin)
+            .call(token1, "balanceOf", arbitrageur.into_tokens())
+            .unwrap()
+            .decoded(token1)
+            .unwrap();
+        assert_eq!(
+            balance,
+            U256::from(common::ARBITRAGEUR_INITIAL_BALANCE_F as u64) * U256::exp10(6)
+        );
+
+        // The pool is created on the pair of the deployed tokens, not the entrypoint's pair.
+        let pair_id = pool_pair_id(&manager, &config).unwrap();
+        assert_ne!(pair_id, common::ENTRYPOINT_PAIR_ID);
+        init_pool(&manager, &config).unwrap();
+    }
+
+    #[test]
+    fn created_pairs_have_distinct_ids() {
+        let config = SimConfig::default();
+        let mut manager = SimulationManager::new();
+        run(&mut manager, &config).unwrap();
+
+        // The entrypoint created the token0/token1 pair, so weth pairs with each are new.
+        let first = create_pair(&manager, "weth", "token0").unwrap();
+        let second = create_pair(&manager, "weth", "token1").unwrap();
+
+        assert_ne!(first, second);
+        assert_ne!(first, config.economic.pool_pair_id);
+        assert_ne!(second, config.economic.pool_pair_id);
+        assert!(create_pair(&manager, "weth", "token2").is_err());
+    }
+
+    #[test]
+    fn setup_mints_every_token() {
+        let mut config = SimConfig::default();
+        config.arbitrageur.venues = vec!["exchange".to_string(), "exchange_1".to_string()];
+        let mut manager = SimulationManager::new();
+        run(&mut manager, &config).unwrap();
+
+        let admin = manager.agents.get("admin").unwrap();
+        let arbitrageur = recast_address(manager.agents.get("arbitrageur").unwrap().address());
+        let portfolio =
+            recast_address(manager.deployed_contracts.get("portfolio").unwrap().address);
+        let mut exec = calls::Caller::new(admin);
+
+        for token in ["token0", "token1"] {
+            let token = manager.deployed_contracts.get(token).unwrap();
+
+            let balance: U256 = exec
+                .call(token, "balanceOf", arbitrageur.into_tokens())
+                .unwrap()
+                .decoded(token)
+                .unwrap();
+            assert_eq!(balance, float_to_wad(common::ARBITRAGEUR_INITIAL_BALANCE_F));
+
+            for venue in config.arbitrageur.venues.iter() {
+                let venue = recast_address(manager.deployed_contracts.get(venue).unwrap().address);
+                let balance: U256 = exec
+                    .call(token, "balanceOf", venue.into_tokens())
+                    .unwrap()
+                    .decoded(token)
+                    .unwrap();
+                assert_eq!(balance, float_to_wad(common::EXCHANGE_INITIAL_BALANCE_F));
+            }
+
+            let allowance: U256 = exec
+                .call(
+                    token,
+                    "allowance",
+                    (recast_address(admin.address()), portfolio).into_tokens(),
+                )
+                .unwrap()
+                .decoded(token)
+                .unwrap();
+            assert_eq!(allowance, U256::MAX);
+        }
+    }
+
+    #[test]
+    fn weth_artifact_from_disk_is_deployed() {
+        let artifact = serde_json::json!({
+            "abi": *weth::WETH_ABI,
+            "bytecode": { "object": weth::WETH_BYTECODE.to_string() },
+        });
+        let path = std::env::temp_dir().join("proto_sim_weth_artifact.json");
+        fs::write(&path, artifact.to_string()).unwrap();
+
+        let mut config = SimConfig::default();
+        config.artifacts.weth = Some(path.to_str().unwrap().to_string());
+        let mut manager = SimulationManager::new();
+        run(&mut manager, &config).unwrap();
+
+        let admin = manager.agents.get("admin").unwrap();
+        let weth = manager.deployed_contracts.get("weth").unwrap();
+        let symbol: String = calls::Caller::new(admin)
+            .call(weth, "symbol", vec![])
+            .unwrap()
+            .decoded(weth)
+            .unwrap();
+        assert_eq!(symbol, "WETH");
+    }
+
+    #[test]
+    fn missing_artifact_fails_setup() {
+        let mut config = SimConfig::default();
+        config.artifacts.weth = Some("does/not/exist.json".to_string());
+        let mut manager = SimulationManager::new();
+
+        assert!(run(&mut manager, &config).is_err());
+    }
+
+    #[test]
+    fn zero_gas_price_calls_without_native_balance() {
+        let mut config = SimConfig::default();
+        config.gas.zero_evm_gas_price = true;
+        let mut manager = SimulationManager::new();
+        run(&mut manager, &config).unwrap();
+
+        // Empties the arbitrageur's native balance.
+        let address = manager.agents.get("arbitrageur").unwrap().address();
+        manager
+            .environment
+            .evm
+            .db
+            .as_mut()
+            .unwrap()
+            .insert_account_info(address, revm::primitives::AccountInfo::default());
+
+        let arbitrageur = manager.agents.get("arbitrageur").unwrap();
+        let token0 = manager.deployed_contracts.get("token0").unwrap();
+        let portfolio =
+            recast_address(manager.deployed_contracts.get("portfolio").unwrap().address);
+        calls::Caller::new(arbitrageur)
+            .approve(token0, portfolio, 1.0)
+            .res()
+            .unwrap();
+    }
+}
@@ -1,35 +1,328 @@
 /// Runs a simulation using the config.
-use arbiter::{agent::AgentType, manager::SimulationManager, utils::recast_address};
+use arbiter::{agent::AgentType, manager::SimulationManager};
 use colored::*;
+use polars::prelude::JsonFormat;
 use visualize;
 
 pub static OUTPUT_DIRECTORY: &str = "out_data";
 pub static OUTPUT_FILE_NAME: &str = "results";
+/// Number of rows kept in memory for the plots when the csv is written incrementally.
+pub static PLOT_WINDOW_ROWS: usize = 1000;
 
 // useful traits
-use crate::calls;
-use crate::config::SimConfig;
+use crate::aggregate::StreamingAggregates;
+use crate::common::SECONDS_PER_YEAR;
+use crate::config::{OutputFormat, SimConfig};
+use crate::counters;
+use crate::hedges::HedgeQueue;
 use crate::log;
+use crate::math;
 use crate::plots;
 use crate::raw_data;
 use crate::setup;
-use crate::spreadsheetorizer::{DiskWritable, Spreadsheet};
+use crate::spreadsheetorizer::{DiskWritable, StreamingWriter};
 use crate::step;
+use crate::summary;
 use crate::task;
 
 /// Runs the simulation using the config and logs the data to `out_data`.
 ///
 /// # Errors
+/// - The `arbiter.toml` config is missing, does not deserialize, or fails validation.
 /// - The `out_data` directory does not exist.
 pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Simulation config defines the key parameters that are being used to generate data.
-    let sim_config = SimConfig::new().unwrap_or(SimConfig::default());
+    let sim_config = SimConfig::new()?;
+
+    // Streaming runs only keep the aggregates, so there are no rows to write.
+    if sim_config.log.streaming {
+        let (aggregates, _) = run_streaming(&sim_config).await?;
+        aggregates.print();
+        counters::print();
+        return Ok(());
+    }
+
+    let output = log::OutputStorage {
+        output_path: String::from(OUTPUT_DIRECTORY),
+        output_file_names: String::from(OUTPUT_FILE_NAME),
+    };
+
+    // Incremental runs write the csv as they go, and only keep the last rows for the plots.
+    if sim_config.log.write_incrementally {
+        let path = format!(
+            "{}/{}_incremental.csv",
+            output.output_path, output.output_file_names
+        );
+        let (raw_data_container, pool_id) = run_incremental(&sim_config, &path).await?;
+        counters::print();
+        write_plots(&raw_data_container, pool_id, &sim_config)?;
+        return Ok(());
+    }
+
+    // Runs the simulation and collects all the sim data in the raw data container.
+    let (raw_data_container, pool_id) = run(&sim_config).await?;
+
+    // Summarize the key metrics of the run.
+    let summary = summary::RunSummary::from_raw_data(&raw_data_container, pool_id, &sim_config)?;
+    summary.print();
+    counters::print();
+
+    let output_format = sim_config.log.output_format;
+    let path = format!(
+        "{}/{}_pool_id_{}.{}",
+        output.output_path,
+        output.output_file_names,
+        pool_id,
+        output_format.extension()
+    );
+
+    // Write the sim data to a file in the configured format.
+    match output_format {
+        OutputFormat::Json => {
+            raw_data_container.write_json_to_disk(&path, pool_id, JsonFormat::Json)?
+        }
+        OutputFormat::Jsonl => {
+            raw_data_container.write_json_to_disk(&path, pool_id, JsonFormat::JsonLines)?
+        }
+        OutputFormat::Csv if sim_config.log.emit_wad => {
+            raw_data_container.write_to_disk_with_wad(&path, pool_id)?
+        }
+        OutputFormat::Csv => {
+            raw_data_container.write_to_disk(&path, pool_id, sim_config.log.float_precision)?
+        }
+    }
+
+    // Write the sim data as parquet too, for large runs that are slow to reload from the csv.
+    if sim_config.log.emit_parquet {
+        let parquet_path = format!(
+            "{}/{}_pool_id_{}.parquet",
+            output.output_path, output.output_file_names, pool_id
+        );
+        raw_data_container.write_parquet_to_disk(&parquet_path, pool_id)?;
+    }
+
+    // Write the peg tracking data on its own, for validating the arbitrage.
+    if sim_config.log.emit_tracking {
+        let tracking_path = format!(
+            "{}/{}_pool_id_{}_tracking.csv",
+            output.output_path, output.output_file_names, pool_id
+        );
+        raw_data_container.write_tracking_to_disk(&tracking_path, pool_id)?;
+    }
+
+    // Write the summary for the consumers of each format.
+    for format in [summary::SummaryFormat::Json, summary::SummaryFormat::Toml] {
+        let summary_path = format!(
+            "{}/{}_pool_id_{}_summary.{}",
+            output.output_path,
+            output.output_file_names,
+            pool_id,
+            format.extension()
+        );
+        summary.write(&summary_path, format)?;
+    }
+
+    // Write some plots from the data.
+    write_plots(&raw_data_container, pool_id, &sim_config)?;
+
+    Ok(())
+}
+
+/// Plots the series of the pool recorded in the raw data.
+fn write_plots(
+    raw_data_container: &raw_data::RawData,
+    pool_id: u64,
+    sim_config: &SimConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let plot = plots::Plot::from_raw_data(
+        raw_data_container,
+        pool_id,
+        visualize::plot::Display {
+            transparent: false,
+            mode: visualize::design::DisplayMode::Light,
+            show: false,
+        },
+    )?;
+    plot.validate_schema()?;
+    plot.stacked_price_plot();
+    plot.stacked_reserves_plot();
+    plot.lp_pvf_plot();
+    plot.arbitrageur_pvf_plot();
+    plot.fee_revenue_plot();
+    plot.portfolio_volume_plot();
+    plot.portfolio_volume_cumulative_plot();
+    plot.divergence_loss_plot();
+    plot.profit_rate_plot(sim_config.process.timestep * SECONDS_PER_YEAR as f64);
+
+    Ok(())
+}
+
+/// Runs the simulation loop for the given config and returns the collected raw data and the pool id.
+/// Does not write any files, which makes it useful for scenarios and tests.
+pub async fn run(
+    sim_config: &SimConfig,
+) -> Result<(raw_data::RawData, u64), Box<dyn std::error::Error>> {
+    // Deploys initial contracts and agents.
+    let mut manager = deploy(sim_config)?;
+    // Runs the sim on a fresh pool.
+    let result = run_with_manager(&mut manager, sim_config).await?;
+
+    // Simulation finish and log
+    manager.shutdown();
+
+    Ok(result)
+}
+
+/// Runs the simulation loop for the given config, folding each logged row into running aggregates
+/// instead of keeping it. Returns the aggregates and the pool id.
+pub async fn run_streaming(
+    sim_config: &SimConfig,
+) -> Result<(StreamingAggregates, u64), Box<dyn std::error::Error>> {
+    let mut manager = deploy(sim_config)?;
+    let result = run_streaming_with_manager(&mut manager, sim_config).await?;
+    manager.shutdown();
+
+    Ok(result)
+}
+
+/// Runs the simulation loop for the given config, appending each logged row to the csv at `path`.
+/// Returns the last rows of the raw data and the pool id.
+pub async fn run_incremental(
+    sim_config: &SimConfig,
+    path: &str,
+) -> Result<(raw_data::RawData, u64), Box<dyn std::error::Error>> {
+    let mut manager = deploy(sim_config)?;
+    let result = run_incremental_with_manager(&mut manager, sim_config, path).await?;
+    manager.shutdown();
+
+    Ok(result)
+}
+
+/// Runs a simulation for each config and returns the collected raw data and pool id of each run.
+/// If `reuse_deployment` is true, the contracts are deployed once and each run creates a new pool
+/// on the same deployment, with the arbitrageur's balances reset between runs.
+/// The deployment is made with the first config, so only the pool parameters and the price process
+/// should differ across the configs.
+pub async fn sweep(
+    sim_configs: &[SimConfig],
+    reuse_deployment: bool,
+) -> Result<Vec<(raw_data::RawData, u64)>, Box<dyn std::error::Error>> {
+    let mut results = Vec::new();
+
+    if !reuse_deployment {
+        for sim_config in sim_configs.iter() {
+            results.push(run(sim_config).await?);
+        }
+        return Ok(results);
+    }
+
+    let first = match sim_configs.first() {
+        Some(config) => config,
+        None => return Ok(results),
+    };
+
+    let mut manager = deploy(first)?;
+    for sim_config in sim_configs.iter() {
+        setup::reset_arbitrageur_balances(
+            &manager,
+            sim_config.economic.arbitrageur_initial_balance_f,
+        )?;
+        results.push(run_with_manager(&mut manager, sim_config).await?);
+    }
+    manager.shutdown();
+
+    Ok(results)
+}
+
+/// Creates the environment and deploys the contracts and agents used in the simulation.
+pub fn deploy(sim_config: &SimConfig) -> Result<SimulationManager, Box<dyn std::error::Error>> {
     // Create the evm god.
     let mut manager = SimulationManager::new();
     // Deploys initial contracts and agents.
-    setup::run(&mut manager, &sim_config)?;
+    setup::run(&mut manager, sim_config)?;
+    // Approve portfolio and the exchange to spend arbitrageur's tokens.
+    setup::approve_arbitrageur(&manager, sim_config)?;
+
+    Ok(manager)
+}
+
+/// Runs the simulation loop on a new pool in an already deployed environment.
+pub async fn run_with_manager(
+    manager: &mut SimulationManager,
+    sim_config: &SimConfig,
+) -> Result<(raw_data::RawData, u64), Box<dyn std::error::Error>> {
     // All sim data is collected in the raw data container.
     let mut raw_data_container = raw_data::RawData::new();
+    let pool_id = run_loop(manager, sim_config, &mut raw_data_container, &mut |_, _| {
+        Ok(())
+    })
+    .await?;
+
+    Ok((raw_data_container, pool_id))
+}
+
+/// Runs the simulation loop on a new pool in an already deployed environment,
+/// keeping only the last row of the raw data once it is folded into the aggregates.
+pub async fn run_streaming_with_manager(
+    manager: &mut SimulationManager,
+    sim_config: &SimConfig,
+) -> Result<(StreamingAggregates, u64), Box<dyn std::error::Error>> {
+    let mut raw_data_container = raw_data::RawData::new();
+    let mut aggregates = StreamingAggregates::new();
+    let pool_id = run_loop(
+        manager,
+        sim_config,
+        &mut raw_data_container,
+        &mut |raw_data, pool_id| {
+            aggregates.record(raw_data, pool_id)?;
+            raw_data.retain_last(pool_id);
+            Ok(())
+        },
+    )
+    .await?;
+
+    Ok((aggregates, pool_id))
+}
+
+/// Runs the simulation loop on a new pool in an already deployed environment,
+/// appending each logged row to the csv at `path` and only keeping the last `PLOT_WINDOW_ROWS` rows in memory.
+pub async fn run_incremental_with_manager(
+    manager: &mut SimulationManager,
+    sim_config: &SimConfig,
+    path: &str,
+) -> Result<(raw_data::RawData, u64), Box<dyn std::error::Error>> {
+    let mut raw_data_container = raw_data::RawData::new();
+    let mut writer = StreamingWriter::new(path, &sim_config.log)?;
+    let pool_id = run_loop(
+        manager,
+        sim_config,
+        &mut raw_data_container,
+        &mut |raw_data, pool_id| {
+            writer.push(raw_data, pool_id)?;
+            raw_data.retain_window(pool_id, PLOT_WINDOW_ROWS);
+            Ok(())
+        },
+    )
+    .await?;
+    writer.finish()?;
+
+    Ok((raw_data_container, pool_id))
+}
+
+/// Runs the simulation loop on a new pool, logging into `raw_data_container`,
+/// and calls `on_step` with the raw data and pool id after each step. Returns the pool id.
+async fn run_loop(
+    manager: &mut SimulationManager,
+    sim_config: &SimConfig,
+    raw_data_container: &mut raw_data::RawData,
+    on_step: &mut dyn FnMut(&mut raw_data::RawData, u64) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    // Fail early on configs that can't produce a meaningful sim.
+    sim_config.validate()?;
+    // Counts the computations of this run only.
+    counters::reset();
+    // Exchange trades the arbitrageur defers, only settled on this run.
+    let mut hedges = HedgeQueue::new();
     // Underlying price process that the sim will run on.
     let substrate = &sim_config.process;
     // Get the price vector to use for the simulation.
@@ -50,85 +343,210 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize the arbitrageur's start prices.
     setup::init_arbitrageur(arbitrageur, prices.clone()).await;
 
-    // Approve portfolio to spend arbitrageur's tokens.
-    let exchange = manager.deployed_contracts.get("exchange").unwrap();
-    let token0 = manager.deployed_contracts.get("token0").unwrap();
-    let token1 = manager.deployed_contracts.get("token1").unwrap();
-    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
-
-    // Arbitrageur approvals...
-    let mut arb_caller = calls::Caller::new(arbitrageur);
-    arb_caller
-        .approve(&token0, recast_address(portfolio.address), 0.0)
-        .res()?;
-    arb_caller
-        .approve(&token1, recast_address(portfolio.address), 0.0)
-        .res()?;
-    arb_caller
-        .approve(&token0, recast_address(exchange.address), 0.0)
-        .res()?;
-    arb_caller
-        .approve(&token1, recast_address(exchange.address), 0.0)
-        .res()?;
-
     // Simulation loop
 
     // Initialize the pool.
-    let pool_id = setup::init_pool(&manager, &sim_config)?;
+    let pool_id = setup::init_pool(manager, sim_config)?;
 
     // Add liquidity to the pool
-    setup::allocate_liquidity(&manager, pool_id)?;
+    setup::allocate_liquidity(manager, pool_id)?;
 
     // Run the first price update. This is important, as it triggers the arb detection.
-    step::run(&manager, prices[0])?;
+    step::run(
+        manager,
+        sim_config
+            .economic
+            .price_convention
+            .to_quote_per_base(prices[0]),
+    )?;
 
-    // Logs initial simulation state.
-    log::run(&manager, &mut raw_data_container, pool_id)?;
+    // Logs initial simulation state, which is before the arbitrageur has acted.
+    if sim_config.log.include_warmup && log::run(manager, raw_data_container, pool_id, sim_config)?
+    {
+        raw_data_container.add_arbitrageur_gas(pool_id, 0, 0.0);
+        raw_data_container.add_swap_retries(pool_id, None);
+        raw_data_container.add_liquidity_too_low(pool_id, false);
+        raw_data_container.add_unhedged(pool_id, false);
+        raw_data_container.add_pending_exposure(pool_id, 0.0);
+        log::record_fee_revenue(raw_data_container, pool_id, None);
+        log::record_volume(raw_data_container, pool_id, None, 0.0);
+    }
+    on_step(raw_data_container, pool_id)?;
 
     println!("{}", "Running...".bright_yellow());
     for (i, price) in prices.iter().skip(1).enumerate() {
-        if std::env::var("VERBOSE").is_ok() {
+        if math::verbose() {
             println!("====== Sim step: {}, price: {} =========", i, price);
         }
 
-        // Run's the arbitrageur's task given the next desired tx.
-        task::run(&manager, *price, pool_id)?;
+        // Passes the step's time, so a non-perpetual pool's tau decreases. Perpetual pools keep a constant tau.
+        if !sim_config.economic.pool_is_perpetual {
+            step::advance_time(manager, sim_config.process.timestep);
+        }
 
-        // Logs the simulation data.
-        log::run(&manager, &mut raw_data_container, pool_id)?;
+        // Replenishes the arbitrageur's inventory at the configured cadence.
+        if sim_config.arbitrageur.is_topup_step(i) {
+            setup::top_up_arbitrageur(manager, sim_config.arbitrageur.topup_amount_f)?;
+        }
 
-        // Increments the simulation forward.
-        step::run(&manager, *price)?;
+        // Sets the pool's fee for the step from the reference prices so far, before the arbitrageur reads it.
+        if sim_config.fee_schedule.is_dynamic() {
+            let fee = sim_config
+                .fee_schedule
+                .fee_basis_points(sim_config.economic.pool_fee_basis_points, &prices[..i + 2]);
+            let priority_fee = sim_config.economic.pool_priority_fee_basis_points.min(fee);
+            setup::set_pool_fee(manager, pool_id, priority_fee, fee)?;
+        }
+
+        // Run's the arbitrageur's task given the next desired tx, logs the simulation data,
+        // and increments the simulation forward.
+        task::run_with_price(
+            manager,
+            raw_data_container,
+            &mut hedges,
+            i,
+            *price,
+            pool_id,
+            sim_config,
+        )?;
+
+        // Snapshots the pool's trading curve at the configured cadence.
+        if sim_config.log.snapshot_every > 0 && i % sim_config.log.snapshot_every == 0 {
+            log::snapshot_curve(manager, raw_data_container, pool_id, sim_config, i)?;
+        }
+
+        on_step(raw_data_container, pool_id)?;
     }
 
-    let output = log::OutputStorage {
-        output_path: String::from(OUTPUT_DIRECTORY),
-        output_file_names: String::from(OUTPUT_FILE_NAME),
-    };
+    Ok(pool_id)
+}
 
-    let path = format!(
-        "{}/{}_pool_id_{}.csv",
-        output.output_path, output.output_file_names, pool_id
-    );
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Write the sim data to a file.
-    raw_data_container.write_to_disk(&path, pool_id)?;
+    #[tokio::test]
+    async fn volatility_fee_schedule_raises_pool_fee() {
+        let mut config = SimConfig::default();
+        config.fee_schedule.schedule_type = crate::config::FeeScheduleType::Volatility;
+        config.fee_schedule.window_steps = 5;
+        config.fee_schedule.volatility_multiplier = 1.0;
 
-    // Write some plots from the data.
-    let plot = plots::Plot::new(
-        visualize::plot::Display {
-            transparent: false,
-            mode: visualize::design::DisplayMode::Light,
-            show: false,
-        },
-        raw_data_container.to_spreadsheet(pool_id),
-    );
-    plot.stacked_price_plot();
-    plot.lp_pvf_plot();
-    plot.arbitrageur_pvf_plot();
+        let (raw_data, pool_id) = run(&config).await.unwrap();
+        let fees = raw_data
+            .get_pool_data(pool_id)
+            .unwrap()
+            .iter()
+            .map(|pool| pool.fee_basis_points)
+            .collect::<Vec<u16>>();
 
-    // Simulation finish and log
-    manager.shutdown();
+        // The warmup row has the fee the pool was created with, the prices' volatility raises it after.
+        assert_eq!(fees[0], config.economic.pool_fee_basis_points);
+        assert!(fees
+            .iter()
+            .any(|fee| *fee > config.economic.pool_fee_basis_points));
+        assert!(fees
+            .iter()
+            .all(|fee| *fee <= config.fee_schedule.max_fee_basis_points));
+    }
 
-    Ok(())
+    #[tokio::test]
+    async fn topups_replenish_arbitrageur_at_cadence() {
+        let mut config = SimConfig::default();
+        let (without_topups, _) = run(&config).await.unwrap();
+
+        config.arbitrageur.topup_amount_f = 10.0;
+        config.arbitrageur.topup_every = 2;
+        let (with_topups, _) = run(&config).await.unwrap();
+
+        // The swaps are sized by the pool, so the balances only differ by the top ups so far.
+        // Row 0 is the warmup, row r is logged on step r - 1, after that step's top up.
+        for token in ["token0", "token1"] {
+            let without = without_topups.get_arbitrageur_balance_float(token).unwrap();
+            let with = with_topups.get_arbitrageur_balance_float(token).unwrap();
+            assert_eq!(with.len(), without.len());
+
+            for row in 0..with.len() {
+                let topups = if row == 0 { 0 } else { (row - 1) / 2 };
+                assert!(
+                    (with[row] - without[row] - 10.0 * topups as f64).abs() < 1e-9,
+                    "{} row {}: {} vs. {}",
+                    token,
+                    row,
+                    with[row],
+                    without[row]
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn excluding_warmup_skips_first_row() {
+        let mut config = SimConfig::default();
+        let (with_warmup, pool_id) = run(&config).await.unwrap();
+
+        config.log.include_warmup = false;
+        let (without_warmup, _) = run(&config).await.unwrap();
+
+        let with_warmup = with_warmup.get_reported_price(pool_id).unwrap();
+        let without_warmup = without_warmup.get_reported_price(pool_id).unwrap();
+
+        assert_eq!(without_warmup.len(), with_warmup.len() - 1);
+        assert_eq!(without_warmup, with_warmup[1..].to_vec());
+    }
+
+    #[tokio::test]
+    async fn warm_deployment_is_reused_across_runs() {
+        let config = SimConfig::default();
+        let mut manager = deploy(&config).unwrap();
+        let portfolio = manager.deployed_contracts.get("portfolio").unwrap().address;
+
+        let (_, first_pool_id) = run_with_manager(&mut manager, &config).await.unwrap();
+        setup::reset_arbitrageur_balances(&manager, config.economic.arbitrageur_initial_balance_f)
+            .unwrap();
+        let (_, second_pool_id) = run_with_manager(&mut manager, &config).await.unwrap();
+
+        // Same portfolio, but each run got its own pool.
+        assert_eq!(
+            manager.deployed_contracts.get("portfolio").unwrap().address,
+            portfolio
+        );
+        assert_ne!(first_pool_id, second_pool_id);
+        manager.shutdown();
+
+        let results = sweep(&[config.clone(), config], true).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_ne!(results[0].1, results[1].1);
+    }
+
+    #[tokio::test]
+    async fn run_loop_decays_tau_of_non_perpetual_pools_only() {
+        let mut config = SimConfig::default();
+        let duration_sec = config.economic.pool_time_remaining_years_f * SECONDS_PER_YEAR as f64;
+        let step_sec = (config.process.timestep * SECONDS_PER_YEAR as f64) as u64;
+        let decayed_sec = (step_sec * config.process.num_steps as u64) as f64;
+
+        for is_perpetual in [true, false] {
+            config.economic.pool_is_perpetual = is_perpetual;
+            let mut manager = deploy(&config).unwrap();
+            let (_, pool_id) = run_with_manager(&mut manager, &config).await.unwrap();
+
+            let time_remaining_sec = log::live_curve(&manager, pool_id)
+                .unwrap()
+                .time_remaining_sec;
+            let expected_sec = if is_perpetual {
+                duration_sec
+            } else {
+                duration_sec - decayed_sec
+            };
+            assert!(
+                (time_remaining_sec - expected_sec).abs() <= 1.0,
+                "perpetual {}: {} vs. {}",
+                is_perpetual,
+                time_remaining_sec,
+                expected_sec
+            );
+            manager.shutdown();
+        }
+    }
 }
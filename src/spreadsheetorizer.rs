@@ -1,46 +1,628 @@
 use polars::prelude::*;
 /// Traits and functions for converting RawData
 /// into human readable spreadsheets!
-use std::{error::Error, fs::File};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+};
 
+use super::config::Log;
 use super::raw_data::*;
 
 /// Trait for transforming simulation data into data frames.
 pub trait Spreadsheet {
     /// Converts the raw pool series data into a spreadsheet "data frame".
-    fn to_spreadsheet(&self, key: u64) -> DataFrame;
+    /// Errors if a series of the pool was never recorded.
+    fn to_spreadsheet(&self, key: u64) -> Result<DataFrame, Box<dyn Error>>;
+
+    /// Converts the raw pool series data into a spreadsheet, with the raw wad series
+    /// as decimal strings in parallel `_wad` columns so they keep their full precision.
+    fn to_spreadsheet_with_wad(&self, key: u64) -> Result<DataFrame, Box<dyn Error>>;
+
+    /// Pairs the reference price path with the pool's reported price and their difference at each step,
+    /// isolating how closely the arbitrageur keeps the pool pegged.
+    fn to_tracking_spreadsheet(&self, key: u64) -> Result<DataFrame, Box<dyn Error>>;
 }
 
 /// Implementation of the Spreadsheet trait for RawData.
 impl Spreadsheet for RawData {
-    fn to_spreadsheet(&self, pool_id: u64) -> DataFrame {
-        df!(
-            "reserves_x" => self.get_pool_x_per_lq_float(pool_id),
-            "reserves_y" => self.get_pool_y_per_lq_float(pool_id),
-            "reported_price" => self.get_reported_price_float(pool_id),
-            "ref_price" => self.get_exchange_price_float(pool_id),
-            "pvf" => self.get_portfolio_value_float(pool_id),
-            "invariant" => self.get_invariant_float(pool_id),
-            "arb_reserve_x" => self.get_arber_reserve_x_float(),
-            "arb_reserve_y" => self.get_arber_reserve_y_float(),
-            "arb_pvf" => self.get_arber_portfolio_value_float(pool_id),
-        )
-        .unwrap()
+    fn to_spreadsheet(&self, pool_id: u64) -> Result<DataFrame, Box<dyn Error>> {
+        let spreadsheet = df!(
+            "step" => self.get_step_index(pool_id)?,
+            "reserves_x" => self.get_pool_x_per_lq_float(pool_id)?,
+            "reserves_y" => self.get_pool_y_per_lq_float(pool_id)?,
+            "reserves_x_abs" => self.get_pool_x_total_float(pool_id)?,
+            "reserves_y_abs" => self.get_pool_y_total_float(pool_id)?,
+            "reported_price" => self.get_reported_price_float(pool_id)?,
+            "ref_price" => self.get_exchange_price_float(pool_id)?,
+            "pvf" => self.get_portfolio_value_float(pool_id)?,
+            "pvf_theoretical" => self.get_portfolio_value_theoretical(pool_id)?,
+            "divergence_loss" => self.get_divergence_loss(pool_id)?,
+            "invariant" => self.get_invariant_float(pool_id)?,
+            "arb_reserve_x" => self.get_arber_reserve_x_float()?,
+            "arb_reserve_y" => self.get_arber_reserve_y_float()?,
+            "arb_pvf" => self.get_arber_portfolio_value_float(pool_id)?,
+            "arb_profit" => self.get_arb_profit(pool_id)?,
+            "fee_revenue" => self.get_fee_revenue_float(pool_id)?,
+            "reserves_valid" => self.get_reserves_valid(pool_id)?,
+            "arb_fee_bps" => self.get_arbitrageur_fee_basis_points(pool_id)?,
+            "arb_gas_cost" => self.get_arbitrageur_gas_cost(pool_id)?,
+            "swap_retries" => self.get_swap_retries(pool_id)?,
+            "liquidity_too_low" => self.get_liquidity_too_low(pool_id)?,
+            "unhedged" => self.get_unhedged(pool_id)?,
+            "pending_exposure" => self.get_pending_exposure(pool_id)?,
+            "volume" => self.get_volume(pool_id)?,
+            "last_timestamp" => self.get_last_timestamp(pool_id)?,
+            "price_regime" => self.get_price_regimes(pool_id)?,
+            "arb_net_profit" => self.get_arbitrageur_net_profit_float(pool_id)?,
+        )?;
+
+        Ok(spreadsheet)
+    }
+
+    fn to_spreadsheet_with_wad(&self, pool_id: u64) -> Result<DataFrame, Box<dyn Error>> {
+        let pool_data = self.get_pool_data(pool_id)?;
+        let wad_columns = df!(
+            "reserves_x_wad" => to_decimal_strings(pool_data.iter().map(|p| p.virtual_x)),
+            "reserves_y_wad" => to_decimal_strings(pool_data.iter().map(|p| p.virtual_y)),
+            "liquidity_wad" => to_decimal_strings(pool_data.iter().map(|p| p.liquidity)),
+            "reported_price_wad" => to_decimal_strings(self.get_reported_price(pool_id)?),
+            "ref_price_wad" => to_decimal_strings(self.get_exchange_price(pool_id)?),
+            "invariant_wad" => to_decimal_strings(self.get_invariant(pool_id)?),
+            "pvf_wad" => to_decimal_strings(self.get_portfolio_value_wad(pool_id)?),
+            "arb_reserve_x_wad" => to_decimal_strings(self.get_arbitrageur_balance("token0")?),
+            "arb_reserve_y_wad" => to_decimal_strings(self.get_arbitrageur_balance("token1")?),
+        )?;
+
+        Ok(self
+            .to_spreadsheet(pool_id)?
+            .hstack(wad_columns.get_columns())?)
+    }
+
+    fn to_tracking_spreadsheet(&self, pool_id: u64) -> Result<DataFrame, Box<dyn Error>> {
+        let reference = self.get_exchange_price_float(pool_id)?;
+        let reported = self.get_reported_price_float(pool_id)?;
+        let difference = reported
+            .iter()
+            .zip(reference.iter())
+            .map(|(reported, reference)| reported - reference)
+            .collect::<Vec<f64>>();
+
+        let spreadsheet = df!(
+            "step" => self.get_step_index(pool_id)?,
+            "ref_price" => reference,
+            "reported_price" => reported,
+            "difference" => difference,
+        )?;
+
+        Ok(spreadsheet)
+    }
+}
+
+/// Formats each value as a decimal string, which holds 256 bit integers that floats and i64 columns can't.
+fn to_decimal_strings<T: ToString>(values: impl IntoIterator<Item = T>) -> Vec<String> {
+    values.into_iter().map(|value| value.to_string()).collect()
+}
+
+/// Rounds every float column of the data frame to `decimals` decimal places.
+fn round_floats(mut dataframe: DataFrame, decimals: usize) -> Result<DataFrame, Box<dyn Error>> {
+    let scale = 10f64.powi(decimals as i32);
+    let float_columns = dataframe
+        .get_columns()
+        .iter()
+        .filter(|column| column.dtype() == &DataType::Float64)
+        .map(|column| column.name().to_string())
+        .collect::<Vec<String>>();
+
+    for name in float_columns {
+        let rounded = dataframe
+            .column(&name)?
+            .f64()?
+            .apply(|value| (value * scale).round() / scale)
+            .into_series();
+        dataframe.replace(&name, rounded)?;
+    }
+
+    Ok(dataframe)
+}
+
+/// Extension of the path, if it has one.
+fn extension_of(path: &str) -> Option<&str> {
+    std::path::Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+}
+
+/// JSON format implied by the path's extension, if it is a JSON extension.
+fn json_format_of(path: &str) -> Option<JsonFormat> {
+    match extension_of(path) {
+        Some("json") => Some(JsonFormat::Json),
+        Some("jsonl") | Some("ndjson") => Some(JsonFormat::JsonLines),
+        _ => None,
     }
 }
 
 pub trait DiskWritable {
-    fn write_to_disk(&self, path: &str, key: u64) -> Result<(), Box<dyn Error>>;
+    /// Writes the spreadsheet in the format of the path's extension: parquet for `.parquet`,
+    /// a JSON array for `.json`, newline delimited JSON for `.jsonl` or `.ndjson`, and csv otherwise.
+    /// Float columns of the text formats are rounded to `precision` decimal places, or written in full precision if `None`.
+    /// Parquet is typed, so it is always written in full precision, see `write_parquet_to_disk`.
+    fn write_to_disk(
+        &self,
+        path: &str,
+        key: u64,
+        precision: Option<usize>,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Writes the spreadsheet as JSON in `format`, with the same column names as the csv.
+    fn write_json_to_disk(
+        &self,
+        path: &str,
+        key: u64,
+        format: JsonFormat,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Writes the spreadsheet with the raw wad columns as a csv.
+    fn write_to_disk_with_wad(&self, path: &str, key: u64) -> Result<(), Box<dyn Error>>;
+
+    /// Writes the spreadsheet as an Arrow IPC file, which analysis tooling reads without parsing.
+    fn write_arrow_ipc(&self, path: &str, key: u64) -> Result<(), Box<dyn Error>>;
+
+    /// Writes the spreadsheet as a parquet file, compressed and typed, so large runs reload quickly.
+    fn write_parquet_to_disk(&self, path: &str, key: u64) -> Result<(), Box<dyn Error>>;
+
+    /// Writes the reference and reported price tracking spreadsheet as a csv.
+    fn write_tracking_to_disk(&self, path: &str, key: u64) -> Result<(), Box<dyn Error>>;
 }
 
 impl<T: Spreadsheet> DiskWritable for T {
-    fn write_to_disk(&self, path: &str, key: u64) -> Result<(), Box<dyn Error>> {
-        let mut dataframe = self.to_spreadsheet(key);
+    fn write_to_disk(
+        &self,
+        path: &str,
+        key: u64,
+        precision: Option<usize>,
+    ) -> Result<(), Box<dyn Error>> {
+        if extension_of(path) == Some("parquet") {
+            return self.write_parquet_to_disk(path, key);
+        }
+
+        let mut dataframe = self.to_spreadsheet(key)?;
+        if let Some(decimals) = precision {
+            dataframe = round_floats(dataframe, decimals)?;
+        }
 
         let file = File::create(path)?;
-        let mut writer = CsvWriter::new(file);
-        writer.finish(&mut dataframe).unwrap();
+        match json_format_of(path) {
+            Some(format) => JsonWriter::new(file)
+                .with_json_format(format)
+                .finish(&mut dataframe)?,
+            None => CsvWriter::new(file).finish(&mut dataframe)?,
+        }
+
+        Ok(())
+    }
+
+    fn write_json_to_disk(
+        &self,
+        path: &str,
+        key: u64,
+        format: JsonFormat,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut dataframe = self.to_spreadsheet(key)?;
+
+        let file = File::create(path)?;
+        JsonWriter::new(file)
+            .with_json_format(format)
+            .finish(&mut dataframe)?;
+
+        Ok(())
+    }
+
+    fn write_to_disk_with_wad(&self, path: &str, key: u64) -> Result<(), Box<dyn Error>> {
+        let mut dataframe = self.to_spreadsheet_with_wad(key)?;
+
+        let file = File::create(path)?;
+        CsvWriter::new(file).finish(&mut dataframe)?;
+
+        Ok(())
+    }
+
+    fn write_arrow_ipc(&self, path: &str, key: u64) -> Result<(), Box<dyn Error>> {
+        let mut dataframe = self.to_spreadsheet(key)?;
+
+        let file = File::create(path)?;
+        IpcWriter::new(file).finish(&mut dataframe)?;
+
+        Ok(())
+    }
+
+    fn write_parquet_to_disk(&self, path: &str, key: u64) -> Result<(), Box<dyn Error>> {
+        let mut dataframe = self.to_spreadsheet(key)?;
+
+        let file = File::create(path)?;
+        ParquetWriter::new(file).finish(&mut dataframe)?;
+
+        Ok(())
+    }
+
+    fn write_tracking_to_disk(&self, path: &str, key: u64) -> Result<(), Box<dyn Error>> {
+        let mut dataframe = self.to_tracking_spreadsheet(key)?;
+
+        let file = File::create(path)?;
+        CsvWriter::new(file).finish(&mut dataframe)?;
+
+        Ok(())
+    }
+}
+
+/// # StreamingWriter
+/// Appends each recorded row of a pool to a csv as the sim runs, so long runs don't hold every row in memory.
+/// Writes the same csv as `write_to_disk`, or `write_to_disk_with_wad` if `emit_wad`, would for the full run.
+///
+/// # Fields
+/// * `writer` - Buffered csv file, flushed every `flush_every` rows and on `finish`. (BufWriter<File>)
+/// * `flush_every` - Number of rows written between flushes. (usize)
+/// * `emit_wad` - Adds the raw wad columns to each row, see `Spreadsheet::to_spreadsheet_with_wad`. (bool)
+/// * `float_precision` - Decimal places the float columns are rounded to, unless `emit_wad`. Full precision if `None`. (Option<usize>)
+/// * `rows_written` - Number of rows written so far. (usize)
+/// * `last_step` - Step index of the last written row, so a row is never written twice. (Option<u64>)
+/// * `initial_arb_value` - Arbitrageur's portfolio value of the first row, the baseline of its net profit. (Option<f64>)
+/// * `cumulative_gas_cost` - Arbitrageur's gas cost summed over the rows after the first. (f64)
+pub struct StreamingWriter {
+    writer: BufWriter<File>,
+    flush_every: usize,
+    emit_wad: bool,
+    float_precision: Option<usize>,
+    rows_written: usize,
+    last_step: Option<u64>,
+    initial_arb_value: Option<f64>,
+    cumulative_gas_cost: f64,
+}
+
+impl StreamingWriter {
+    pub fn new(path: &str, log: &Log) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            flush_every: log.flush_every.max(1),
+            emit_wad: log.emit_wad,
+            float_precision: log.float_precision,
+            rows_written: 0,
+            last_step: None,
+            initial_arb_value: None,
+            cumulative_gas_cost: 0.0,
+        })
+    }
+
+    /// Appends the last recorded row of the pool to the csv.
+    /// Does nothing if the row was already written, e.g. a step that was not recorded in on change only mode.
+    pub fn push(&mut self, raw_data: &RawData, pool_id: u64) -> Result<(), Box<dyn Error>> {
+        let step = match raw_data.step_indices.get(&pool_id).and_then(|s| s.last()) {
+            Some(step) => *step,
+            None => return Ok(()),
+        };
+        if self.last_step == Some(step) {
+            return Ok(());
+        }
+        self.last_step = Some(step);
+
+        let last_row = raw_data.last_row(pool_id);
+        let mut row = if self.emit_wad {
+            last_row.to_spreadsheet_with_wad(pool_id)?
+        } else {
+            last_row.to_spreadsheet(pool_id)?
+        };
+
+        // The net profit is relative to the first row, which may no longer be in memory.
+        let arb_value = row.column("arb_pvf")?.f64()?.get(0).unwrap_or(0.0);
+        let gas_cost = row.column("arb_gas_cost")?.f64()?.get(0).unwrap_or(0.0);
+        let initial = *self.initial_arb_value.get_or_insert(arb_value);
+        if self.rows_written > 0 {
+            self.cumulative_gas_cost += gas_cost;
+        }
+        row.with_column(Series::new(
+            "arb_net_profit",
+            [arb_value - initial - self.cumulative_gas_cost],
+        ))?;
+        if let (false, Some(decimals)) = (self.emit_wad, self.float_precision) {
+            row = round_floats(row, decimals)?;
+        }
+
+        CsvWriter::new(&mut self.writer)
+            .has_header(self.rows_written == 0)
+            .finish(&mut row)?;
+        self.rows_written += 1;
+
+        if self.rows_written % self.flush_every == 0 {
+            self.writer.flush()?;
+        }
 
         Ok(())
     }
+
+    /// Flushes the rows still buffered and returns the number of rows written.
+    pub fn finish(mut self) -> Result<usize, Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(self.rows_written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SimConfig;
+    use crate::sim;
+    use crate::task::PriceRegime;
+    use arbiter::utils::float_to_wad;
+    use bindings::i_portfolio::PoolsReturn;
+    use ethers::types::{Address, I256};
+
+    /// Records a row of every series of a pool for each reference price by hand, instead of running the sim.
+    /// The values have more decimal places than the rounding tests round to.
+    fn recorded_rows(prices: &[f64]) -> (RawData, u64) {
+        let pool_id = 0;
+        let mut raw_data = RawData::new();
+        for (step, price) in prices.iter().enumerate() {
+            let reserve_x = 0.5 / price;
+            let reserve_y = 0.5 * price;
+            let pvf = reserve_x * price + reserve_y;
+            let pool_data = PoolsReturn {
+                virtual_x: float_to_wad(reserve_x).as_u128(),
+                virtual_y: float_to_wad(reserve_y).as_u128(),
+                liquidity: float_to_wad(1.0).as_u128(),
+                fee_basis_points: 10,
+                priority_fee_basis_points: 0,
+                last_timestamp: 0,
+                controller: Address::zero(),
+                strategy: Address::zero(),
+            };
+
+            raw_data.add_step_index(pool_id, step as u64);
+            raw_data.add_pool_data(pool_id, pool_data);
+            raw_data.add_reported_price(pool_id, float_to_wad(price * 1.001));
+            raw_data.add_exchange_price(pool_id, float_to_wad(*price));
+            raw_data.add_invariant(pool_id, I256::from(step as i64 * 1_000));
+            raw_data.add_portfolio_value(pool_id, float_to_wad(pvf));
+            raw_data.add_pool_portfolio_value(pool_id, pvf);
+            raw_data.add_pool_portfolio_value_theoretical(pool_id, pvf);
+            raw_data.add_divergence_loss(pool_id, pvf - 1.0);
+            raw_data.add_arbitrageur_balance(
+                "token0".to_string(),
+                float_to_wad(50.0 + step as f64 / 7.0),
+            );
+            raw_data.add_arbitrageur_balance(
+                "token1".to_string(),
+                float_to_wad(50.0 - step as f64 / 7.0),
+            );
+            raw_data.add_arbitrageur_portfolio_value(pool_id, 100.0 + step as f64 / 3.0);
+            raw_data.add_arb_profit(pool_id, if step == 0 { 0.0 } else { 1.0 / 3.0 });
+            raw_data.add_fee_revenue(pool_id, float_to_wad(step as f64 * 0.0003));
+            raw_data.add_reserves_valid(pool_id, true);
+            raw_data.add_arbitrageur_fee_basis_points(pool_id, 10);
+            raw_data.add_arbitrageur_gas(pool_id, 21_000, 0.0);
+            raw_data.add_swap_retries(pool_id, Some(0));
+            raw_data.add_liquidity_too_low(pool_id, false);
+            raw_data.add_unhedged(pool_id, false);
+            raw_data.add_pending_exposure(pool_id, 0.0);
+            raw_data.add_volume(pool_id, step as f64 / 9.0);
+            raw_data.add_price_regime(pool_id, PriceRegime::InBand);
+        }
+
+        (raw_data, pool_id)
+    }
+
+    /// Reference prices of the hand recorded rows.
+    const PRICES: [f64; 5] = [1.0, 1.0123456, 1.1, 0.95, 1.05];
+
+    #[test]
+    fn arrow_ipc_matches_csv() {
+        let (raw_data, pool_id) = recorded_rows(&PRICES);
+
+        let dir = std::env::temp_dir();
+        let csv_path = dir.join("proto_sim_export.csv");
+        let ipc_path = dir.join("proto_sim_export.arrow");
+        let csv_path = csv_path.to_str().unwrap();
+        let ipc_path = ipc_path.to_str().unwrap();
+
+        raw_data.write_to_disk(csv_path, pool_id, None).unwrap();
+        raw_data.write_arrow_ipc(ipc_path, pool_id).unwrap();
+
+        let csv = CsvReader::from_path(csv_path).unwrap().finish().unwrap();
+        let ipc = IpcReader::new(File::open(ipc_path).unwrap())
+            .finish()
+            .unwrap();
+
+        assert_eq!(ipc.get_column_names(), csv.get_column_names());
+        assert_eq!(ipc.height(), csv.height());
+    }
+
+    #[test]
+    fn parquet_round_trips_csv_columns() {
+        let (raw_data, pool_id) = recorded_rows(&PRICES);
+
+        let dir = std::env::temp_dir();
+        let csv_path = dir.join("proto_sim_export_parquet.csv");
+        let parquet_path = dir.join("proto_sim_export.parquet");
+        let csv_path = csv_path.to_str().unwrap();
+        let parquet_path = parquet_path.to_str().unwrap();
+
+        raw_data.write_to_disk(csv_path, pool_id, None).unwrap();
+        // The precision only rounds the text formats, so the parquet still matches the full precision csv.
+        raw_data
+            .write_to_disk(parquet_path, pool_id, Some(4))
+            .unwrap();
+
+        let csv = CsvReader::from_path(csv_path).unwrap().finish().unwrap();
+        let parquet = ParquetReader::new(File::open(parquet_path).unwrap())
+            .finish()
+            .unwrap();
+
+        assert_eq!(parquet.get_column_names(), csv.get_column_names());
+        assert_eq!(parquet.height(), csv.height());
+        for column in csv.get_columns() {
+            if column.dtype() == &DataType::Float64 {
+                assert!(
+                    column.series_equal_missing(parquet.column(column.name()).unwrap()),
+                    "column {} differs",
+                    column.name()
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn incremental_csv_matches_in_memory_csv() {
+        let mut config = SimConfig::default();
+        config.log.flush_every = 3;
+
+        let dir = std::env::temp_dir();
+        let csv_path = dir.join("proto_sim_export_in_memory.csv");
+        let incremental_path = dir.join("proto_sim_export_incremental.csv");
+        let csv_path = csv_path.to_str().unwrap();
+        let incremental_path = incremental_path.to_str().unwrap();
+
+        // Full precision, rounded, and with the wad columns, which are never rounded.
+        for (emit_wad, float_precision) in [(false, None), (false, Some(4)), (true, Some(4))] {
+            config.log.emit_wad = emit_wad;
+            config.log.float_precision = float_precision;
+
+            let (raw_data, pool_id) = sim::run(&config).await.unwrap();
+            if emit_wad {
+                raw_data.write_to_disk_with_wad(csv_path, pool_id).unwrap();
+            } else {
+                raw_data
+                    .write_to_disk(csv_path, pool_id, float_precision)
+                    .unwrap();
+            }
+            sim::run_incremental(&config, incremental_path)
+                .await
+                .unwrap();
+
+            assert_eq!(
+                std::fs::read_to_string(incremental_path).unwrap(),
+                std::fs::read_to_string(csv_path).unwrap(),
+                "emit_wad {}, float_precision {:?}",
+                emit_wad,
+                float_precision
+            );
+        }
+    }
+
+    #[test]
+    fn json_extension_writes_json_with_csv_columns() {
+        let (raw_data, pool_id) = recorded_rows(&PRICES);
+
+        let dir = std::env::temp_dir();
+        let csv_path = dir.join("proto_sim_export_json.csv");
+        let json_path = dir.join("proto_sim_export.json");
+        let csv_path = csv_path.to_str().unwrap();
+        let json_path = json_path.to_str().unwrap();
+
+        raw_data.write_to_disk(csv_path, pool_id, None).unwrap();
+        raw_data.write_to_disk(json_path, pool_id, None).unwrap();
+
+        let csv = CsvReader::from_path(csv_path).unwrap().finish().unwrap();
+        let json = JsonReader::new(File::open(json_path).unwrap())
+            .with_json_format(JsonFormat::Json)
+            .finish()
+            .unwrap();
+
+        assert!(std::fs::read_to_string(json_path).unwrap().starts_with('['));
+        assert_eq!(json.get_column_names(), csv.get_column_names());
+        assert_eq!(json.height(), csv.height());
+    }
+
+    #[test]
+    fn json_lines_writes_a_row_per_line() {
+        let (raw_data, pool_id) = recorded_rows(&PRICES);
+
+        let path = std::env::temp_dir().join("proto_sim_export.jsonl");
+        let path = path.to_str().unwrap();
+        raw_data
+            .write_json_to_disk(path, pool_id, JsonFormat::JsonLines)
+            .unwrap();
+
+        let contents = std::fs::read_to_string(path).unwrap();
+        let rows = raw_data.get_step_index(pool_id).unwrap().len();
+        assert_eq!(contents.lines().count(), rows);
+        assert!(contents.lines().all(|line| line.starts_with('{')));
+    }
+
+    #[test]
+    fn float_precision_rounds_csv_floats() {
+        let (raw_data, pool_id) = recorded_rows(&PRICES);
+
+        let path = std::env::temp_dir().join("proto_sim_export_precision.csv");
+        let path = path.to_str().unwrap();
+        raw_data.write_to_disk(path, pool_id, Some(4)).unwrap();
+
+        let csv = CsvReader::from_path(path).unwrap().finish().unwrap();
+        let reported_price = raw_data.get_reported_price_float(pool_id).unwrap();
+        let column = csv.column("reported_price").unwrap().f64().unwrap();
+
+        assert_eq!(column.len(), reported_price.len());
+        for (written, stored) in column.into_iter().zip(reported_price.iter()) {
+            let written = written.unwrap();
+            assert_eq!(written, (stored * 1e4).round() / 1e4);
+            assert!((written - stored).abs() <= 0.5e-4);
+        }
+
+        // No value is written with more than 4 decimal places.
+        let contents = std::fs::read_to_string(path).unwrap();
+        for value in contents.lines().skip(1).flat_map(|line| line.split(',')) {
+            if let Some((_, decimals)) = value.split_once('.').filter(|_| !value.contains('e')) {
+                assert!(
+                    decimals.len() <= 4,
+                    "value {} has more than 4 decimals",
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn wad_columns_parse_back_to_stored_values() {
+        let (raw_data, pool_id) = recorded_rows(&PRICES);
+        let spreadsheet = raw_data.to_spreadsheet_with_wad(pool_id).unwrap();
+
+        let reported_price = raw_data.get_reported_price(pool_id).unwrap();
+        let column = spreadsheet
+            .column("reported_price_wad")
+            .unwrap()
+            .utf8()
+            .unwrap();
+
+        assert_eq!(column.len(), reported_price.len());
+        for (parsed, stored) in column.into_iter().zip(reported_price.iter()) {
+            let parsed = ethers::types::U256::from_dec_str(parsed.unwrap()).unwrap();
+            assert_eq!(parsed, *stored);
+        }
+    }
+
+    #[test]
+    fn tracking_difference_is_reported_minus_reference() {
+        let (raw_data, pool_id) = recorded_rows(&PRICES);
+        let tracking = raw_data.to_tracking_spreadsheet(pool_id).unwrap();
+        let column = |name: &str| -> Vec<f64> {
+            tracking
+                .column(name)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_no_null_iter()
+                .collect()
+        };
+
+        let reference = column("ref_price");
+        let reported = column("reported_price");
+        let difference = column("difference");
+
+        assert_eq!(
+            difference.len(),
+            raw_data.get_reported_price(pool_id).unwrap().len()
+        );
+        for i in 0..difference.len() {
+            assert_eq!(difference[i], reported[i] - reference[i]);
+        }
+    }
 }
@@ -1,4 +1,6 @@
 use super::calls::Caller;
+use super::common::SECONDS_PER_YEAR;
+use super::event_log::{self, Event};
 use arbiter::{
     manager::SimulationManager,
     utils::{float_to_wad, recast_address},
@@ -7,7 +9,26 @@ use ethers::abi::Tokenize;
 
 /// Moves the simulation forward a step by calling `setPrice` triggering the `PriceChange` event.
 pub fn run(manager: &SimulationManager, price: f64) -> Result<(), Box<dyn std::error::Error>> {
-    let exchange = manager.deployed_contracts.get("exchange").unwrap();
+    set_venue_price(manager, "exchange", price)
+}
+
+/// Advances the EVM's block timestamp by `timestep` years, rounded down to whole seconds.
+/// Non-perpetual pools compute tau from the block timestamp, so their time remaining decreases.
+pub fn advance_time(manager: &mut SimulationManager, timestep: f64) {
+    let seconds = (timestep * SECONDS_PER_YEAR as f64) as u64;
+    manager.environment.evm.env.block.timestamp += revm::primitives::U256::from(seconds);
+}
+
+/// Sets the price of the exchange venue named `venue`, triggering its `PriceChange` event.
+pub fn set_venue_price(
+    manager: &SimulationManager,
+    venue: &str,
+    price: f64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let exchange = manager
+        .deployed_contracts
+        .get(venue)
+        .ok_or(format!("step.rs: venue {} not deployed", venue))?;
     let token = manager.deployed_contracts.get("token0").unwrap();
     let admin = manager.agents.get("admin").unwrap();
 
@@ -23,5 +44,53 @@ pub fn run(manager: &SimulationManager, price: f64) -> Result<(), Box<dyn std::e
         )?
         .res()?;
 
+    event_log::emit(Event::PriceStep {
+        venue: venue.to_string(),
+        price,
+    })?;
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SimConfig;
+    use crate::setup;
+    use arbiter::utils::wad_to_float;
+    use ethers::types::U256;
+
+    /// Reported price of the pool, as a float.
+    fn reported_price(manager: &SimulationManager, pool_id: u64) -> f64 {
+        let admin = manager.agents.get("admin").unwrap();
+        let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+        let price: U256 = Caller::new(admin)
+            .view(portfolio, "getSpotPrice", pool_id.into_tokens())
+            .unwrap();
+        wad_to_float(price)
+    }
+
+    #[test]
+    fn non_perpetual_pool_price_moves_toward_strike_as_tau_decays() {
+        let mut config = SimConfig::default();
+        config.economic.pool_is_perpetual = false;
+        config.economic.pool_time_remaining_years_f = 1.0;
+        config.economic.pool_strike_price_f = 1.0;
+        config.process.initial_price = 1.2;
+
+        let mut manager = SimulationManager::new();
+        setup::run(&mut manager, &config).unwrap();
+        let pool_id = setup::init_pool(&manager, &config).unwrap();
+        setup::allocate_liquidity(&manager, pool_id).unwrap();
+
+        // Steps a tenth of a year at a time, until a tenth of the pool's duration remains.
+        let mut distances = vec![(reported_price(&manager, pool_id) - 1.0).abs()];
+        for _ in 0..9 {
+            advance_time(&mut manager, 0.1);
+            distances.push((reported_price(&manager, pool_id) - 1.0).abs());
+        }
+
+        assert!(distances[0] > 0.1, "{:?}", distances);
+        assert!(distances.windows(2).all(|w| w[1] < w[0]), "{:?}", distances);
+    }
+}
@@ -0,0 +1,287 @@
+/// Summarizes the key outcome metrics of a simulation run.
+use colored::*;
+use ethers::types::Address;
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fs};
+
+use super::common::SECONDS_PER_YEAR;
+use super::config::SimConfig;
+use super::raw_data::{LpPerformance, RawData, RawDataError};
+
+/// # RunSummary
+/// Final metrics of a simulation run, computed from the raw data.
+///
+/// # Fields
+/// * `steps` - Number of logged steps. (usize)
+/// * `final_reported_price` - Last reported price of the pool. (f64)
+/// * `final_reference_price` - Last reference price of the exchange. (f64)
+/// * `tracking_rmse` - Root mean squared error between the reported and reference prices. (f64)
+/// * `tracking_error_median` - Median absolute error between the reported and reference prices. (f64)
+/// * `tracking_error_p95` - 95th percentile absolute error between the reported and reference prices. (f64)
+/// * `final_lp_value` - Last portfolio value of the pool. (f64)
+/// * `final_arbitrageur_value` - Last portfolio value of the arbitrageur. (f64)
+/// * `arbitrageur_profit` - Change in the arbitrageur's portfolio value over the run. (f64)
+/// * `cumulative_fees` - Fees paid into the pool over the run, valued at the reference price of each swap. (f64)
+/// * `lp_fees` - Share of the cumulative fees accrued to the LP. (f64)
+/// * `controller_fees` - Share of the cumulative fees captured by the pool's controller. (f64)
+/// * `lp_performance` - Annualized return, volatility, and sharpe ratio of the LP. (LpPerformance)
+/// * `domain_edge_hits` - Steps the pool's reserves were at the edge of the trading function's domain. (usize)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub steps: usize,
+    pub final_reported_price: f64,
+    pub final_reference_price: f64,
+    pub tracking_rmse: f64,
+    pub tracking_error_median: f64,
+    pub tracking_error_p95: f64,
+    pub final_lp_value: f64,
+    pub final_arbitrageur_value: f64,
+    pub arbitrageur_profit: f64,
+    pub cumulative_fees: f64,
+    pub lp_fees: f64,
+    pub controller_fees: f64,
+    pub lp_performance: LpPerformance,
+    pub domain_edge_hits: usize,
+}
+
+impl RunSummary {
+    /// Computes the summary of the series stored for `pool_id`.
+    /// The config's timestep maps each logged step to an amount of time.
+    /// Errors if a series of the pool was never recorded.
+    pub fn from_raw_data(
+        raw_data: &RawData,
+        pool_id: u64,
+        config: &SimConfig,
+    ) -> Result<Self, RawDataError> {
+        let seconds_per_step = config.process.timestep * SECONDS_PER_YEAR as f64;
+        let reported = raw_data.get_reported_price_float(pool_id)?;
+        let reference = raw_data.get_exchange_price_float(pool_id)?;
+        let lp_value = raw_data.get_portfolio_value_float(pool_id)?;
+        let arbitrageur_value = raw_data.get_arber_portfolio_value_float(pool_id)?;
+        let tracking_error = raw_data.tracking_error_percentiles(pool_id, &[50.0, 95.0])?;
+        let fee_split = split_fees(raw_data, pool_id, config.economic.controller_fee_share)?;
+
+        Ok(Self {
+            steps: reported.len(),
+            final_reported_price: reported.last().copied().unwrap_or(0.0),
+            final_reference_price: reference.last().copied().unwrap_or(0.0),
+            tracking_rmse: root_mean_squared_error(&reported, &reference),
+            tracking_error_median: tracking_error[0],
+            tracking_error_p95: tracking_error[1],
+            final_lp_value: lp_value.last().copied().unwrap_or(0.0),
+            final_arbitrageur_value: arbitrageur_value.last().copied().unwrap_or(0.0),
+            arbitrageur_profit: arbitrageur_value.last().copied().unwrap_or(0.0)
+                - arbitrageur_value.first().copied().unwrap_or(0.0),
+            cumulative_fees: cumulative_fees(raw_data, pool_id)?,
+            lp_fees: fee_split.lp_fees,
+            controller_fees: fee_split.controller_fees,
+            lp_performance: raw_data.lp_performance(pool_id, seconds_per_step)?,
+            domain_edge_hits: raw_data.get_domain_edge_hits(pool_id)?,
+        })
+    }
+
+    /// Writes the summary to `path` in the given format.
+    /// Floats are written with the shortest representation that round trips exactly.
+    /// Note: JSON cannot represent NaN or infinite floats, they are written as `null`.
+    pub fn write(&self, path: &str, format: SummaryFormat) -> Result<(), Box<dyn Error>> {
+        let contents = match format {
+            SummaryFormat::Json => serde_json::to_string_pretty(self)?,
+            SummaryFormat::Toml => toml::to_string_pretty(self)?,
+        };
+
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Reads a summary from `path` in the given format.
+    #[allow(unused)]
+    pub fn read(path: &str, format: SummaryFormat) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        let summary = match format {
+            SummaryFormat::Json => serde_json::from_str(&contents)?,
+            SummaryFormat::Toml => toml::from_str(&contents)?,
+        };
+
+        Ok(summary)
+    }
+
+    /// Prints the summary to the console.
+    pub fn print(&self) {
+        println!(
+            "{}\n{}\n{:#?}\n{}",
+            "Summary:".bright_yellow(),
+            "------------------".bright_yellow(),
+            self,
+            "------------------".bright_yellow()
+        );
+    }
+}
+
+/// # SummaryFormat
+/// File formats a run summary can be written as.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SummaryFormat {
+    Json,
+    Toml,
+}
+
+impl SummaryFormat {
+    /// File extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SummaryFormat::Json => "json",
+            SummaryFormat::Toml => "toml",
+        }
+    }
+}
+
+/// # FeeSplit
+/// Fees paid into the pool, split between the LP and the pool's controller.
+///
+/// # Fields
+/// * `lp_fees` - Fees accrued to the LP. (f64)
+/// * `controller_fees` - Fees captured by the controller. (f64)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeSplit {
+    pub lp_fees: f64,
+    pub controller_fees: f64,
+}
+
+/// Fees paid into the pool over the run, the last of the pool's recorded fee revenue.
+pub fn cumulative_fees(raw_data: &RawData, pool_id: u64) -> Result<f64, RawDataError> {
+    Ok(raw_data
+        .get_fee_revenue_float(pool_id)?
+        .last()
+        .copied()
+        .unwrap_or(0.0))
+}
+
+/// Fees paid into the pool in each recorded step, the increase of the pool's recorded fee revenue,
+/// which accrues each swap's fee at the step's reference price, see `log::record_fee_revenue`.
+pub fn step_fees(raw_data: &RawData, pool_id: u64) -> Result<Vec<f64>, RawDataError> {
+    let fee_revenue = raw_data.get_fee_revenue_float(pool_id)?;
+
+    Ok(fee_revenue
+        .iter()
+        .scan(0.0, |previous, accrued| {
+            let fee = accrued - *previous;
+            *previous = *accrued;
+            Some(fee)
+        })
+        .collect())
+}
+
+/// Splits the fees paid into the pool over the run between the LP and the controller.
+/// The controller captures `controller_fee_share` of the fees of the steps the pool had a controller,
+/// the LP accrues the rest.
+pub fn split_fees(
+    raw_data: &RawData,
+    pool_id: u64,
+    controller_fee_share: f64,
+) -> Result<FeeSplit, RawDataError> {
+    let pool_data = raw_data.get_pool_data(pool_id)?;
+    let share = controller_fee_share.clamp(0.0, 1.0);
+
+    Ok(step_fees(raw_data, pool_id)?.iter().enumerate().fold(
+        FeeSplit::default(),
+        |mut split, (i, fee)| {
+            if pool_data[i].controller != Address::zero() {
+                split.controller_fees += fee * share;
+                split.lp_fees += fee * (1.0 - share);
+            } else {
+                split.lp_fees += fee;
+            }
+            split
+        },
+    ))
+}
+
+/// Root mean squared error between two equal length series.
+/// Returns zero for empty series.
+pub fn root_mean_squared_error(a: &[f64], b: &[f64]) -> f64 {
+    let len = a.len().min(b.len());
+    if len == 0 {
+        return 0.0;
+    }
+
+    let sum_squared = a
+        .iter()
+        .zip(b.iter())
+        .map(|(x, y)| (x - y).powi(2))
+        .sum::<f64>();
+
+    (sum_squared / len as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_json_round_trip() {
+        let summary = RunSummary {
+            steps: 1000,
+            final_reported_price: 1.0123456789012345,
+            final_reference_price: 0.9876543210987654,
+            tracking_rmse: 0.000123456789,
+            tracking_error_median: 0.0001,
+            tracking_error_p95: 0.0003,
+            final_lp_value: 1.999999999999999,
+            final_arbitrageur_value: 100.1,
+            arbitrageur_profit: 0.1,
+            cumulative_fees: 0.0025,
+            lp_fees: 0.002,
+            controller_fees: 0.0005,
+            lp_performance: LpPerformance {
+                annualized_return: 0.05,
+                annualized_vol: 0.1,
+                sharpe: 0.5,
+            },
+            domain_edge_hits: 2,
+        };
+
+        let path = std::env::temp_dir().join("proto_sim_summary_round_trip.json");
+        let path = path.to_str().unwrap();
+        summary.write(path, SummaryFormat::Json).unwrap();
+        let read = RunSummary::read(path, SummaryFormat::Json).unwrap();
+
+        assert_eq!(read, summary);
+    }
+
+    #[test]
+    fn controller_and_lp_fees_sum_to_total() {
+        use bindings::i_portfolio::PoolsReturn;
+        use ethers::utils::parse_ether;
+
+        // The pool only has a controller after the first swap's step.
+        let mut raw_data = RawData::new();
+        for (step, fee_revenue) in [0.0, 0.003, 0.005, 0.005].into_iter().enumerate() {
+            let controller = if step < 2 {
+                Address::zero()
+            } else {
+                Address::from_low_u64_be(1)
+            };
+            raw_data.add_fee_revenue(0, parse_ether(fee_revenue).unwrap());
+            raw_data.add_pool_data(
+                0,
+                PoolsReturn {
+                    virtual_x: parse_ether(1.0).unwrap().as_u128(),
+                    virtual_y: parse_ether(1.0).unwrap().as_u128(),
+                    liquidity: parse_ether(1.0).unwrap().as_u128(),
+                    fee_basis_points: 30,
+                    priority_fee_basis_points: 5,
+                    last_timestamp: 0,
+                    controller,
+                    strategy: Address::zero(),
+                },
+            );
+        }
+
+        let total = cumulative_fees(&raw_data, 0).unwrap();
+        let split = split_fees(&raw_data, 0, 0.25).unwrap();
+
+        assert!((total - 0.005).abs() < 1e-12);
+        assert!((split.lp_fees + split.controller_fees - total).abs() < 1e-12);
+        assert!((split.controller_fees - 0.002 * 0.25).abs() < 1e-12);
+    }
+}
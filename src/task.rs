@@ -1,36 +1,117 @@
 use anyhow::anyhow;
 use arbiter::{
     agent::Agent,
+    environment::contract::{IsDeployed, SimulationContract},
     manager::SimulationManager,
-    utils::{float_to_wad, recast_address, unpack_execution},
+    utils::{float_to_wad, recast_address, unpack_execution, wad_to_float},
 };
+use colored::*;
 use ethers::{
     abi::{Tokenizable, Tokenize},
     types::*,
     utils::parse_ether,
 };
+use std::collections::HashMap;
 use std::error::Error;
 
 // dynamic, generated with compile.sh
-use bindings::{i_portfolio_actions::SwapReturn, portfolio::PoolsReturn, shared_types::Order};
+use bindings::{
+    external_normal_strategy_lib::NormalCurve as SolidityNormalCurve,
+    i_portfolio_actions::SwapReturn, portfolio::PoolsReturn, shared_types::Order,
+};
 
 use super::calls::{Caller, DecodedReturns};
 use super::common;
+use super::config::{
+    self, DirectionFilter, PreferDirection, PriceJumpPolicy, ShrinkStrategy, SimConfig,
+    TradeFailurePolicy,
+};
+use super::counters;
+use super::event_log::{self, Event};
+use super::hedges::{ExchangeTrade, HedgeQueue};
+use super::log;
+use super::math;
+use super::raw_data::{self, RawData};
+use super::step;
 
-#[allow(unused)]
+/// # SwapDirection
+/// Direction of the arbitrageur's swap on the pool. The exchange leg trades the opposite direction.
+///
+/// # Variants
+/// * `SwapXToY` - Sells x to the pool for y, lowering its reported price.
+/// * `SwapYToX` - Sells y to the pool for x, raising its reported price.
+/// * `None` - No swap.
 enum SwapDirection {
     SwapXToY,
     SwapYToX,
     None,
 }
 
-#[allow(unused)]
-fn check_no_arb_bounds(
+impl SwapDirection {
+    /// Whether the pool swap sells the asset, x. `None` if there is no swap.
+    fn sell_asset(&self) -> Option<bool> {
+        match self {
+            SwapDirection::SwapXToY => Some(true),
+            SwapDirection::SwapYToX => Some(false),
+            SwapDirection::None => None,
+        }
+    }
+}
+
+/// # PriceRegime
+/// Where a reference price is relative to the no-arb band around the pool's reported price.
+///
+/// # Variants
+/// * `InBand` - Within the band, the pool is priced within its fee and there is no arbitrage.
+/// * `ArbUp` - Outside the band and above the reported price, arbitrage raises the pool's price.
+/// * `ArbDown` - Outside the band and at or below the reported price, arbitrage lowers the pool's price.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PriceRegime {
+    InBand,
+    ArbUp,
+    ArbDown,
+}
+
+impl PriceRegime {
+    /// Name of the regime in the spreadsheet's categorical column.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PriceRegime::InBand => "in_band",
+            PriceRegime::ArbUp => "arb_up",
+            PriceRegime::ArbDown => "arb_down",
+        }
+    }
+}
+
+/// Classifies the reference price against the no-arb band around the current price,
+/// with the band's scalar `fee` in wad, see `no_arb_fee_wad`.
+pub fn classify(current_price: U256, reference_price: U256, fee: U256) -> PriceRegime {
+    classify_within(current_price, reference_price, fee, U256::zero())
+}
+
+/// Classifies the reference price, treating prices within `tolerance` of a bound, in wad, as inside the band.
+/// If the bounds are inverted, so the price is beyond both, the sign of the price difference decides.
+fn classify_within(
     current_price: U256,
-    target_price: U256,
+    reference_price: U256,
     fee: U256,
-) -> Option<SwapDirection> {
-    // Check the no-arbitrage bounds
+    tolerance: U256,
+) -> PriceRegime {
+    let (lower_arb_bound, upper_arb_bound) = no_arb_bounds(current_price, fee, tolerance);
+
+    if (reference_price > upper_arb_bound) | (reference_price < lower_arb_bound) {
+        if reference_price > current_price {
+            PriceRegime::ArbUp
+        } else {
+            PriceRegime::ArbDown
+        }
+    } else {
+        PriceRegime::InBand
+    }
+}
+
+/// Lower and upper no-arb bounds around the current price, widened by `tolerance`.
+fn no_arb_bounds(current_price: U256, fee: U256, tolerance: U256) -> (U256, U256) {
     let upper_arb_bound = current_price
         .checked_mul(parse_ether(1.0).unwrap())
         .unwrap()
@@ -42,109 +123,645 @@ fn check_no_arb_bounds(
         .checked_div(parse_ether(1.0).unwrap())
         .unwrap();
 
-    if (target_price > upper_arb_bound) | (target_price < lower_arb_bound) {
-        // If the prices are outside of the no-arbitrage bounds, then we can arbitrage.
-        let price_difference = current_price.checked_sub(target_price);
-        if price_difference.is_none() {
-            // If this difference is `None`, then the subtraction overflowed so current_price<target_price.
-            Some(SwapDirection::SwapXToY)
-        } else {
-            // If the price difference is still nonzero, then we must swap with price[0]>price[1].
-            Some(SwapDirection::SwapYToX)
+    (
+        lower_arb_bound.saturating_sub(tolerance),
+        upper_arb_bound.saturating_add(tolerance),
+    )
+}
+
+/// Checks if the target price is outside of the no-arb bounds around the current price.
+/// Prices within `tolerance` of a bound, in wad, are treated as inside the bounds,
+/// so prices on a bound deterministically resolve to no swap despite integer rounding.
+/// If the target is within `tolerance` of both bounds, e.g. the narrow band of a zero fee,
+/// the direction is ambiguous and `prefer_direction` breaks the tie.
+#[allow(unused)]
+fn check_no_arb_bounds(
+    current_price: U256,
+    target_price: U256,
+    fee: U256,
+    tolerance: U256,
+    prefer_direction: &PreferDirection,
+) -> Option<SwapDirection> {
+    // Check the no-arbitrage bounds
+    let (lower_arb_bound, upper_arb_bound) = no_arb_bounds(current_price, fee, U256::zero());
+    let near_lower = abs_diff(target_price, lower_arb_bound) <= tolerance;
+    let near_upper = abs_diff(target_price, upper_arb_bound) <= tolerance;
+
+    if near_lower && near_upper {
+        // The target is on both bounds, so use the preferred direction.
+        match prefer_direction {
+            PreferDirection::SwapXToY => return Some(SwapDirection::SwapXToY),
+            PreferDirection::SwapYToX => return Some(SwapDirection::SwapYToX),
+            PreferDirection::Sign => {}
         }
-    } else {
+    }
+
+    match classify_within(current_price, target_price, fee, tolerance) {
+        // Buying x from the pool raises its price to the target.
+        PriceRegime::ArbUp => Some(SwapDirection::SwapYToX),
+        // Selling x to the pool lowers its price to the target.
+        PriceRegime::ArbDown => Some(SwapDirection::SwapXToY),
         // Prices are within the no-arbitrage bounds, so we don't have an arbitrage.
-        Some(SwapDirection::None)
+        PriceRegime::InBand => Some(SwapDirection::None),
+    }
+}
+
+/// Absolute difference of two wad values.
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+/// Fee the pool charges `swapper`, in basis points.
+/// Portfolio charges the priority fee instead of the regular fee if the swapper is the pool's controller.
+pub fn effective_fee_basis_points(
+    controller: Address,
+    fee_basis_points: u16,
+    priority_fee_basis_points: u16,
+    swapper: Address,
+) -> u16 {
+    if controller != Address::zero() && controller == swapper {
+        priority_fee_basis_points
+    } else {
+        fee_basis_points
     }
 }
 
+/// Scalar of the no-arb bounds, in wad.
+/// Doubles the pool's fee to get the arb bounds for the arbitrageur.
+pub fn no_arb_fee_wad(fee_basis_points: u16) -> U256 {
+    U256::from(
+        (common::BASIS_POINT_DIVISOR as u128 - (fee_basis_points as u128 * 2_u128)) * 1e18 as u128
+            / common::BASIS_POINT_DIVISOR as u128,
+    )
+}
+
+/// Most swap attempts made for a single order, the first attempt included, before the arbitrageur gives up.
+const MAX_SWAP_ATTEMPTS: usize = 100;
+
+/// # StepOutcome
+/// What the arbitrageur's task did in a step.
+///
+/// # Fields
+/// * `gas_used` - Gas used by the arbitrageur's transactions. (u64)
+/// * `swap_retries` - Failed attempts before the swap went through. `None` if the arbitrageur did not swap. (Option<usize>)
+/// * `liquidity_too_low` - The arbitrageur skipped the step because the pool's liquidity was below the minimum. (bool)
+/// * `swap` - Order the arbitrageur's swap went through with. `None` if it did not swap. (Option<Order>)
+/// * `unhedged` - The exchange trade failed after the swap went through, leaving the arbitrageur's position unhedged. (bool)
+/// * `deferred` - Exchange trade hedging the swap, to settle `hedge_lag_steps` steps later. `None` if it was not deferred. (Option<ExchangeTrade>)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StepOutcome {
+    pub gas_used: u64,
+    pub swap_retries: Option<usize>,
+    pub liquidity_too_low: bool,
+    pub swap: Option<Order>,
+    pub unhedged: bool,
+    pub deferred: Option<ExchangeTrade>,
+}
+
 /// Runs the tasks for each actor in the environment
 /// Requires the arbitrageur's next desired transaction
-pub fn run(manager: &SimulationManager, price: f64, pool_id: u64) -> Result<(), anyhow::Error> {
-    let verbose = std::env::var("VERBOSE");
+/// Returns the gas used by the arbitrageur's transactions and the retries its swap took.
+pub fn run(
+    manager: &SimulationManager,
+    price: f64,
+    pool_id: u64,
+    config: &SimConfig,
+) -> Result<StepOutcome, anyhow::Error> {
+    let verbose = math::verbose();
 
     // Get the instances we need.
     let arber = manager.agents.get("arbitrageur").unwrap();
     let admin = manager.agents.get("admin").unwrap();
     let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
-    let mut caller = Caller::new(admin);
+    let caller = Caller::new(admin);
+
+    // Check if we are within the no-arb bounds.
+    let current_price_wad: U256 = caller.view(portfolio, "getSpotPrice", pool_id.into_tokens())?;
+
+    // The primary venue still holds the previous reference price, it is set after the arbitrageur acts.
+    let primary = config
+        .arbitrageur
+        .venues
+        .first()
+        .ok_or(anyhow!("task.rs: No exchange venues configured."))?;
+    let previous_price_wad = venue_price(manager, primary)?;
+
+    // Only act on reference price moves in the configured direction.
+    if !passes_direction_filter(
+        &config.arbitrageur.direction_filter,
+        previous_price_wad,
+        float_to_wad(price),
+    ) {
+        if verbose {
+            println!("Reference price move filtered out, skipping.");
+        }
+        return Ok(StepOutcome::default());
+    }
+
+    // Guards against arbing extreme jumps of the reference price.
+    let price = match guard_price_jump(wad_to_float(previous_price_wad), price, &config.arbitrageur)
+    {
+        Some(price) => price,
+        None => {
+            if verbose {
+                println!(
+                    "Reference price jump above {}%, skipping.",
+                    config.arbitrageur.max_price_jump_pct
+                );
+            }
+            return Ok(StepOutcome::default());
+        }
+    };
 
     // Collect the key variables to check for arbitrage.
-    let target_price_wad = float_to_wad(price);
+    // The primary venue is quoted at the next reference price, other venues at their current price.
+    let prices = venue_prices(manager, &config.arbitrageur.venues, float_to_wad(price))?;
+    let (venue, target_price_wad) = best_venue(&prices, current_price_wad)
+        .ok_or(anyhow!("task.rs: No exchange venues configured."))?;
 
-    // Check if we are within the no-arb bounds.
-    let current_price_wad: U256 = caller
-        .call(portfolio, "getSpotPrice", pool_id.into_tokens())?
-        .decoded(portfolio)?;
+    if verbose && prices.len() > 1 {
+        println!("Venue prices: {:#?}, best venue: {}", prices, venue);
+    }
 
-    if verbose.is_ok() {
+    if verbose {
         println!(
             "Reported price: {:#?}, Reference price: {:#?}",
             current_price_wad, target_price_wad
         );
     }
 
-    // todo: get pool fee from actual pool...
-    let pool_state = caller.call(portfolio, "pools", vec![pool_id.into_token()])?;
-    let pool_state: PoolsReturn = pool_state.decoded(portfolio)?;
+    let pool_state: PoolsReturn = caller.view(portfolio, "pools", vec![pool_id.into_token()])?;
 
-    // Doubles the pool's fee to get the arb bounds for the arbitrageur.
-    let fee = U256::from(
-        (common::BASIS_POINT_DIVISOR as u128 - (pool_state.fee_basis_points as u128 * 2_u128))
-            * 1e18 as u128
-            / common::BASIS_POINT_DIVISOR as u128,
+    // Swaps against a thin pool slip too much, so the arbitrageur leaves it alone.
+    if is_liquidity_too_low(&pool_state, &config.arbitrageur) {
+        if verbose {
+            println!(
+                "Pool liquidity {} below minimum {}, skipping.",
+                wad_to_float(U256::from(pool_state.liquidity)),
+                config.arbitrageur.min_pool_liquidity_f
+            );
+        }
+        return Ok(StepOutcome {
+            liquidity_too_low: true,
+            ..StepOutcome::default()
+        });
+    }
+
+    // The arbitrageur pays the priority fee instead if it is the pool's controller.
+    let fee_basis_points = effective_fee_basis_points(
+        pool_state.controller,
+        pool_state.fee_basis_points,
+        pool_state.priority_fee_basis_points,
+        recast_address(arber.address()),
+    );
+    let fee = no_arb_fee_wad(fee_basis_points);
+
+    if verbose {
+        println!(
+            "Fee basis points: {}, priority fee basis points: {}, arbitrageur pays: {}",
+            pool_state.fee_basis_points, pool_state.priority_fee_basis_points, fee_basis_points
+        );
+    }
+
+    let direction: Option<SwapDirection> = check_no_arb_bounds(
+        current_price_wad,
+        target_price_wad,
+        fee,
+        U256::from(config.arbitrageur.no_arb_tolerance_wad),
+        &config.arbitrageur.prefer_direction,
     );
-    let direction: Option<SwapDirection> =
-        check_no_arb_bounds(current_price_wad, target_price_wad, fee);
 
-    match direction {
+    // The direction decides which side of the pool the arbitrageur sells, and the exchange leg buys it back.
+    let sell_asset = match direction {
         Some(SwapDirection::SwapXToY) => {
-            if verbose.is_ok() {
-                println!("Swap X to Y");
+            if verbose {
+                println!(
+                    "Swap X to Y: selling x to the pool, buying it back with y on {}.",
+                    venue
+                );
             }
+            true
         }
         Some(SwapDirection::SwapYToX) => {
-            if verbose.is_ok() {
-                println!("Swap Y to X");
+            if verbose {
+                println!(
+                    "Swap Y to X: buying x from the pool with y, selling it on {}.",
+                    venue
+                );
             }
+            false
         }
-        Some(SwapDirection::None) => {
-            if verbose.is_ok() {
+        Some(SwapDirection::None) | None => {
+            if verbose {
                 println!("No swap required.");
             }
-            return Ok(());
+            return Ok(StepOutcome::default());
         }
-        None => {
-            if verbose.is_ok() {
-                println!("No swap required.");
-            }
-            return Ok(());
-        }
-    }
+    };
 
     // Fetches the swap order required to move the portfolio pool's reported price to `target_price_wad`.
-    let swap_order = get_swap_order(manager, pool_id, target_price_wad);
+    let swap_order = get_swap_order(
+        manager,
+        pool_id,
+        target_price_wad,
+        sell_asset,
+        &config.arbitrageur.prefer_direction,
+    );
     let swap_order = match swap_order {
         Ok(order) => order,
         Err(e) => {
             return Err(anyhow!("task.rs: Error on getting swap order: {:#?}", e));
         }
     };
+    // The preferred direction can take the actor's side of an ambiguous arb, see `get_swap_order`.
+    let sell_asset = swap_order.sell_asset;
 
-    if verbose.is_ok() {
+    if verbose {
         println!("Swap order: {:#?}", swap_order);
     }
 
+    // An arbitrageur short of capital trades as much as it can afford instead.
+    let swap_order = cap_order_to_balance(manager, config, swap_order)?;
+
     if swap_order.input == 0 {
-        return Ok(());
+        return Ok(StepOutcome::default());
+    }
+
+    // Skips dust orders, which cost a full swap and trade for a negligible peg improvement.
+    if is_dust(&swap_order, &config.arbitrageur) {
+        if verbose {
+            println!(
+                "Swap output {} below minimum {}, skipping.",
+                swap_order.output, config.arbitrageur.min_swap_output_f
+            );
+        }
+        return Ok(StepOutcome::default());
+    }
+
+    // Front runs the arbitrageur's swap in the same direction, if sandwiching is configured.
+    let front_run_output = if config.sandwich.size_f > 0.0 {
+        Some(sandwich_swap(
+            manager,
+            pool_id,
+            sell_asset,
+            float_to_wad(config.sandwich.size_f),
+        )?)
+    } else {
+        None
+    };
+
+    let (swapped, retries, mut gas_used) =
+        swap_with_retries(manager, swap_order, &config.arbitrageur)?;
+
+    if let Some(order) = &swapped {
+        event_log::emit(Event::Swap {
+            pool_id,
+            sell_asset: order.sell_asset,
+            input: order.input,
+            output: order.output,
+            retries,
+        })?;
+    }
+
+    // Back runs the arbitrageur's swap by swapping the front run's output back.
+    if let Some(output) = front_run_output {
+        sandwich_swap(manager, pool_id, !sell_asset, output)?;
     }
 
-    let mut swap_success = false;
+    let swap_retries = swapped.as_ref().map(|_| retries);
+    let swap = swapped.clone();
+    let mut unhedged = false;
+    let mut deferred = None;
+    match swapped {
+        Some(order) if config.arbitrageur.hedge_lag_steps > 0 => {
+            // Hedges the swap some steps later, holding the position until then.
+            deferred = Some(ExchangeTrade {
+                venue: venue.clone(),
+                sell_asset: !sell_asset,
+                amount_in: order.output,
+            });
+        }
+        Some(order) => {
+            // Do the swap on the liquid exchange offering the best price.
+            // Selling the opposite side of the portfolio swap, with its output as the input,
+            // returns the token the arbitrageur paid into the pool, closing its position in the other token.
+            let (trade_call_result, trade_gas) =
+                exchange_trade(manager, &venue, !sell_asset, order.output)?;
+
+            // The portfolio swap already went through, the policy decides what happens to the open position.
+            match trade_call_result {
+                Ok(true) => {}
+                Ok(false) | Err(_)
+                    if config.arbitrageur.trade_failure_policy != TradeFailurePolicy::Abort =>
+                {
+                    if verbose {
+                        println!("Exchange trade failed, arbitrageur is unhedged.");
+                    }
+                    unhedged = true;
+                }
+                Ok(false) => return Err(anyhow!("Trade failed.")),
+                Err(e) => return Err(e),
+            }
+
+            gas_used += trade_gas;
+        }
+        None => {}
+    }
+
+    Ok(StepOutcome {
+        gas_used,
+        swap_retries,
+        liquidity_too_low: false,
+        swap,
+        unhedged,
+        deferred,
+    })
+}
+
+/// Trades `amount_in` on the exchange `venue` as the arbitrageur, selling the asset for the quote if `sell_asset`.
+/// Returns the result of the trade and the gas it used.
+fn exchange_trade(
+    manager: &SimulationManager,
+    venue: &str,
+    sell_asset: bool,
+    amount_in: u128,
+) -> Result<(Result<bool, anyhow::Error>, u64), anyhow::Error> {
+    let arber = manager.agents.get("arbitrageur").unwrap();
+    let exchange = manager
+        .deployed_contracts
+        .get(venue)
+        .ok_or(anyhow!("task.rs: venue {} not deployed", venue))?;
+    let token0 = manager.deployed_contracts.get("token0").unwrap();
+    let token1 = manager.deployed_contracts.get("token1").unwrap();
+
+    // The exchange's pair is always (asset, quote), the direction is set by `sellAsset` alone.
+    let mut exec = Caller::new(arber);
+    let result = exec
+        .call(
+            exchange,
+            "trade",
+            (
+                recast_address(token0.address),
+                recast_address(token1.address),
+                sell_asset,
+                amount_in,
+            )
+                .into_tokens(),
+        )
+        .and_then(|exec| exec.decoded(exchange));
+
+    Ok((result, exec.last_gas_used().unwrap_or(0)))
+}
+
+/// Settles the deferred exchange trades of `hedges` due on `step`, at the exchange prices of the step.
+/// A failed trade errors with the abort trade failure policy, otherwise its position stays unhedged.
+/// Returns the gas used by the trades.
+fn settle_due_trades(
+    manager: &SimulationManager,
+    hedges: &mut HedgeQueue,
+    step: usize,
+    config: &SimConfig,
+) -> Result<u64, anyhow::Error> {
+    let mut gas_used = 0;
+    for trade in hedges.take_due(step) {
+        let (result, gas) =
+            exchange_trade(manager, &trade.venue, trade.sell_asset, trade.amount_in)?;
+        gas_used += gas;
+        match result {
+            Ok(true) => {}
+            _ if config.arbitrageur.trade_failure_policy != TradeFailurePolicy::Abort => {}
+            Ok(false) => return Err(anyhow!("Deferred trade failed: {:?}", trade)),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(gas_used)
+}
+
+/// Runs a single sim step at an explicit reference price, bypassing the price process:
+/// the arbitrageur's task, logging the step with the gas it used, then setting the exchange to the price.
+/// The price is in the configured price convention.
+/// Note: the gas of a step that is not logged, in on change only mode, is not recorded.
+/// With the rollback trade failure policy, a step whose exchange trade failed is undone by restoring
+/// a snapshot of the EVM state taken before the arbitrageur's task. Its gas is still recorded.
+/// `step` is the index of the step, which the arbitrageur's schedule and its deferred exchange trades are kept by.
+/// Deferred exchange trades of `hedges` due on the step settle first, even off the arbitrageur's schedule.
+pub fn run_with_price(
+    manager: &mut SimulationManager,
+    raw_data: &mut RawData,
+    hedges: &mut HedgeQueue,
+    step: usize,
+    price: f64,
+    pool_id: u64,
+    config: &SimConfig,
+) -> Result<(), anyhow::Error> {
+    let price = config.economic.price_convention.to_quote_per_base(price);
+    let hedge_gas = settle_due_trades(manager, hedges, step, config)?;
+    // Off schedule, the arbitrageur sits out and the pool drifts from the reference price.
+    let mut outcome = if config.arbitrageur.is_scheduled(step) {
+        let snapshot = match config.arbitrageur.trade_failure_policy {
+            TradeFailurePolicy::Rollback => Some(manager.environment.evm.db.clone()),
+            _ => None,
+        };
+        let outcome = run(manager, price, pool_id, config)?;
+        match snapshot {
+            Some(snapshot) if outcome.unhedged => {
+                manager.environment.evm.db = snapshot;
+                StepOutcome {
+                    gas_used: outcome.gas_used,
+                    ..StepOutcome::default()
+                }
+            }
+            _ => outcome,
+        }
+    } else {
+        StepOutcome::default()
+    };
+    outcome.gas_used += hedge_gas;
+    if let Some(trade) = outcome.deferred.take() {
+        hedges.defer(step + config.arbitrageur.hedge_lag_steps, trade);
+    }
+    let logged = log::run(manager, raw_data, pool_id, config)
+        .map_err(|e| anyhow!("task.rs: Error on logging: {}", e))?;
+    if logged {
+        raw_data.add_arbitrageur_gas(pool_id, outcome.gas_used, config.gas.cost(outcome.gas_used));
+        raw_data.add_swap_retries(pool_id, outcome.swap_retries);
+        raw_data.add_liquidity_too_low(pool_id, outcome.liquidity_too_low);
+        raw_data.add_unhedged(pool_id, outcome.unhedged);
+        raw_data.add_pending_exposure(pool_id, hedges.exposure(price));
+        log::record_fee_revenue(raw_data, pool_id, outcome.swap.as_ref());
+        log::record_volume(raw_data, pool_id, outcome.swap.as_ref(), price);
+    }
+    step::run(manager, price).map_err(|e| anyhow!("task.rs: Error on step: {}", e))?;
+    counters::end_step();
+
+    Ok(())
+}
+
+/// Checks the arbitrageur did its job: the pool's reported price is within `tolerance` of the
+/// primary exchange's reference price, as a fraction of the reference price.
+/// Call it after a step, once the reference price is set.
+pub fn assert_converged(
+    manager: &SimulationManager,
+    pool_id: u64,
+    tolerance: f64,
+) -> Result<(), anyhow::Error> {
+    let admin = manager.agents.get("admin").unwrap();
+    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+
+    let reported: U256 =
+        Caller::new(admin).view(portfolio, "getSpotPrice", pool_id.into_tokens())?;
+    let reported = wad_to_float(reported);
+    let reference = wad_to_float(venue_price(manager, "exchange")?);
+
+    let deviation = (reported - reference).abs() / reference;
+    if deviation.is_nan() || deviation > tolerance {
+        return Err(anyhow!(
+            "task.rs: pool {} did not converge: reported price {} is {} from reference price {}, more than the tolerance {}",
+            pool_id,
+            reported,
+            deviation,
+            reference,
+            tolerance
+        ));
+    }
+
+    Ok(())
+}
+
+/// True if the order's output is below the configured minimum swap output.
+fn is_dust(order: &Order, config: &config::Arbitrageur) -> bool {
+    U256::from(order.output) < float_to_wad(config.min_swap_output_f)
+}
+
+/// True if the pool's liquidity is below the configured minimum pool liquidity.
+fn is_liquidity_too_low(pool: &PoolsReturn, config: &config::Arbitrageur) -> bool {
+    U256::from(pool.liquidity) < float_to_wad(config.min_pool_liquidity_f)
+}
+
+/// Gets the price of each venue. The primary venue, the first one, is quoted at `primary_price_wad`
+/// since its price is only set after the arbitrageur acts on it.
+fn venue_prices(
+    manager: &SimulationManager,
+    venues: &[String],
+    primary_price_wad: U256,
+) -> Result<HashMap<String, U256>, anyhow::Error> {
+    let mut prices = HashMap::new();
+    for (i, venue) in venues.iter().enumerate() {
+        if i == 0 {
+            prices.insert(venue.clone(), primary_price_wad);
+            continue;
+        }
+
+        let price = venue_price(manager, venue)?;
+        prices.insert(venue.clone(), price);
+    }
+
+    Ok(prices)
+}
+
+/// Gets the current price of the exchange `venue`, in wad.
+fn venue_price(manager: &SimulationManager, venue: &str) -> Result<U256, anyhow::Error> {
+    let admin = manager.agents.get("admin").unwrap();
+    let token0 = manager.deployed_contracts.get("token0").unwrap();
+    let exchange = manager
+        .deployed_contracts
+        .get(venue)
+        .ok_or(anyhow!("task.rs: Venue {} not deployed.", venue))?;
+
+    let price: U256 = Caller::new(admin).view(
+        exchange,
+        "getPrice",
+        recast_address(token0.address).into_tokens(),
+    )?;
+
+    Ok(price)
+}
+
+/// True if the reference price moving from `previous_price_wad` to `next_price_wad` is a move
+/// the arbitrageur acts on. An unchanged price passes every filter.
+fn passes_direction_filter(
+    filter: &DirectionFilter,
+    previous_price_wad: U256,
+    next_price_wad: U256,
+) -> bool {
+    match filter {
+        DirectionFilter::Both => true,
+        DirectionFilter::UpOnly => next_price_wad >= previous_price_wad,
+        DirectionFilter::DownOnly => next_price_wad <= previous_price_wad,
+    }
+}
+
+/// Applies the price jump circuit breaker to the move of the reference price from `previous_price` to `next_price`.
+/// Returns the price to arb towards, or `None` if the step should not be arbed.
+fn guard_price_jump(
+    previous_price: f64,
+    next_price: f64,
+    config: &config::Arbitrageur,
+) -> Option<f64> {
+    if config.max_price_jump_pct <= 0.0 || previous_price <= 0.0 {
+        return Some(next_price);
+    }
+
+    let max_jump = config.max_price_jump_pct / 100.0;
+    let jump = next_price / previous_price - 1.0;
+    if jump.abs() <= max_jump {
+        return Some(next_price);
+    }
+
+    match config.price_jump_policy {
+        PriceJumpPolicy::Skip => None,
+        PriceJumpPolicy::Clamp => Some(previous_price * (1.0 + max_jump.copysign(jump))),
+    }
+}
+
+/// Picks the venue whose price deviates the most from the pool's reported price,
+/// i.e. the venue offering the largest arbitrage edge.
+fn best_venue(prices: &HashMap<String, U256>, current_price_wad: U256) -> Option<(String, U256)> {
+    prices
+        .iter()
+        .max_by_key(|(_, price)| {
+            if **price > current_price_wad {
+                **price - current_price_wad
+            } else {
+                current_price_wad - **price
+            }
+        })
+        .map(|(venue, price)| (venue.clone(), *price))
+}
+
+/// Swaps on portfolio, shrinking the order's output after each failed attempt until it succeeds.
+/// If configured, the output is first narrowed to the largest the pool accepts, checked without swapping,
+/// so the shrinking is only a fallback for when the check and the pool disagree.
+/// Returns the order that was swapped, if any, the number of retries it took,
+/// and the gas used by every attempt, failed attempts included.
+fn swap_with_retries(
+    manager: &SimulationManager,
+    swap_order: Order,
+    config: &config::Arbitrageur,
+) -> Result<(Option<Order>, usize, u64), anyhow::Error> {
+    let verbose = math::verbose();
+
+    let arber = manager.agents.get("arbitrageur").unwrap();
+    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+
     let mut order = swap_order.clone();
-    let mut max_iter = 100; // limit to 100 tries.
-    while !swap_success && max_iter > 0 {
-        max_iter -= 1;
+    if config.search_swap_output {
+        match search_swap_output(manager, &order)? {
+            Some(output) => order.output = output,
+            None => return Ok((None, 0, 0)),
+        }
+    }
 
+    let mut retries = 0;
+    let mut gas_used = 0;
+    // Bounds of the output the bisect strategy narrows down between failed attempts.
+    let mut bisection = None;
+    for _ in 0..MAX_SWAP_ATTEMPTS {
         let swap_call_result = arber.call(portfolio, "swap", vec![order.clone().into_token()]);
         let swap_call_result = match swap_call_result {
             Ok(result) => result,
@@ -153,68 +770,329 @@ pub fn run(manager: &SimulationManager, price: f64, pool_id: u64) -> Result<(),
             }
         };
 
+        gas_used += swap_call_result.gas_used();
+
         match unpack_execution(swap_call_result) {
             Ok(unpacked) => {
-                if verbose.is_ok() {
+                if verbose {
                     let swap_return: SwapReturn = portfolio.decode_output("swap", unpacked)?;
                     println!(
-                        "Swap successful call returned: poolId {}, input {}, output {}, starting output: {}",
+                        "Swap successful call returned: poolId {}, input {}, output {}, starting output: {}, retries: {}",
                         swap_return.pool_id,
                         swap_return.input,
                         swap_return.output,
-                        swap_order.output
+                        swap_order.output,
+                        retries
                     );
                 }
 
-                swap_success = true;
+                return Ok((Some(order), retries, gas_used));
             }
             Err(_) => {
-                // reduce output by a small amount until we are successful in swapping
-                order.output = order
-                    .output
-                    .checked_mul(999_u128)
-                    .unwrap()
-                    .checked_div(1000_u128)
-                    .unwrap();
+                // reduce output until we are successful in swapping
+                order.output = match config.swap_retry_shrink {
+                    ShrinkStrategy::Bisect => {
+                        bisect_output(manager, &order, config, &mut bisection)?
+                    }
+                    _ => shrink_output(manager, &order, config)?,
+                };
+                retries += 1;
             }
         };
     }
 
-    if swap_success {
-        // Do the swap on the liquid exchange.
-        let exchange = manager.deployed_contracts.get("exchange").unwrap();
-        let token0 = manager.deployed_contracts.get("token0").unwrap();
-        let token1 = manager.deployed_contracts.get("token1").unwrap();
+    Ok((None, retries, gas_used))
+}
 
-        let mut exec = Caller::new(arber);
-
-        let trade_call_result: bool = exec
-            .call(
-                exchange,
-                "trade",
-                (
-                    recast_address(token0.address),
-                    recast_address(token1.address),
-                    !order.sell_asset, // opposite of sell asset
-                    order.output,      // swap in the output amount of the portfolio swap
-                )
-                    .into_tokens(),
-            )?
-            .decoded(exchange)?;
+/// Largest output in `[0, upper]` that `accepts`, found with a binary search of at most `max_iter` checks.
+/// Assumes every output below an accepted output is accepted too.
+/// Returns `None` if no nonzero output is accepted.
+pub fn largest_accepted_output<F>(
+    upper: u128,
+    max_iter: usize,
+    mut accepts: F,
+) -> Result<Option<u128>, anyhow::Error>
+where
+    F: FnMut(u128) -> Result<bool, anyhow::Error>,
+{
+    if accepts(upper)? {
+        return Ok(Some(upper));
+    }
+
+    let mut bisection = OutputBisection::new(0, upper);
+    for _ in 1..max_iter {
+        let midpoint = match bisection.midpoint() {
+            Some(midpoint) => midpoint,
+            None => break,
+        };
+        bisection.record(midpoint, accepts(midpoint)?);
+    }
+
+    Ok(if bisection.lower > 0 {
+        Some(bisection.lower)
+    } else {
+        None
+    })
+}
+
+/// # OutputBisection
+/// Bounds of a binary search for the largest output a swap succeeds with.
+/// Narrowed by checking outputs without swapping, see `largest_accepted_output`,
+/// or by the swap attempts themselves, see `bisect_output`.
+///
+/// # Fields
+/// * `lower` - Largest output accepted, or expected to be, zero if none. (u128)
+/// * `upper` - Smallest output rejected. (u128)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutputBisection {
+    pub lower: u128,
+    pub upper: u128,
+}
+
+impl OutputBisection {
+    pub fn new(lower: u128, upper: u128) -> Self {
+        Self { lower, upper }
+    }
 
-        if !trade_call_result {
-            return Err(anyhow!("Trade failed."));
+    /// Output halfway between the bounds, `None` once there is no output between them.
+    pub fn midpoint(&self) -> Option<u128> {
+        if self.upper.saturating_sub(self.lower) <= 1 {
+            return None;
         }
+
+        Some(self.lower + (self.upper - self.lower) / 2)
     }
 
-    Ok(())
+    /// Narrows the bounds to `output`, as the lower bound if it was accepted, else as the upper bound.
+    pub fn record(&mut self, output: u128, accepted: bool) {
+        if accepted {
+            self.lower = output;
+        } else {
+            self.upper = output;
+        }
+    }
+}
+
+/// Searches for the largest output, up to the order's output, the pool accepts for the order's input.
+/// Each output is checked with view calls only, see `swap_accepted`.
+fn search_swap_output(
+    manager: &SimulationManager,
+    order: &Order,
+) -> Result<Option<u128>, anyhow::Error> {
+    let arber = manager.agents.get("arbitrageur").unwrap();
+    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+    let library = manager.deployed_contracts.get("library").unwrap();
+    let mut caller = Caller::new(arber);
+
+    let pool: PoolsReturn = caller.view(portfolio, "pools", order.pool_id.into_tokens())?;
+    let curve: SolidityNormalCurve = caller.view(
+        library,
+        "getCurveConfiguration",
+        (recast_address(portfolio.address), order.pool_id).into_tokens(),
+    )?;
+    let fee_basis_points = effective_fee_basis_points(
+        pool.controller,
+        pool.fee_basis_points,
+        pool.priority_fee_basis_points,
+        recast_address(arber.address()),
+    );
+
+    largest_accepted_output(order.output, MAX_SWAP_ATTEMPTS, |output| {
+        let mut next = curve.clone();
+        if !apply_swap(&mut next, order, output, fee_basis_points, pool.liquidity) {
+            return Ok(false);
+        }
+        swap_accepted(&mut caller, library, &next, curve.invariant)
+    })
+}
+
+/// Moves the curve's reserves per liquidity by a swap of the order's input, less the fee, for `output`.
+/// Returns false if the output is larger than the reserve it is paid from.
+fn apply_swap(
+    curve: &mut SolidityNormalCurve,
+    order: &Order,
+    output: u128,
+    fee_basis_points: u16,
+    liquidity: u128,
+) -> bool {
+    let wad = parse_ether(1.0).unwrap();
+    let divisor = U256::from(common::BASIS_POINT_DIVISOR);
+    let input_less_fee =
+        U256::from(order.input) * (divisor - U256::from(fee_basis_points)) / divisor;
+    let input = input_less_fee * wad / U256::from(liquidity);
+    let output = U256::from(output) * wad / U256::from(liquidity);
+
+    let (reserve_in, reserve_out) = if order.sell_asset {
+        (&mut curve.reserve_x_per_wad, &mut curve.reserve_y_per_wad)
+    } else {
+        (&mut curve.reserve_y_per_wad, &mut curve.reserve_x_per_wad)
+    };
+    if output > *reserve_out {
+        return false;
+    }
+
+    *reserve_in += input;
+    *reserve_out -= output;
+    true
+}
+
+/// True if the trading function of the curve is no lower than `invariant`, the pool's invariant before the swap.
+fn swap_accepted(
+    caller: &mut Caller,
+    library: &SimulationContract<IsDeployed>,
+    curve: &SolidityNormalCurve,
+    invariant: I256,
+) -> Result<bool, anyhow::Error> {
+    let next_invariant: I256 =
+        caller.view(library, "tradingFunction", vec![curve.clone().into_token()])?;
+    Ok(next_invariant >= invariant)
+}
+
+/// Caps the order's input to the arbitrageur's balance of the token it sells, in wad,
+/// scaling its output down by the same fraction, so the swap doesn't revert for lack of funds.
+fn cap_order_to_balance(
+    manager: &SimulationManager,
+    config: &SimConfig,
+    order: Order,
+) -> Result<Order, anyhow::Error> {
+    let arber = manager.agents.get("arbitrageur").unwrap();
+    let (token, decimals) = if order.sell_asset {
+        (
+            manager.deployed_contracts.get("token0").unwrap(),
+            config.tokens.decimals_x,
+        )
+    } else {
+        (
+            manager.deployed_contracts.get("token1").unwrap(),
+            config.tokens.decimals_y,
+        )
+    };
+
+    let balance: U256 = Caller::new(arber).view(
+        token,
+        "balanceOf",
+        recast_address(arber.address()).into_tokens(),
+    )?;
+    let balance_wad = raw_data::scale_to_wad(balance, decimals);
+
+    if U256::from(order.input) <= balance_wad {
+        return Ok(order);
+    }
+
+    let input = balance_wad.as_u128();
+    let output = U256::from(order.output) * balance_wad / U256::from(order.input);
+    Ok(Order {
+        input,
+        output: output.as_u128(),
+        ..order
+    })
+}
+
+/// Swaps `input` on the pool as the sandwicher, a crude model of adversarial transactions around the arbitrageur's swap.
+/// The admin agent plays the sandwicher and mints the input tokens it needs.
+/// Returns the output received.
+fn sandwich_swap(
+    manager: &SimulationManager,
+    pool_id: u64,
+    sell_asset: bool,
+    input: U256,
+) -> Result<U256, anyhow::Error> {
+    let admin = manager.agents.get("admin").unwrap();
+    let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+    let token = if sell_asset {
+        manager.deployed_contracts.get("token0").unwrap()
+    } else {
+        manager.deployed_contracts.get("token1").unwrap()
+    };
+
+    let output = get_amount_out(manager, pool_id, sell_asset, input)
+        .map_err(|e| anyhow!("task.rs: Error on getting sandwich amount out: {:#?}", e))?;
+    if output.is_zero() {
+        return Ok(output);
+    }
+
+    let mut caller = Caller::new(admin);
+    caller
+        .call(
+            token,
+            "mint",
+            (recast_address(admin.address()), input).into_tokens(),
+        )?
+        .res()?;
+    let order = Order {
+        use_max: false,
+        pool_id,
+        input: input.as_u128(),
+        output: output.as_u128(),
+        sell_asset,
+    };
+    caller
+        .call(portfolio, "swap", vec![order.into_token()])?
+        .res()?;
+
+    Ok(output)
+}
+
+/// Computes the next output to try after a failed swap, using the configured shrink strategy.
+fn shrink_output(
+    manager: &SimulationManager,
+    order: &Order,
+    config: &config::Arbitrageur,
+) -> Result<u128, anyhow::Error> {
+    let shrunk = U256::from(order.output)
+        .checked_mul(float_to_wad(config.swap_retry_shrink_factor))
+        .unwrap()
+        .checked_div(parse_ether(1.0).unwrap())
+        .unwrap()
+        .as_u128();
+
+    match config.swap_retry_shrink {
+        ShrinkStrategy::Factor => Ok(shrunk),
+        ShrinkStrategy::Quoted | ShrinkStrategy::Bisect => {
+            // Jump straight to the feasible output if the gap is larger than a single shrink.
+            let feasible = get_amount_out(
+                manager,
+                order.pool_id,
+                order.sell_asset,
+                U256::from(order.input),
+            )
+            .map_err(|e| anyhow!("task.rs: Error on getting amount out: {:#?}", e))?;
+
+            Ok(shrunk.min(feasible.as_u128()))
+        }
+    }
 }
 
-/// Computes the swap order required to move the portfolio pool's reported price to `target_price_wad`.
+/// Next output to try after the order's output failed, halfway between it and the largest output expected to succeed.
+/// The bisection starts from the quoted shrink of the output, and starts over from it if that fails itself,
+/// so each failure halves the gap instead of shrinking by a fixed factor.
+fn bisect_output(
+    manager: &SimulationManager,
+    order: &Order,
+    config: &config::Arbitrageur,
+    bisection: &mut Option<OutputBisection>,
+) -> Result<u128, anyhow::Error> {
+    match bisection.as_mut() {
+        Some(bounds) if order.output > bounds.lower => bounds.record(order.output, false),
+        _ => {
+            let lower = shrink_output(manager, order, config)?;
+            *bisection = Some(OutputBisection::new(lower, order.output));
+        }
+    }
+
+    Ok(bisection.map_or(0, |bounds| bounds.midpoint().unwrap_or(bounds.lower)))
+}
+
+/// Computes the swap order in the direction of `sell_asset` required to move the portfolio pool's reported price
+/// to `target_price_wad`. If the actor computes the opposite direction, e.g. from rounding at the no-arb bounds,
+/// the direction is ambiguous: the order takes the actor's direction if it is `prefer_direction`,
+/// else it has no input so the step is skipped instead of swapping away from the target.
 fn get_swap_order(
     manager: &SimulationManager,
     pool_id: u64,
     target_price_wad: ethers::prelude::U256,
+    sell_asset: bool,
+    prefer_direction: &PreferDirection,
 ) -> Result<Order, Box<dyn std::error::Error>> {
     //println!("Pool id: {}", pool_id);
     let arbitrageur = manager.agents.get("arbitrageur").unwrap();
@@ -245,6 +1123,23 @@ fn get_swap_order(
         }
     }
 
+    let prefers_actor = match prefer_direction {
+        PreferDirection::Sign => false,
+        PreferDirection::SwapXToY => swap_x_in,
+        PreferDirection::SwapYToX => !swap_x_in,
+    };
+    if swap_x_in != sell_asset && order_input_wad_per_liq != U256::zero() && !prefers_actor {
+        println!(
+            "{} the actor's swap direction, sell x: {}, disagrees with the no-arb bounds, skipping.",
+            "Warning:".bright_yellow(),
+            swap_x_in
+        );
+        order_input_wad_per_liq = U256::zero();
+    }
+    if !prefers_actor {
+        swap_x_in = sell_asset;
+    }
+
     //println!("there");
 
     //println!("swap_x_in: {}", order_input_wad_per_liq);
@@ -284,6 +1179,9 @@ fn get_swap_order(
     Ok(order)
 }
 
+/// Gets the output of selling `amount_in` into the pool `pool_id`.
+/// Amounts are in wad, portfolio scales them to each token's decimals when it settles,
+/// so the arbitrageur's math is the same for tokens of any decimals.
 pub fn get_amount_out(
     manager: &SimulationManager,
     pool_id: u64,
@@ -315,3 +1213,786 @@ pub fn get_amount_out(
 
     Ok(amount_out)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PriceConvention;
+    use crate::setup::{self, setup_pool};
+
+    /// Counts the retries it takes to swap an order requesting 5% more output than the pool gives.
+    fn retries_for_oversized_order(shrink: ShrinkStrategy, shrink_factor: f64) -> usize {
+        let mut config = SimConfig::default();
+        config.arbitrageur.swap_retry_shrink = shrink;
+        config.arbitrageur.swap_retry_shrink_factor = shrink_factor;
+        // Counts the retries of the shrink strategy alone.
+        config.arbitrageur.search_swap_output = false;
+        let (manager, pool_id) = setup_pool(&config);
+
+        let mut order = get_swap_order(
+            &manager,
+            pool_id,
+            float_to_wad(1.1),
+            false,
+            &PreferDirection::Sign,
+        )
+        .unwrap();
+        order.output = order.output * 105 / 100;
+
+        let (swapped, retries, _) =
+            swap_with_retries(&manager, order, &config.arbitrageur).unwrap();
+        assert!(swapped.is_some());
+        retries
+    }
+
+    /// Reported price of the pool, in wad.
+    fn reported_price(manager: &SimulationManager, pool_id: u64) -> U256 {
+        let admin = manager.agents.get("admin").unwrap();
+        let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+        Caller::new(admin)
+            .call(portfolio, "getSpotPrice", pool_id.into_tokens())
+            .unwrap()
+            .decoded(portfolio)
+            .unwrap()
+    }
+
+    #[test]
+    fn dust_orders_are_skipped() {
+        // Just outside of the default 10 bps fee's no-arb band.
+        let price = 1.0025;
+
+        let mut config = SimConfig::default();
+        config.arbitrageur.min_swap_output_f = 1.0;
+        let (manager, pool_id) = setup_pool(&config);
+        let before = reported_price(&manager, pool_id);
+        run(&manager, price, pool_id, &config).unwrap();
+        assert_eq!(reported_price(&manager, pool_id), before);
+
+        // Without a minimum, the same opportunity is arbitraged.
+        let config = SimConfig::default();
+        let (manager, pool_id) = setup_pool(&config);
+        let before = reported_price(&manager, pool_id);
+        run(&manager, price, pool_id, &config).unwrap();
+        assert_ne!(reported_price(&manager, pool_id), before);
+    }
+
+    #[test]
+    fn low_liquidity_pool_is_skipped_and_flagged() {
+        // The pool is allocated 1 unit of liquidity.
+        let mut config = SimConfig::default();
+        config.arbitrageur.min_pool_liquidity_f = 2.0;
+        let (mut manager, pool_id) = setup_pool(&config);
+        let before = reported_price(&manager, pool_id);
+
+        let mut raw_data = RawData::new();
+        run_with_price(
+            &mut manager,
+            &mut raw_data,
+            &mut HedgeQueue::new(),
+            0,
+            1.1,
+            pool_id,
+            &config,
+        )
+        .unwrap();
+
+        assert_eq!(reported_price(&manager, pool_id), before);
+        assert_eq!(raw_data.get_liquidity_too_low(pool_id).unwrap(), vec![true]);
+        assert_eq!(raw_data.get_swap_retries(pool_id).unwrap(), vec![0.0]);
+
+        // Above the minimum, the same opportunity is arbitraged.
+        config.arbitrageur.min_pool_liquidity_f = 0.5;
+        let (manager, pool_id) = setup_pool(&config);
+        let outcome = run(&manager, 1.1, pool_id, &config).unwrap();
+        assert!(!outcome.liquidity_too_low);
+        assert!(outcome.swap_retries.is_some());
+    }
+
+    #[test]
+    fn no_swap_step_makes_fewer_evm_calls() {
+        let config = SimConfig::default();
+        let (manager, pool_id) = setup_pool(&config);
+
+        // Inside the no-arb band, then outside of it.
+        let before = counters::totals();
+        run(&manager, config.process.initial_price, pool_id, &config).unwrap();
+        let no_swap = counters::totals().since(&before);
+
+        let before = counters::totals();
+        run(&manager, 1.1, pool_id, &config).unwrap();
+        let swap = counters::totals().since(&before);
+
+        assert!(no_swap.evm_calls < swap.evm_calls);
+    }
+
+    #[test]
+    fn priority_fee_tightens_no_arb_band() {
+        let controller = Address::from_low_u64_be(common::ARBITRAGEUR_ADDRESS_BASE);
+        let other = Address::from_low_u64_be(1);
+
+        // Controller pays the priority fee, everyone else pays the regular fee.
+        assert_eq!(effective_fee_basis_points(controller, 30, 5, controller), 5);
+        assert_eq!(effective_fee_basis_points(controller, 30, 5, other), 30);
+        assert_eq!(
+            effective_fee_basis_points(Address::zero(), 30, 5, other),
+            30
+        );
+
+        // A price 0.3% away is outside the priority fee's band but inside the regular fee's band.
+        let current = float_to_wad(1.0);
+        let target = float_to_wad(1.003);
+
+        let priority = check_no_arb_bounds(
+            current,
+            target,
+            no_arb_fee_wad(5),
+            U256::zero(),
+            &PreferDirection::Sign,
+        );
+        let regular = check_no_arb_bounds(
+            current,
+            target,
+            no_arb_fee_wad(30),
+            U256::zero(),
+            &PreferDirection::Sign,
+        );
+
+        assert!(matches!(priority, Some(SwapDirection::SwapYToX)));
+        assert!(matches!(regular, Some(SwapDirection::None)));
+    }
+
+    #[test]
+    fn price_on_no_arb_bound_does_not_swap() {
+        let current = float_to_wad(1.0);
+        let fee = no_arb_fee_wad(10);
+        let tolerance = U256::from(SimConfig::default().arbitrageur.no_arb_tolerance_wad);
+
+        // Exactly on the upper bound, and one wei past it.
+        let upper = current * parse_ether(1.0).unwrap() / fee;
+        for target in [upper, upper + 1] {
+            let direction =
+                check_no_arb_bounds(current, target, fee, tolerance, &PreferDirection::Sign);
+            assert!(matches!(direction, Some(SwapDirection::None)));
+        }
+
+        // Beyond the tolerance it's an arb.
+        let direction = check_no_arb_bounds(
+            current,
+            upper + tolerance + 1,
+            fee,
+            tolerance,
+            &PreferDirection::Sign,
+        );
+        assert!(matches!(direction, Some(SwapDirection::SwapYToX)));
+    }
+
+    #[test]
+    fn classify_covers_each_regime() {
+        // The default 10 bps fee's band is about 0.2% wide on each side.
+        let current = float_to_wad(1.0);
+        let fee = no_arb_fee_wad(10);
+
+        assert_eq!(classify(current, current, fee), PriceRegime::InBand);
+        assert_eq!(
+            classify(current, float_to_wad(1.001), fee),
+            PriceRegime::InBand
+        );
+        assert_eq!(
+            classify(current, float_to_wad(0.999), fee),
+            PriceRegime::InBand
+        );
+        assert_eq!(
+            classify(current, float_to_wad(1.01), fee),
+            PriceRegime::ArbUp
+        );
+        assert_eq!(
+            classify(current, float_to_wad(0.99), fee),
+            PriceRegime::ArbDown
+        );
+        assert_eq!(PriceRegime::ArbUp.as_str(), "arb_up");
+    }
+
+    #[test]
+    fn ambiguous_direction_uses_preference() {
+        // A zero fee collapses both bounds onto the current price, so a target within
+        // the tolerance of the current price is on both bounds.
+        let current = float_to_wad(1.0);
+        let fee = no_arb_fee_wad(0);
+        let tolerance = U256::from(SimConfig::default().arbitrageur.no_arb_tolerance_wad);
+        let target = current + tolerance / 2;
+
+        let check = |prefer| check_no_arb_bounds(current, target, fee, tolerance, &prefer);
+
+        // The bounds alone treat the target as inside the band.
+        assert!(matches!(
+            check(PreferDirection::Sign),
+            Some(SwapDirection::None)
+        ));
+        assert!(matches!(
+            check(PreferDirection::SwapXToY),
+            Some(SwapDirection::SwapXToY)
+        ));
+        assert!(matches!(
+            check(PreferDirection::SwapYToX),
+            Some(SwapDirection::SwapYToX)
+        ));
+    }
+
+    #[test]
+    fn swap_order_takes_preferred_direction_when_actor_disagrees() {
+        let config = SimConfig::default();
+        let (manager, pool_id) = setup_pool(&config);
+        // A higher reference price buys x from the pool, but the bounds are told to sell x.
+        let target = float_to_wad(1.1);
+        let order = |prefer| get_swap_order(&manager, pool_id, target, true, &prefer).unwrap();
+
+        let sign = order(PreferDirection::Sign);
+        assert!(sign.sell_asset);
+        assert_eq!(sign.input, 0);
+
+        let x_to_y = order(PreferDirection::SwapXToY);
+        assert_eq!(x_to_y.input, 0);
+
+        let y_to_x = order(PreferDirection::SwapYToX);
+        assert!(!y_to_x.sell_asset);
+        assert!(y_to_x.input > 0);
+    }
+
+    #[test]
+    fn direction_drives_swap_order_sell_asset() {
+        let config = SimConfig::default();
+        let (manager, pool_id) = setup_pool(&config);
+        let current = reported_price(&manager, pool_id);
+        let fee = no_arb_fee_wad(config.economic.pool_fee_basis_points);
+        let tolerance = U256::from(config.arbitrageur.no_arb_tolerance_wad);
+
+        // A higher reference price buys x from the pool, a lower one sells x to it.
+        for (price, sell_asset) in [(1.1, false), (0.9, true)] {
+            let target = float_to_wad(price);
+            let direction =
+                check_no_arb_bounds(current, target, fee, tolerance, &PreferDirection::Sign)
+                    .unwrap();
+            assert_eq!(direction.sell_asset(), Some(sell_asset));
+
+            let order = get_swap_order(
+                &manager,
+                pool_id,
+                target,
+                direction.sell_asset().unwrap(),
+                &PreferDirection::Sign,
+            )
+            .unwrap();
+            assert_eq!(order.sell_asset, sell_asset);
+            assert!(order.input > 0);
+            assert!(order.output > 0);
+        }
+    }
+
+    #[test]
+    fn best_venue_has_largest_edge() {
+        let mut config = SimConfig::default();
+        config.arbitrageur.venues = vec!["exchange".to_string(), "exchange_1".to_string()];
+        let (manager, pool_id) = setup_pool(&config);
+
+        // The second venue quotes a price much further from the pool's price of 1.0.
+        step::set_venue_price(&manager, "exchange_1", 1.2).unwrap();
+
+        let current_price_wad = reported_price(&manager, pool_id);
+
+        let prices =
+            venue_prices(&manager, &config.arbitrageur.venues, float_to_wad(1.05)).unwrap();
+        let (venue, price) = best_venue(&prices, current_price_wad).unwrap();
+
+        assert_eq!(venue, "exchange_1");
+        assert_eq!(price, float_to_wad(1.2));
+    }
+
+    #[test]
+    fn up_only_ignores_price_decreases() {
+        let mut config = SimConfig::default();
+        config.arbitrageur.direction_filter = DirectionFilter::UpOnly;
+
+        // Well outside of the no-arb band, below the initial price of 1.0.
+        let (manager, pool_id) = setup_pool(&config);
+        let before = reported_price(&manager, pool_id);
+        run(&manager, 0.95, pool_id, &config).unwrap();
+        assert_eq!(reported_price(&manager, pool_id), before);
+
+        // Increases are still arbitraged.
+        let (manager, pool_id) = setup_pool(&config);
+        let before = reported_price(&manager, pool_id);
+        run(&manager, 1.05, pool_id, &config).unwrap();
+        assert_ne!(reported_price(&manager, pool_id), before);
+    }
+
+    /// Runs a steadily rising reference price with the arbitrageur acting every `interval` steps.
+    /// Returns the reference prices and the reported price logged at each step.
+    fn rising_prices_with_interval(interval: usize) -> (Vec<f64>, Vec<f64>) {
+        let mut config = SimConfig::default();
+        config.arbitrageur.arb_interval_steps = interval;
+        let (mut manager, pool_id) = setup_pool(&config);
+
+        let prices = (1..=9).map(|i| 1.0 + 0.01 * i as f64).collect::<Vec<f64>>();
+        let mut raw_data = RawData::new();
+        let mut hedges = HedgeQueue::new();
+        for (step, price) in prices.iter().enumerate() {
+            run_with_price(
+                &mut manager,
+                &mut raw_data,
+                &mut hedges,
+                step,
+                *price,
+                pool_id,
+                &config,
+            )
+            .unwrap();
+        }
+
+        (prices, raw_data.get_reported_price_float(pool_id).unwrap())
+    }
+
+    #[test]
+    fn scheduled_arbitrageur_swaps_every_third_step() {
+        let (prices, reported) = rising_prices_with_interval(3);
+
+        // The reported price only moves on the steps the arbitrageur swaps.
+        let mut previous = 1.0;
+        let mut swap_steps = Vec::new();
+        for (i, price) in reported.iter().enumerate() {
+            if (price - previous).abs() > 1e-12 {
+                swap_steps.push(i);
+            }
+            previous = *price;
+        }
+        assert_eq!(swap_steps, vec![0, 3, 6]);
+
+        // Drifting between the swaps tracks the reference price worse than swapping every step.
+        let (continuous_prices, continuous) = rising_prices_with_interval(1);
+        let batched_error = crate::summary::root_mean_squared_error(&reported, &prices);
+        let continuous_error =
+            crate::summary::root_mean_squared_error(&continuous, &continuous_prices);
+        assert!(batched_error > continuous_error);
+    }
+
+    #[test]
+    fn scripted_prices_swap_in_their_direction() {
+        let config = SimConfig::default();
+        let (mut manager, pool_id) = setup_pool(&config);
+        let initial = float_to_wad(config.process.initial_price);
+
+        let mut raw_data = RawData::new();
+        let mut hedges = HedgeQueue::new();
+        for (step, price) in [1.05, 0.95].into_iter().enumerate() {
+            run_with_price(
+                &mut manager,
+                &mut raw_data,
+                &mut hedges,
+                step,
+                price,
+                pool_id,
+                &config,
+            )
+            .unwrap();
+            assert_converged(&manager, pool_id, 0.005).unwrap();
+        }
+
+        // Up step pulls the reported price up, down step pulls it back down.
+        let reported = raw_data.get_reported_price(pool_id).unwrap();
+        assert_eq!(reported.len(), 2);
+        assert!(reported[0] > initial);
+        assert!(reported[1] < initial);
+    }
+
+    #[test]
+    fn small_deviation_converges() {
+        let config = SimConfig::default();
+        let (manager, pool_id) = setup_pool(&config);
+
+        // A deviation of 0.5%, outside of the 10 bps fee's no-arb band.
+        let price = 1.005;
+        run(&manager, price, pool_id, &config).unwrap();
+        step::run(&manager, price).unwrap();
+
+        // The reported price ends within the no-arb band of the reference price.
+        assert_converged(&manager, pool_id, 0.0025).unwrap();
+    }
+
+    #[test]
+    fn unchased_target_does_not_converge() {
+        // The price jump breaker skips the step, so the arbitrageur never chases the target.
+        let mut config = SimConfig::default();
+        config.arbitrageur.max_price_jump_pct = 20.0;
+        config.arbitrageur.price_jump_policy = PriceJumpPolicy::Skip;
+        let (manager, pool_id) = setup_pool(&config);
+
+        let price = 10.0;
+        run(&manager, price, pool_id, &config).unwrap();
+        step::run(&manager, price).unwrap();
+
+        let err = assert_converged(&manager, pool_id, 0.0025).unwrap_err();
+        assert!(err.to_string().contains("did not converge"), "{}", err);
+    }
+
+    #[test]
+    fn flipped_price_convention_converges() {
+        let mut config = SimConfig::default();
+        config.economic.price_convention = PriceConvention::BasePerQuote;
+        let (mut manager, pool_id) = setup_pool(&config);
+        let initial = reported_price(&manager, pool_id);
+
+        // 0.95 base per quote is a rise of the quote per base price the pool reports.
+        let mut raw_data = RawData::new();
+        run_with_price(
+            &mut manager,
+            &mut raw_data,
+            &mut HedgeQueue::new(),
+            0,
+            0.95,
+            pool_id,
+            &config,
+        )
+        .unwrap();
+
+        assert!(reported_price(&manager, pool_id) > initial);
+        assert!((wad_to_float(reported_price(&manager, pool_id)) - 1.0 / 0.95).abs() < 0.005);
+        assert_converged(&manager, pool_id, 0.005).unwrap();
+    }
+
+    #[test]
+    fn price_jump_breaker_engages() {
+        let jump = 10.0;
+
+        // Skipping leaves the pool untouched.
+        let mut config = SimConfig::default();
+        config.arbitrageur.max_price_jump_pct = 20.0;
+        config.arbitrageur.price_jump_policy = PriceJumpPolicy::Skip;
+        let (manager, pool_id) = setup_pool(&config);
+        let before = reported_price(&manager, pool_id);
+        run(&manager, jump, pool_id, &config).unwrap();
+        assert_eq!(reported_price(&manager, pool_id), before);
+
+        // Clamping arbs towards the price at the max jump, 20% above the initial price of 1.0, not the jump.
+        config.arbitrageur.price_jump_policy = PriceJumpPolicy::Clamp;
+        let (manager, pool_id) = setup_pool(&config);
+        let before = reported_price(&manager, pool_id);
+        run(&manager, jump, pool_id, &config).unwrap();
+        let after = reported_price(&manager, pool_id);
+        assert!(after > before);
+        assert!(after < float_to_wad(1.25));
+    }
+
+    #[test]
+    fn sandwich_lowers_lp_value() {
+        let lp_value = |size_f: f64| {
+            let mut config = SimConfig::default();
+            config.sandwich.size_f = size_f;
+            let (mut manager, pool_id) = setup_pool(&config);
+
+            let mut raw_data = RawData::new();
+            let mut hedges = HedgeQueue::new();
+            for (step, price) in [1.05, 1.1, 1.05, 1.0].into_iter().enumerate() {
+                run_with_price(
+                    &mut manager,
+                    &mut raw_data,
+                    &mut hedges,
+                    step,
+                    price,
+                    pool_id,
+                    &config,
+                )
+                .unwrap();
+            }
+
+            *raw_data
+                .get_portfolio_value_float(pool_id)
+                .unwrap()
+                .last()
+                .unwrap()
+        };
+
+        assert!(lp_value(0.01) < lp_value(0.0));
+    }
+
+    /// Arbitrageur's balances of token0 and token1, in wad.
+    fn arbitrageur_balances(manager: &SimulationManager) -> (U256, U256) {
+        let arber = manager.agents.get("arbitrageur").unwrap();
+        let token0 = manager.deployed_contracts.get("token0").unwrap();
+        let token1 = manager.deployed_contracts.get("token1").unwrap();
+        let mut caller = Caller::new(arber);
+        let balance_0 = caller.balance_of(token0).decoded(token0).unwrap();
+        let balance_1 = caller.balance_of(token1).decoded(token1).unwrap();
+        (balance_0, balance_1)
+    }
+
+    #[test]
+    fn swap_is_capped_by_arbitrageur_balance() {
+        let mut config = SimConfig::default();
+        config.economic.arbitrageur_initial_balance_f = 0.001;
+        let (manager, pool_id) = setup_pool(&config);
+
+        let uncapped = get_swap_order(
+            &manager,
+            pool_id,
+            float_to_wad(1.5),
+            false,
+            &PreferDirection::Sign,
+        )
+        .unwrap();
+        let (_, y_before) = arbitrageur_balances(&manager);
+        assert!(U256::from(uncapped.input) > y_before);
+
+        let capped = cap_order_to_balance(&manager, &config, uncapped.clone()).unwrap();
+        assert_eq!(U256::from(capped.input), y_before);
+        assert!(capped.output < uncapped.output);
+
+        // Buys x from the pool with its whole y balance, instead of a swap that reverts.
+        let outcome = run(&manager, 1.5, pool_id, &config).unwrap();
+        assert!(outcome.swap_retries.is_some());
+        let (_, y_after) = arbitrageur_balances(&manager);
+        assert!(y_after > y_before);
+    }
+
+    #[test]
+    fn searched_swap_output_is_the_largest_the_pool_accepts() {
+        let config = SimConfig::default();
+        let (manager, pool_id) = setup_pool(&config);
+        let arber = manager.agents.get("arbitrageur").unwrap();
+        let portfolio = manager.deployed_contracts.get("portfolio").unwrap();
+
+        // Asks for more than the pool pays, so the search is bounded by the pool and not the order.
+        let mut order = get_swap_order(
+            &manager,
+            pool_id,
+            float_to_wad(1.1),
+            false,
+            &PreferDirection::Sign,
+        )
+        .unwrap();
+        order.output *= 2;
+        let output = search_swap_output(&manager, &order).unwrap().unwrap();
+        assert!(output < order.output);
+
+        let swaps = |output: u128| {
+            let order = Order {
+                output,
+                ..order.clone()
+            };
+            let result = arber
+                .call(portfolio, "swap", vec![order.into_token()])
+                .unwrap();
+            unpack_execution(result).is_ok()
+        };
+
+        // The reverted swap leaves the pool as it was, for the found output to swap on.
+        assert!(!swaps(output + 1));
+        assert!(swaps(output));
+    }
+
+    #[test]
+    fn exchange_leg_closes_position_in_both_directions() {
+        let config = SimConfig::default();
+
+        // Price up: buys x from the pool with y, sells the x on the exchange for more y.
+        let (manager, pool_id) = setup_pool(&config);
+        let (x_before, y_before) = arbitrageur_balances(&manager);
+        run(&manager, 1.1, pool_id, &config).unwrap();
+        let (x_after, y_after) = arbitrageur_balances(&manager);
+        assert_eq!(x_after, x_before);
+        assert!(y_after > y_before);
+
+        // Price down: sells x to the pool for y, buys more x on the exchange with the y.
+        let (manager, pool_id) = setup_pool(&config);
+        let (x_before, y_before) = arbitrageur_balances(&manager);
+        run(&manager, 0.9, pool_id, &config).unwrap();
+        let (x_after, y_after) = arbitrageur_balances(&manager);
+        assert_eq!(y_after, y_before);
+        assert!(x_after > x_before);
+    }
+
+    /// Revokes the arbitrageur's approvals of the exchange, so its trades fail after the portfolio swap.
+    fn revoke_exchange_approvals(manager: &SimulationManager) {
+        let arbitrageur = manager.agents.get("arbitrageur").unwrap();
+        let exchange = recast_address(manager.deployed_contracts.get("exchange").unwrap().address);
+        let mut caller = Caller::new(arbitrageur);
+        for token in ["token0", "token1"] {
+            let token = manager.deployed_contracts.get(token).unwrap();
+            caller
+                .call(token, "approve", (exchange, U256::zero()).into_tokens())
+                .unwrap()
+                .res()
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn failed_exchange_trade_follows_policy() {
+        // Aborting errors, ending the sim.
+        let mut config = SimConfig::default();
+        let (mut manager, pool_id) = setup_pool(&config);
+        revoke_exchange_approvals(&manager);
+        let mut raw_data = RawData::new();
+        assert!(run_with_price(
+            &mut manager,
+            &mut raw_data,
+            &mut HedgeQueue::new(),
+            0,
+            1.1,
+            pool_id,
+            &config
+        )
+        .is_err());
+
+        // Rolling back leaves the pool and the arbitrageur as they were before the step.
+        config.arbitrageur.trade_failure_policy = TradeFailurePolicy::Rollback;
+        let (mut manager, pool_id) = setup_pool(&config);
+        revoke_exchange_approvals(&manager);
+        let before = reported_price(&manager, pool_id);
+        let balances = arbitrageur_balances(&manager);
+        let mut raw_data = RawData::new();
+        run_with_price(
+            &mut manager,
+            &mut raw_data,
+            &mut HedgeQueue::new(),
+            0,
+            1.1,
+            pool_id,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(reported_price(&manager, pool_id), before);
+        assert_eq!(arbitrageur_balances(&manager), balances);
+        assert_eq!(raw_data.get_unhedged(pool_id).unwrap(), vec![false]);
+
+        // Continuing keeps the swap and records the unhedged position.
+        config.arbitrageur.trade_failure_policy = TradeFailurePolicy::Continue;
+        let (mut manager, pool_id) = setup_pool(&config);
+        revoke_exchange_approvals(&manager);
+        let before = reported_price(&manager, pool_id);
+        let mut raw_data = RawData::new();
+        run_with_price(
+            &mut manager,
+            &mut raw_data,
+            &mut HedgeQueue::new(),
+            0,
+            1.1,
+            pool_id,
+            &config,
+        )
+        .unwrap();
+        assert!(reported_price(&manager, pool_id) > before);
+        assert_eq!(raw_data.get_unhedged(pool_id).unwrap(), vec![true]);
+    }
+
+    #[test]
+    fn lagged_hedge_trades_on_next_step_at_its_price() {
+        let mut config = SimConfig::default();
+        config.arbitrageur.hedge_lag_steps = 1;
+        let (mut manager, pool_id) = setup_pool(&config);
+
+        // Step 0 swaps on the pool, buying x, but defers selling it on the exchange to step 1.
+        let mut raw_data = RawData::new();
+        let mut hedges = HedgeQueue::new();
+        let before = arbitrageur_balances(&manager);
+        run_with_price(
+            &mut manager,
+            &mut raw_data,
+            &mut hedges,
+            0,
+            1.1,
+            pool_id,
+            &config,
+        )
+        .unwrap();
+        let pending = hedges.pending().to_vec();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].due_step, 1);
+        assert!(pending[0].trade.sell_asset);
+        let amount_in = U256::from(pending[0].trade.amount_in);
+        let swapped = arbitrageur_balances(&manager);
+        assert_eq!(swapped.0, before.0 + amount_in);
+
+        // The pool is already at 1.1, so step 1 only settles the trade, at step 0's price of 1.1 the exchange then holds.
+        run_with_price(
+            &mut manager,
+            &mut raw_data,
+            &mut hedges,
+            1,
+            1.1,
+            pool_id,
+            &config,
+        )
+        .unwrap();
+        assert!(hedges.pending().is_empty());
+        let settled = arbitrageur_balances(&manager);
+        assert_eq!(settled.0, before.0);
+        assert_eq!(
+            settled.1 - swapped.1,
+            amount_in * float_to_wad(1.1) / parse_ether(1.0).unwrap()
+        );
+
+        let exposure = raw_data.get_pending_exposure(pool_id).unwrap();
+        assert!(exposure[0] > 0.0);
+        assert_eq!(exposure[1], 0.0);
+    }
+
+    #[test]
+    fn larger_shrink_factor_takes_fewer_retries() {
+        let fine = retries_for_oversized_order(ShrinkStrategy::Factor, 0.999);
+        let coarse = retries_for_oversized_order(ShrinkStrategy::Factor, 0.9);
+        assert!(coarse < fine);
+    }
+
+    #[test]
+    fn bisect_takes_fewer_retries_than_fine_factor() {
+        let fine = retries_for_oversized_order(ShrinkStrategy::Factor, 0.999);
+        let bisect = retries_for_oversized_order(ShrinkStrategy::Bisect, 0.999);
+        assert!(bisect < fine);
+    }
+
+    #[test]
+    fn binary_search_finds_largest_accepted_output() {
+        // Synthetic pool that accepts any output up to a known max.
+        let max_output = 987_654_321_012_345_678_u128;
+        let mut checks = 0;
+        let found = largest_accepted_output(2 * max_output, MAX_SWAP_ATTEMPTS, |output| {
+            checks += 1;
+            Ok(output <= max_output)
+        })
+        .unwrap()
+        .unwrap();
+
+        assert!(max_output - found <= 1);
+        assert!(checks <= MAX_SWAP_ATTEMPTS);
+        assert_eq!(
+            largest_accepted_output(max_output, MAX_SWAP_ATTEMPTS, |_| Ok(true)).unwrap(),
+            Some(max_output)
+        );
+        assert_eq!(
+            largest_accepted_output(max_output, MAX_SWAP_ATTEMPTS, |_| Ok(false)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn swap_retries_are_recorded_each_step() {
+        let config = SimConfig::default();
+        let (mut manager, pool_id) = setup_pool(&config);
+
+        let mut raw_data = RawData::new();
+        let mut hedges = HedgeQueue::new();
+        for (step, price) in [1.05, 1.05, 0.95].into_iter().enumerate() {
+            run_with_price(
+                &mut manager,
+                &mut raw_data,
+                &mut hedges,
+                step,
+                price,
+                pool_id,
+                &config,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(raw_data.get_swap_retries(pool_id).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,161 @@
+/// Validates a simulation's csv output against a separately computed theoretical model csv.
+use colored::*;
+use polars::prelude::*;
+use std::error::Error;
+
+/// # ColumnDivergence
+/// Largest divergence between the sim and the theory in a column they share.
+///
+/// # Fields
+/// * `column` - Name of the compared column. (String)
+/// * `max_abs_error` - Largest absolute difference between the sim and theory values. (f64)
+/// * `row` - Row of the largest difference. (usize)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnDivergence {
+    pub column: String,
+    pub max_abs_error: f64,
+    pub row: usize,
+}
+
+impl ColumnDivergence {
+    /// True if the divergence is more than the tolerance, or is not a number.
+    pub fn exceeds(&self, tolerance: f64) -> bool {
+        self.max_abs_error.is_nan() || self.max_abs_error > tolerance
+    }
+}
+
+/// Compares the sim csv at `sim_path` against the theory csv at `theory_path`,
+/// prints the divergence of each column and errors if any is more than `tolerance`.
+pub fn main(sim_path: &str, theory_path: &str, tolerance: f64) -> Result<(), Box<dyn Error>> {
+    let divergences = compare_paths(sim_path, theory_path)?;
+
+    println!("{}", "Validation results:".bright_yellow());
+    for divergence in divergences.iter() {
+        let line = format!(
+            "{}: max abs error {} at row {}",
+            divergence.column, divergence.max_abs_error, divergence.row
+        );
+        if divergence.exceeds(tolerance) {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line.green());
+        }
+    }
+
+    let failures = divergences
+        .iter()
+        .filter(|divergence| divergence.exceeds(tolerance))
+        .map(|divergence| divergence.column.clone())
+        .collect::<Vec<String>>();
+    if !failures.is_empty() {
+        return Err(format!(
+            "columns diverge from the theory by more than {}: {}",
+            tolerance,
+            failures.join(", ")
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Reads both csv files and compares them with `compare`.
+pub fn compare_paths(
+    sim_path: &str,
+    theory_path: &str,
+) -> Result<Vec<ColumnDivergence>, Box<dyn Error>> {
+    let sim = CsvReader::from_path(sim_path)?.finish()?;
+    let theory = CsvReader::from_path(theory_path)?.finish()?;
+    compare(&sim, &theory)
+}
+
+/// Divergence of each column of the theory from the same column of the sim, in the theory's column order.
+/// Errors if the sim is missing a column of the theory, or the two have a different number of rows.
+pub fn compare(
+    sim: &DataFrame,
+    theory: &DataFrame,
+) -> Result<Vec<ColumnDivergence>, Box<dyn Error>> {
+    if sim.height() != theory.height() {
+        return Err(format!(
+            "sim has {} rows but the theory has {}",
+            sim.height(),
+            theory.height()
+        )
+        .into());
+    }
+
+    let mut divergences = Vec::new();
+    for expected in theory.get_columns() {
+        let column = expected.name();
+        let actual = sim
+            .column(column)
+            .map_err(|_| format!("sim is missing the theory column {}", column))?;
+
+        let expected = expected.cast(&DataType::Float64)?;
+        let actual = actual.cast(&DataType::Float64)?;
+
+        let (row, max_abs_error) = actual
+            .f64()?
+            .into_iter()
+            .zip(expected.f64()?.into_iter())
+            // A missing or unparsable value is an infinite divergence.
+            .map(|(a, e)| match (a, e) {
+                (Some(a), Some(e)) if !(a - e).is_nan() => (a - e).abs(),
+                _ => f64::INFINITY,
+            })
+            .enumerate()
+            .fold((0, 0.0), |(max_row, max), (row, error)| {
+                if error > max {
+                    (row, error)
+                } else {
+                    (max_row, max)
+                }
+            });
+
+        divergences.push(ColumnDivergence {
+            column: column.to_string(),
+            max_abs_error,
+            row,
+        });
+    }
+
+    Ok(divergences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_csv(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn sim_agreeing_with_theory_passes_and_diverging_fails() {
+        let sim = write_csv(
+            "proto_sim_validate_sim.csv",
+            "ref_price,reported_price,pvf\n1.0,1.0,2.0\n1.1,1.1,2.1\n1.2,1.2,2.2\n",
+        );
+        let agreeing = write_csv(
+            "proto_sim_validate_theory_agreeing.csv",
+            "reported_price,pvf\n1.0,2.0\n1.1,2.1000001\n1.2,2.2\n",
+        );
+        let diverging = write_csv(
+            "proto_sim_validate_theory_diverging.csv",
+            "reported_price,pvf\n1.0,2.0\n1.1,2.1\n1.3,2.2\n",
+        );
+
+        assert!(main(&sim, &agreeing, 1e-6).is_ok());
+
+        let divergences = compare_paths(&sim, &diverging).unwrap();
+        assert_eq!(divergences[0].column, "reported_price");
+        assert_eq!(divergences[0].row, 2);
+        assert!(divergences[0].exceeds(1e-6));
+        assert!(!divergences[1].exceeds(1e-6));
+
+        let error = main(&sim, &diverging, 1e-6).unwrap_err();
+        assert!(error.to_string().contains("reported_price"));
+    }
+}